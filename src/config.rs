@@ -13,6 +13,7 @@
 
 use std::env;
 use std::path::PathBuf;
+use std::time::Duration;
 
 /// Server configuration
 ///
@@ -39,6 +40,45 @@ pub struct Config {
     /// Default website ID created on first run
     pub default_website_id: String,
 
+    /// Maximum number of versions kept in each website's history (0 disables history)
+    pub history_max_versions: usize,
+
+    /// Versions older than this number of days are removed from the history
+    pub history_max_age_days: i64,
+
+    /// Days deleted websites are kept in the trash (0 deletes them at once)
+    pub trash_retention_days: i64,
+
+    /// Hours between two deletions of the unused assets (0 disables it)
+    pub assets_gc_interval_hours: u64,
+
+    /// Store identical assets once, as links to content-addressed blobs
+    pub deduplicate_assets: bool,
+
+    /// Largest uploaded asset file, in megabytes (0 for no limit)
+    pub assets_max_file_size_mb: u64,
+
+    /// Largest total of the asset files of an upload request, in megabytes (0 for no limit)
+    pub assets_max_upload_size_mb: u64,
+
+    /// MIME types of the assets which can be uploaded, e.g. "image/*" or "application/pdf"
+    pub assets_allowed_types: Vec<String>,
+
+    /// Uploaded images larger than this width or height are downscaled
+    pub image_max_size: Option<u32>,
+
+    /// Remove the metadata (EXIF, XMP, comments) of the uploaded images
+    pub image_strip_metadata: bool,
+
+    /// Widths of the smaller copies made of the uploaded images
+    pub image_variant_widths: Vec<u32>,
+
+    /// Also make WebP copies of the uploaded PNG images
+    pub image_webp_variants: bool,
+
+    /// Directory of the cached asset thumbnails (default: `.thumbnails` in the data path)
+    pub thumbnails_path: Option<PathBuf>,
+
     /// Path to the dashboard UI (its index.html is served at `/` when no `?id=`)
     pub dashboard_path: Option<PathBuf>,
 
@@ -48,6 +88,28 @@ pub struct Config {
     /// Advanced static routes: list of "route:path" pairs
     /// Example: "/assets:./public/assets,/:./dist/client"
     pub static_routes: Vec<(String, PathBuf)>,
+
+    /// GitLab instance URL for the GitLab connectors
+    pub gitlab_domain: String,
+
+    /// GitLab OAuth application ID (GitLab connectors are enabled when set with the secret)
+    pub gitlab_client_id: Option<String>,
+
+    /// GitLab OAuth application secret
+    pub gitlab_client_secret: Option<String>,
+
+    /// Name of the GitLab connectors in the UI
+    pub gitlab_display_name: String,
+
+    /// Directory of the template library, one template website per folder
+    pub templates_path: Option<PathBuf>,
+
+    /// Connector plugin commands, started at launch and spoken to over JSON-RPC on stdio
+    /// Example: "/usr/local/bin/ftp-hosting,node ./s3-storage.js --bucket sites"
+    pub plugins: Vec<String>,
+
+    /// Seconds a connector plugin has to answer a call before it is killed (0 for no limit)
+    pub plugin_timeout_secs: u64,
 }
 
 impl Config {
@@ -59,6 +121,25 @@ impl Config {
     /// - SILEX_DATA_PATH: Website data storage path (default: "./data")
     /// - SILEX_HOSTING_PATH: Publication output path (default: "./public")
     /// - SILEX_ASSETS_FOLDER: Assets folder name (default: "assets")
+    /// - SILEX_HISTORY_MAX_VERSIONS: Versions kept per website (default: 50, 0 disables history)
+    /// - SILEX_HISTORY_MAX_AGE_DAYS: Maximum age of the kept versions (default: 30)
+    /// - SILEX_TRASH_RETENTION_DAYS: Days deleted websites are kept (default: 30, 0 disables the trash)
+    /// - SILEX_ASSETS_GC_INTERVAL_HOURS: Hours between deletions of unused assets (default: 0, disabled)
+    /// - SILEX_DEDUPLICATE_ASSETS: Store identical assets once, "true" or "false" (default: false)
+    /// - SILEX_ASSETS_MAX_FILE_SIZE_MB: Largest uploaded asset (default: 20, 0 for no limit)
+    /// - SILEX_ASSETS_MAX_UPLOAD_SIZE_MB: Largest asset upload request (default: 100, 0 for no limit)
+    /// - SILEX_ASSETS_ALLOWED_TYPES: Comma separated MIME types of the assets (default: images but SVG, video, audio, fonts and PDF)
+    /// - SILEX_IMAGE_MAX_SIZE: Maximum width and height of the uploaded images (default: none)
+    /// - SILEX_IMAGE_STRIP_METADATA: Remove the metadata of the uploaded images (default: true)
+    /// - SILEX_IMAGE_VARIANT_WIDTHS: Comma separated widths of the image copies (default: none)
+    /// - SILEX_IMAGE_WEBP: Also make WebP copies of the uploaded PNG images (default: false)
+    /// - SILEX_THUMBNAILS_PATH: Thumbnail cache directory (default: ".thumbnails" in the data path)
+    /// - SILEX_GITLAB_DOMAIN: GitLab instance URL (default: "https://gitlab.com")
+    /// - SILEX_GITLAB_CLIENT_ID / SILEX_GITLAB_CLIENT_SECRET: GitLab OAuth application
+    /// - SILEX_GITLAB_DISPLAY_NAME: GitLab connectors name (default: "GitLab")
+    /// - SILEX_TEMPLATES_PATH: Template library directory (default: none)
+    /// - SILEX_PLUGINS: Comma separated connector plugin commands
+    /// - SILEX_PLUGIN_TIMEOUT_SECS: Seconds a plugin has to answer a call (default: 120, 0 for no limit)
     pub fn from_env() -> Self {
         // Try to load .env file, but don't fail if it doesn't exist
         let _ = dotenvy::dotenv();
@@ -86,6 +167,71 @@ impl Config {
         let default_website_id =
             env::var("SILEX_DEFAULT_WEBSITE_ID").unwrap_or_else(|_| "default".to_string());
 
+        let history_max_versions = env::var("SILEX_HISTORY_MAX_VERSIONS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(50);
+
+        let history_max_age_days = env::var("SILEX_HISTORY_MAX_AGE_DAYS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(30);
+
+        let trash_retention_days = env::var("SILEX_TRASH_RETENTION_DAYS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(30);
+
+        let assets_gc_interval_hours = env::var("SILEX_ASSETS_GC_INTERVAL_HOURS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+
+        let deduplicate_assets = env_flag("SILEX_DEDUPLICATE_ASSETS", false);
+
+        // Limits of the asset uploads
+        let assets_max_file_size_mb = env::var("SILEX_ASSETS_MAX_FILE_SIZE_MB")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(20);
+
+        let assets_max_upload_size_mb = env::var("SILEX_ASSETS_MAX_UPLOAD_SIZE_MB")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(100);
+
+        let assets_allowed_types = env::var("SILEX_ASSETS_ALLOWED_TYPES")
+            .ok()
+            .map(|s| {
+                s.split(',')
+                    .map(|t| t.trim().to_lowercase())
+                    .filter(|t| !t.is_empty())
+                    .collect()
+            })
+            .unwrap_or_else(default_assets_allowed_types);
+
+        // Processing of the uploaded images
+        let image_max_size = env::var("SILEX_IMAGE_MAX_SIZE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|&size| size > 0);
+
+        let image_strip_metadata = env_flag("SILEX_IMAGE_STRIP_METADATA", true);
+
+        let image_variant_widths = env::var("SILEX_IMAGE_VARIANT_WIDTHS")
+            .ok()
+            .map(|s| {
+                s.split(',')
+                    .filter_map(|width| width.trim().parse().ok())
+                    .filter(|&width| width > 0)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let image_webp_variants = env_flag("SILEX_IMAGE_WEBP", false);
+
+        let thumbnails_path = env::var("SILEX_THUMBNAILS_PATH").ok().map(PathBuf::from);
+
         // Dashboard path (its index.html served at `/` when no `?id=`)
         let dashboard_path = env::var("SILEX_DASHBOARD_PATH").ok().map(PathBuf::from);
 
@@ -110,6 +256,42 @@ impl Config {
             })
             .unwrap_or_default();
 
+        // GitLab connectors (also accept the TypeScript server variable names)
+        let gitlab_domain = env::var("SILEX_GITLAB_DOMAIN")
+            .or_else(|_| env::var("GITLAB_DOMAIN"))
+            .unwrap_or_else(|_| default_gitlab_domain());
+
+        let gitlab_client_id = env::var("SILEX_GITLAB_CLIENT_ID")
+            .or_else(|_| env::var("GITLAB_CLIENT_ID"))
+            .ok();
+
+        let gitlab_client_secret = env::var("SILEX_GITLAB_CLIENT_SECRET")
+            .or_else(|_| env::var("GITLAB_CLIENT_SECRET"))
+            .ok();
+
+        let gitlab_display_name = env::var("SILEX_GITLAB_DISPLAY_NAME")
+            .or_else(|_| env::var("GITLAB_DISPLAY_NAME"))
+            .unwrap_or_else(|_| "GitLab".to_string());
+
+        let templates_path = env::var("SILEX_TEMPLATES_PATH").ok().map(PathBuf::from);
+
+        // Connector plugins, one command line per plugin
+        let plugins = env::var("SILEX_PLUGINS")
+            .ok()
+            .map(|s| {
+                s.split(',')
+                    .map(str::trim)
+                    .filter(|command| !command.is_empty())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let plugin_timeout_secs = env::var("SILEX_PLUGIN_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(120);
+
         Config {
             url,
             port,
@@ -117,9 +299,29 @@ impl Config {
             hosting_path,
             assets_folder,
             default_website_id,
+            history_max_versions,
+            history_max_age_days,
+            trash_retention_days,
+            assets_gc_interval_hours,
+            deduplicate_assets,
+            assets_max_file_size_mb,
+            assets_max_upload_size_mb,
+            assets_allowed_types,
+            image_max_size,
+            image_strip_metadata,
+            image_variant_widths,
+            image_webp_variants,
+            thumbnails_path,
             dashboard_path,
             static_path,
             static_routes,
+            gitlab_domain,
+            gitlab_client_id,
+            gitlab_client_secret,
+            gitlab_display_name,
+            templates_path,
+            plugins,
+            plugin_timeout_secs,
         }
     }
 
@@ -127,6 +329,18 @@ impl Config {
     pub fn server_url(&self) -> &str {
         &self.url
     }
+
+    /// Maximum duration of a connector plugin call, None for no limit
+    pub fn plugin_timeout(&self) -> Option<Duration> {
+        (self.plugin_timeout_secs > 0).then(|| Duration::from_secs(self.plugin_timeout_secs))
+    }
+
+    /// Get the thumbnail cache directory
+    pub fn thumbnails_path(&self) -> PathBuf {
+        self.thumbnails_path
+            .clone()
+            .unwrap_or_else(|| self.data_path.join(".thumbnails"))
+    }
 }
 
 /// Default data path: ./silex/storage relative to the current working directory.
@@ -134,6 +348,39 @@ fn default_data_path() -> PathBuf {
     PathBuf::from("./silex/storage")
 }
 
+/// Default MIME types of the assets: media, fonts and PDF documents
+///
+/// SVG images are left out, as they can hold scripts.
+fn default_assets_allowed_types() -> Vec<String> {
+    [
+        "image/png",
+        "image/jpeg",
+        "image/gif",
+        "image/webp",
+        "image/avif",
+        "image/bmp",
+        "image/x-icon",
+        "video/*",
+        "audio/*",
+        "font/*",
+        "application/pdf",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+/// Read a boolean environment variable, "true", "1" or "yes" being true
+fn env_flag(name: &str, default: bool) -> bool {
+    env::var(name)
+        .map(|v| matches!(v.to_lowercase().as_str(), "true" | "1" | "yes"))
+        .unwrap_or(default)
+}
+
+/// Default GitLab instance
+fn default_gitlab_domain() -> String {
+    "https://gitlab.com".to_string()
+}
+
 impl Default for Config {
     fn default() -> Self {
         Config {
@@ -143,9 +390,29 @@ impl Default for Config {
             hosting_path: None,
             assets_folder: "assets".to_string(),
             default_website_id: "default".to_string(),
+            history_max_versions: 50,
+            history_max_age_days: 30,
+            trash_retention_days: 30,
+            assets_gc_interval_hours: 0,
+            deduplicate_assets: false,
+            assets_max_file_size_mb: 20,
+            assets_max_upload_size_mb: 100,
+            assets_allowed_types: default_assets_allowed_types(),
+            image_max_size: None,
+            image_strip_metadata: true,
+            image_variant_widths: Vec::new(),
+            image_webp_variants: false,
+            thumbnails_path: None,
             dashboard_path: None,
             static_path: None,
             static_routes: Vec::new(),
+            gitlab_domain: default_gitlab_domain(),
+            gitlab_client_id: None,
+            gitlab_client_secret: None,
+            gitlab_display_name: "GitLab".to_string(),
+            templates_path: None,
+            plugins: Vec::new(),
+            plugin_timeout_secs: 120,
         }
     }
 }
@@ -13,7 +13,7 @@
 //! This is useful for local development and testing.
 
 use async_trait::async_trait;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tokio::fs;
 
 use crate::connectors::traits::{ConnectorInfo, HostingConnector};
@@ -88,7 +88,7 @@ impl FsHosting {
     /// This is the core publication logic.
     async fn write_files(
         &self,
-        target_dir: &PathBuf,
+        target_dir: &Path,
         files: &[ConnectorFile],
         job: &mut PublicationJobData,
     ) -> ConnectorResult<()> {
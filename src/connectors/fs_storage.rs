@@ -15,20 +15,45 @@
 //! - meta.json (metadata file)
 //! - assets/ (uploaded assets)
 //! - pages/ (individual page files)
+//! - history/ (snapshots of previous saves)
+//!
+//! Deleted websites are moved to `.trash/` and purged after a retention period.
+//!
+//! With asset deduplication, asset files are hard links to content-addressed
+//! blobs in `.blobs/`, named after the SHA-256 of their content. The same bytes
+//! are stored once whatever the number of websites and names using them, and
+//! the link count of a blob is its reference count: blobs with no other link
+//! are purged. Saves replace files and never write them in place, so a
+//! duplicate shares all the files of the original until either changes them.
+//!
+//! Operations on a website are serialized with a per-website read/write lock,
+//! doubled with an advisory file lock in `.locks/` for the processes sharing
+//! the same data path. Website IDs are checked before locking, and only the
+//! websites which exist get a lock file.
 
 use async_trait::async_trait;
-use chrono::{DateTime, Utc};
-use std::collections::HashSet;
-use std::path::PathBuf;
+use chrono::{DateTime, Duration, Utc};
+use sha2::{Digest, Sha256};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use tokio::fs;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::sync::{OwnedRwLockReadGuard, OwnedRwLockWriteGuard, RwLock};
 use uuid::Uuid;
 
-use crate::connectors::traits::{to_connector_data, ConnectorInfo, StorageConnector};
+use crate::connectors::traits::{
+    page_not_found, session_user, to_connector_data, ConnectorInfo, StorageConnector,
+};
+use crate::connectors::website_files;
 use crate::error::{ConnectorError, ConnectorResult};
 use crate::models::{
-    constants, ConnectorFile, ConnectorOptions, ConnectorType, ConnectorUser, WebsiteData,
-    WebsiteId, WebsiteMeta, WebsiteMetaFileContent,
+    constants, page_id_of, page_revision, AssetInfo, AssetStream, ConnectorFile, ConnectorOptions,
+    ConnectorType, ConnectorUser, IntegrityReport, TrashedWebsite, WebsiteData, WebsiteId,
+    WebsiteMeta, WebsiteMetaFileContent, WebsiteVersion, CURRENT_SCHEMA_VERSION,
 };
+use crate::services::validate_page;
 
 /// Icon for filesystem connector (user silhouette SVG as data URI)
 const USER_ICON: &str = "data:image/svg+xml,%3Csvg xmlns='http://www.w3.org/2000/svg' height='1em' viewBox='0 0 448 512'%3E%3Cpath d='M304 128a80 80 0 1 0 -160 0 80 80 0 1 0 160 0zM96 128a128 128 0 1 1 256 0A128 128 0 1 1 96 128zM49.3 464H398.7c-8.9-63.3-63.3-112-129-112H178.3c-65.7 0-120.1 48.7-129 112zM0 482.3C0 383.8 79.8 304 178.3 304h91.4C368.2 304 448 383.8 448 482.3c0 16.4-13.3 29.7-29.7 29.7H29.7C13.3 512 0 498.7 0 482.3z'/%3E%3C/svg%3E";
@@ -36,6 +61,30 @@ const USER_ICON: &str = "data:image/svg+xml,%3Csvg xmlns='http://www.w3.org/2000
 /// Icon for the connector (laptop icon)
 const FILE_ICON: &str = "/assets/laptop.png";
 
+/// Folder where snapshots of the website data are kept
+const HISTORY_FOLDER: &str = "history";
+
+/// Folder of the advisory lock files, in the data path
+const LOCKS_FOLDER: &str = ".locks";
+
+/// Default maximum number of versions kept per website
+const DEFAULT_HISTORY_MAX_VERSIONS: usize = 50;
+
+/// Default maximum age of the versions kept per website, in days
+const DEFAULT_HISTORY_MAX_AGE_DAYS: i64 = 30;
+
+/// Shortest time between two versions kept by page saves, in minutes
+const PAGE_VERSION_INTERVAL_MINUTES: i64 = 5;
+
+/// Folder of the deleted websites, in the data path
+const TRASH_FOLDER: &str = ".trash";
+
+/// Default number of days deleted websites are kept in the trash
+const DEFAULT_TRASH_RETENTION_DAYS: i64 = 30;
+
+/// Folder of the content-addressed asset blobs, in the data path
+const BLOBS_FOLDER: &str = ".blobs";
+
 /// Filesystem storage connector
 ///
 /// Stores websites in a directory structure:
@@ -48,6 +97,13 @@ const FILE_ICON: &str = "/assets/laptop.png";
 ///       image.png
 ///     pages/
 ///       index-abc123.json
+///     history/
+///       1760000000000-0123456789ab.json
+///   .trash/
+///     {deleted_at_millis}-{website_id}/
+///   .blobs/
+///     ab/
+///       ab12...ef (SHA-256 of the content)
 /// ```
 pub struct FsStorage {
     /// Root path where all websites are stored
@@ -55,6 +111,61 @@ pub struct FsStorage {
 
     /// Folder name for assets within each website
     assets_folder: String,
+
+    /// Maximum number of versions kept in each website's history (0 disables history)
+    history_max_versions: usize,
+
+    /// Versions older than this are removed from the history (the latest is always kept)
+    history_max_age: Duration,
+
+    /// How long deleted websites are kept in the trash (zero deletes them at once)
+    trash_retention: Duration,
+
+    /// Whether asset files are links to content-addressed blobs
+    deduplicate_assets: bool,
+
+    /// In-process locks, by website ID, removed when no task holds or waits for them
+    locks: WebsiteLocks,
+}
+
+/// In-process locks, by website ID
+type WebsiteLocks = Arc<Mutex<HashMap<String, Arc<RwLock<()>>>>>;
+
+/// Lock on a website, released when dropped
+///
+/// Holds the in-process lock and the advisory file lock.
+struct WebsiteLock {
+    /// Shared in-process lock, for readers
+    read: Option<OwnedRwLockReadGuard<()>>,
+
+    /// Exclusive in-process lock, for writers
+    write: Option<OwnedRwLockWriteGuard<()>>,
+
+    /// Lock file, unlocked when closed
+    _file: std::fs::File,
+
+    /// The locks of the storage, to remove this one when it is not used anymore
+    locks: WebsiteLocks,
+
+    /// ID of the locked website
+    website_id: String,
+}
+
+impl Drop for WebsiteLock {
+    fn drop(&mut self) {
+        self.read.take();
+        self.write.take();
+
+        // The map holds the last reference when no other task holds or waits for the lock,
+        // references are only taken with the map locked
+        let mut locks = self.locks.lock().unwrap_or_else(|e| e.into_inner());
+        if locks
+            .get(&self.website_id)
+            .is_some_and(|lock| Arc::strong_count(lock) == 1)
+        {
+            locks.remove(&self.website_id);
+        }
+    }
 }
 
 impl FsStorage {
@@ -67,6 +178,141 @@ impl FsStorage {
         FsStorage {
             data_path,
             assets_folder,
+            history_max_versions: DEFAULT_HISTORY_MAX_VERSIONS,
+            history_max_age: Duration::days(DEFAULT_HISTORY_MAX_AGE_DAYS),
+            trash_retention: Duration::days(DEFAULT_TRASH_RETENTION_DAYS),
+            deduplicate_assets: false,
+            locks: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Set the limits of the version history
+    ///
+    /// # Arguments
+    /// * `max_versions` - Maximum number of versions kept per website, 0 disables history
+    /// * `max_age` - Versions older than this are removed, except the latest one
+    pub fn with_history(mut self, max_versions: usize, max_age: Duration) -> Self {
+        self.history_max_versions = max_versions;
+        self.history_max_age = max_age;
+        self
+    }
+
+    /// Set how long deleted websites are kept in the trash
+    ///
+    /// A zero retention disables the trash: deleted websites are removed at once.
+    pub fn with_trash(mut self, retention: Duration) -> Self {
+        self.trash_retention = retention;
+        self
+    }
+
+    /// Store the assets once per content, in the blob store
+    ///
+    /// Assets written before are not converted, they are stored as they are.
+    pub fn with_asset_deduplication(mut self, enabled: bool) -> Self {
+        self.deduplicate_assets = enabled;
+        self
+    }
+
+    /// Get the in-process lock of a website
+    fn website_lock(&self, website_id: &str) -> Arc<RwLock<()>> {
+        let mut locks = self.locks.lock().unwrap_or_else(|e| e.into_inner());
+        locks.entry(website_id.to_string()).or_default().clone()
+    }
+
+    /// Open and lock a website's lock file, waiting for other processes
+    ///
+    /// The website ID must have been checked with `check_website_id`.
+    async fn lock_file(&self, website_id: &str, exclusive: bool) -> ConnectorResult<std::fs::File> {
+        let locks_path = self.data_path.join(LOCKS_FOLDER);
+        let path = locks_path.join(format!("{}.lock", website_id));
+
+        let file = tokio::task::spawn_blocking(move || {
+            std::fs::create_dir_all(&locks_path)?;
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(&path)?;
+            if exclusive {
+                file.lock()?;
+            } else {
+                file.lock_shared()?;
+            }
+            Ok::<_, std::io::Error>(file)
+        })
+        .await
+        .map_err(std::io::Error::other)??;
+
+        Ok(file)
+    }
+
+    /// Lock a website for reading, other readers are allowed
+    ///
+    /// The in-process lock is taken first so that the file lock
+    /// is only waited for when other processes hold it.
+    /// Fails with NotFound when the website does not exist.
+    async fn read_lock(&self, website_id: &str) -> ConnectorResult<WebsiteLock> {
+        self.check_website_exists(website_id).await?;
+        let guard = self.website_lock(website_id).read_owned().await;
+        // The website may have been deleted while waiting
+        self.check_website_exists(website_id).await?;
+        let file = self.lock_file(website_id, false).await?;
+        let lock = WebsiteLock {
+            read: Some(guard),
+            write: None,
+            _file: file,
+            locks: self.locks.clone(),
+            website_id: website_id.to_string(),
+        };
+        // Or by another process
+        self.check_website_exists(website_id).await?;
+        Ok(lock)
+    }
+
+    /// Lock a website for writing, waiting for readers and other writers
+    ///
+    /// Fails with NotFound when the website does not exist, or was deleted
+    /// while waiting for the lock: saves waiting behind a deletion never
+    /// create the website again.
+    async fn write_lock(&self, website_id: &str) -> ConnectorResult<WebsiteLock> {
+        self.check_website_exists(website_id).await?;
+        let guard = self.website_lock(website_id).write_owned().await;
+        self.check_website_exists(website_id).await?;
+        let file = self.lock_file(website_id, true).await?;
+        let lock = WebsiteLock {
+            read: None,
+            write: Some(guard),
+            _file: file,
+            locks: self.locks.clone(),
+            website_id: website_id.to_string(),
+        };
+        self.check_website_exists(website_id).await?;
+        Ok(lock)
+    }
+
+    /// Lock a website which may not exist yet for writing, e.g. to restore it
+    async fn new_website_lock(&self, website_id: &str) -> ConnectorResult<WebsiteLock> {
+        check_website_id(website_id)?;
+        let guard = self.website_lock(website_id).write_owned().await;
+        let file = self.lock_file(website_id, true).await?;
+        Ok(WebsiteLock {
+            read: None,
+            write: Some(guard),
+            _file: file,
+            locks: self.locks.clone(),
+            website_id: website_id.to_string(),
+        })
+    }
+
+    /// Check that a website ID is valid and that the website exists
+    async fn check_website_exists(&self, website_id: &str) -> ConnectorResult<()> {
+        check_website_id(website_id)?;
+        match fs::metadata(self.website_path(website_id)).await {
+            Ok(metadata) if metadata.is_dir() => Ok(()),
+            _ => Err(ConnectorError::NotFound(format!(
+                "Website '{}' not found",
+                website_id
+            ))),
         }
     }
 
@@ -77,7 +323,8 @@ impl FsStorage {
 
     /// Get the path to a website's data file
     fn website_data_path(&self, website_id: &str) -> PathBuf {
-        self.website_path(website_id).join(constants::WEBSITE_DATA_FILE)
+        self.website_path(website_id)
+            .join(constants::WEBSITE_DATA_FILE)
     }
 
     /// Get the path to a website's metadata file
@@ -91,504 +338,659 @@ impl FsStorage {
         self.website_path(website_id).join(&self.assets_folder)
     }
 
-    /// Initialize the data directory and create a default website if needed
-    pub async fn init(&self, default_website_id: &str) -> ConnectorResult<()> {
-        let default_path = self.website_path(default_website_id);
-
-        // Check if the default website already exists
-        if fs::metadata(&default_path).await.is_ok() {
-            return Ok(());
+    /// Get the path to an asset file, rejecting paths which would leave the assets folder
+    fn asset_file_path(&self, website_id: &str, file_name: &str) -> ConnectorResult<PathBuf> {
+        let relative_path = file_name.trim_start_matches('/');
+        if relative_path
+            .split(['/', '\\'])
+            .any(|part| part.is_empty() || part == "." || part == "..")
+        {
+            return Err(ConnectorError::InvalidInput(format!(
+                "Invalid asset path '{}'",
+                file_name
+            )));
         }
-
-        // Create the default website directory with assets folder
-        fs::create_dir_all(self.assets_path(default_website_id)).await?;
-
-        // Create the default metadata
-        let meta = WebsiteMetaFileContent {
-            name: "Default website".to_string(),
-            image_url: None,
-            connector_user_settings: Default::default(),
-        };
-        let default_id = default_website_id.to_string();
-        self.set_website_meta(&serde_json::json!({}), &default_id, &meta)
-            .await?;
-
-        // Create the default website data
-        self.update_website(
-            &serde_json::json!({}),
-            &default_id,
-            &WebsiteData::default(),
-        )
-        .await?;
-
-        tracing::info!(
-            "Created default website '{}' in {}",
-            default_website_id,
-            self.data_path.display()
-        );
-
-        Ok(())
+        Ok(self.assets_path(website_id).join(relative_path))
     }
 
-    /// Serialize data to JSON with sorted keys for stable output
-    fn serialize_json<T: serde::Serialize>(data: &T) -> ConnectorResult<String> {
-        // Serialize to Value first, then to string with sorted keys
-        let value = serde_json::to_value(data)?;
-        let sorted = sort_json_keys(&value);
-        Ok(serde_json::to_string_pretty(&sorted)?)
+    /// Get the path to a website's history folder
+    fn history_path(&self, website_id: &str) -> PathBuf {
+        self.website_path(website_id).join(HISTORY_FOLDER)
     }
 
-    /// Get the pages folder path from website data
-    fn get_pages_folder(data: &WebsiteData) -> &str {
-        if data.pages_folder.is_empty() {
-            constants::LEGACY_WEBSITE_PAGES_FOLDER
-        } else {
-            &data.pages_folder
+    /// Get the path to a version snapshot, rejecting IDs which are not ours
+    fn version_path(&self, website_id: &str, version_id: &str) -> ConnectorResult<PathBuf> {
+        let valid = !version_id.is_empty()
+            && version_id
+                .chars()
+                .all(|c| c.is_ascii_hexdigit() || c == '-');
+        if !valid {
+            return Err(ConnectorError::InvalidInput(format!(
+                "Invalid version ID '{}'",
+                version_id
+            )));
         }
+        Ok(self
+            .history_path(website_id)
+            .join(format!("{}.json", version_id)))
     }
 
-    /// Get a slug from a page name (for file naming)
-    fn get_page_slug(name: &str) -> String {
-        name.to_lowercase()
-            .chars()
-            .map(|c| if c.is_alphanumeric() { c } else { '-' })
-            .collect::<String>()
-            .trim_matches('-')
-            .to_string()
+    /// Get the path to the blob of a content
+    fn blob_path(&self, content: &[u8]) -> PathBuf {
+        self.blob_path_for_hash(Sha256::digest(content).as_slice())
     }
 
-    /// Split website data into separate files (website.json + individual pages)
-    fn split_website_data(
-        data: &WebsiteData,
-    ) -> ConnectorResult<Vec<(String, String)>> {
-        let mut files = Vec::new();
-        let pages_folder = Self::get_pages_folder(data);
-
-        // Process each page
-        let mut page_refs = Vec::new();
-        for page in &data.pages {
-            // Get page ID and name
-            let page_id = page.get("id").and_then(|v| v.as_str());
-            let page_name = page
-                .get("name")
-                .and_then(|v| v.as_str())
-                .unwrap_or("page");
-
-            // Skip empty pages (like the {} from EMPTY_PAGES in tests)
-            if page_id.is_none() {
-                page_refs.push(page.clone());
-                continue;
-            }
+    /// Get the path to the blob of a content, from its SHA-256
+    fn blob_path_for_hash(&self, hash: &[u8]) -> PathBuf {
+        let hash: String = hash.iter().map(|b| format!("{:02x}", b)).collect();
+        self.data_path
+            .join(BLOBS_FOLDER)
+            .join(&hash[..2])
+            .join(hash)
+    }
 
-            let page_id = page_id.unwrap();
-            let slug = Self::get_page_slug(page_name);
-            let file_name = format!("{}-{}.json", slug, page_id);
-            let file_path = format!("{}/{}", pages_folder, file_name);
+    /// Write an asset file, as a link to its blob when deduplicating
+    ///
+    /// Falls back to a plain file when the blob can not be linked, e.g. when
+    /// it was purged in between or the filesystem has no hard links.
+    async fn write_asset_file(&self, path: &Path, content: &[u8]) -> ConnectorResult<()> {
+        if !self.deduplicate_assets {
+            return write_atomic(path, content).await;
+        }
 
-            // Write the page file
-            let page_content = Self::serialize_json(page)?;
-            files.push((file_path, page_content));
+        let blob_path = self.blob_path(content);
+        if fs::metadata(&blob_path).await.is_err() {
+            if let Some(parent) = blob_path.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+            write_atomic(&blob_path, content).await?;
+        }
 
-            // Create a reference to the page file
-            page_refs.push(serde_json::json!({
-                "name": page_name,
-                "id": page_id,
-                "isFile": true
-            }));
+        if !link_blob(&blob_path, path).await? {
+            return write_atomic(path, content).await;
         }
+        Ok(())
+    }
 
-        // Create the main website.json with page references instead of full pages
-        let website_data_with_refs = serde_json::json!({
-            "pages": page_refs,
-            "pagesFolder": pages_folder,
-            "assets": data.assets,
-            "styles": data.styles,
-            "settings": data.settings,
-            "fonts": data.fonts,
-            "symbols": data.symbols,
-            "publication": data.publication,
-        });
+    /// Purge the blobs which no asset file links to anymore
+    ///
+    /// Returns the number of blobs purged. Run periodically by the server.
+    /// Link counts are only available on Unix, elsewhere blobs are kept.
+    pub async fn purge_unused_blobs(&self) -> ConnectorResult<usize> {
+        let mut purged = 0;
+
+        let blobs_path = self.data_path.join(BLOBS_FOLDER);
+        for path in list_files_recursive(blobs_path.clone(), String::new()).await? {
+            let blob_path = blobs_path.join(&path);
+            let Ok(metadata) = fs::metadata(&blob_path).await else {
+                continue;
+            };
+            if link_count(&metadata) != Some(1) {
+                continue;
+            }
+            // An asset linked in between falls back to a plain file
+            match fs::remove_file(&blob_path).await {
+                Ok(()) => {
+                    purged += 1;
+                    remove_empty_dirs(&blob_path, &blobs_path).await;
+                }
+                Err(e) => tracing::warn!("Could not purge blob {}: {}", blob_path.display(), e),
+            }
+        }
 
-        let website_content = Self::serialize_json(&website_data_with_refs)?;
-        files.push((constants::WEBSITE_DATA_FILE.to_string(), website_content));
+        Ok(purged)
+    }
 
-        Ok(files)
+    /// Get the path to the trash folder
+    fn trash_path(&self) -> PathBuf {
+        self.data_path.join(TRASH_FOLDER)
     }
 
-    /// Merge website data from main file and page files
-    async fn merge_website_data(
-        &self,
-        website_id: &str,
-        website_content: &str,
-    ) -> ConnectorResult<WebsiteData> {
-        let mut parsed: serde_json::Value = serde_json::from_str(website_content)?;
+    /// Get the path to a trash entry and the ID of its website, rejecting IDs which are not ours
+    fn trash_entry_path(&self, trash_id: &str) -> ConnectorResult<(PathBuf, WebsiteId)> {
+        let website_id = parse_trash_id(trash_id)
+            .map(|(_, website_id)| website_id)
+            .ok_or_else(|| {
+                ConnectorError::InvalidInput(format!("Invalid trash ID '{}'", trash_id))
+            })?;
+        Ok((self.trash_path().join(trash_id), website_id))
+    }
 
-        // Get pages folder
-        let pages_folder = parsed
-            .get("pagesFolder")
-            .and_then(|v| v.as_str())
-            .unwrap_or(constants::LEGACY_WEBSITE_PAGES_FOLDER);
+    /// Read the websites in the trash, newest first
+    async fn read_trash(&self) -> ConnectorResult<Vec<TrashedWebsite>> {
+        let mut websites = Vec::new();
 
-        // Check if we have page references to load
-        let pages = match parsed.get("pages") {
-            Some(serde_json::Value::Array(pages)) if !pages.is_empty() => pages.clone(),
-            _ => return Ok(serde_json::from_value(parsed)?),
+        let mut entries = match fs::read_dir(self.trash_path()).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(websites),
+            Err(e) => return Err(e.into()),
         };
 
-        // Check if pages are already embedded (no isFile field)
-        if pages
-            .first()
-            .map(|p| !p.get("isFile").is_some())
-            .unwrap_or(true)
-        {
-            return Ok(serde_json::from_value(parsed)?);
+        while let Some(entry) = entries.next_entry().await? {
+            let trash_id = entry.file_name().to_string_lossy().to_string();
+            let Some((deleted_at, website_id)) = parse_trash_id(&trash_id) else {
+                continue;
+            };
+
+            // The name is informative, a website without metadata is still listed
+            let name = fs::read(entry.path().join(constants::WEBSITE_META_DATA_FILE))
+                .await
+                .ok()
+                .and_then(|content| serde_json::from_slice::<WebsiteMetaFileContent>(&content).ok())
+                .map(|meta| meta.name)
+                .unwrap_or_else(|| website_id.clone());
+
+            websites.push(TrashedWebsite {
+                trash_id,
+                website_id,
+                name,
+                deleted_at,
+                expires_at: deleted_at + self.trash_retention,
+            });
         }
 
-        // Load pages from separate files
-        let mut loaded_pages = Vec::new();
-        for page_ref in pages {
-            let is_file = page_ref.get("isFile").and_then(|v| v.as_bool()).unwrap_or(false);
+        websites.sort_by_key(|w| std::cmp::Reverse(w.deleted_at));
+        Ok(websites)
+    }
 
-            if is_file {
-                let page_name = page_ref.get("name").and_then(|v| v.as_str()).unwrap_or("page");
-                let page_id = page_ref.get("id").and_then(|v| v.as_str()).unwrap_or("");
+    /// Permanently delete a trash entry
+    async fn remove_trash_entry(&self, trash_id: &str) -> ConnectorResult<()> {
+        let (path, _) = self.trash_entry_path(trash_id)?;
+        fs::remove_dir_all(&path).await.map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                ConnectorError::NotFound(format!("Trash entry '{}' not found", trash_id))
+            } else {
+                ConnectorError::Io(e)
+            }
+        })
+    }
 
-                let slug = Self::get_page_slug(page_name);
-                let file_name = format!("{}-{}.json", slug, page_id);
-                let file_path = self.website_path(website_id).join(pages_folder).join(&file_name);
+    /// Purge the websites kept in the trash longer than the retention period
+    ///
+    /// Returns the number of websites purged. Run periodically by the server.
+    pub async fn purge_expired_trash(&self) -> ConnectorResult<usize> {
+        let now = Utc::now();
+        let mut purged = 0;
 
-                match fs::read_to_string(&file_path).await {
-                    Ok(content) => {
-                        let page: serde_json::Value = serde_json::from_str(&content)?;
-                        loaded_pages.push(page);
-                    }
-                    Err(e) => {
-                        tracing::warn!("Could not load page file {}: {}", file_path.display(), e);
-                        loaded_pages.push(page_ref);
-                    }
+        for website in self.read_trash().await? {
+            if website.expires_at > now {
+                continue;
+            }
+            match self.remove_trash_entry(&website.trash_id).await {
+                Ok(()) => purged += 1,
+                Err(e) => {
+                    tracing::warn!("Could not purge {} from the trash: {}", website.trash_id, e)
                 }
-            } else {
-                loaded_pages.push(page_ref);
             }
         }
 
-        // Replace pages with loaded content
-        parsed["pages"] = serde_json::Value::Array(loaded_pages);
-
-        Ok(serde_json::from_value(parsed)?)
-    }
-}
-
-impl ConnectorInfo for FsStorage {
-    fn connector_id(&self) -> &str {
-        "fs-storage"
-    }
-
-    fn connector_type(&self) -> ConnectorType {
-        ConnectorType::Storage
+        Ok(purged)
     }
 
-    fn display_name(&self) -> &str {
-        "File system storage"
-    }
+    /// Read the versions in a website's history, newest first
+    async fn read_history(&self, website_id: &str) -> ConnectorResult<Vec<WebsiteVersion>> {
+        let mut versions = Vec::new();
 
-    fn icon(&self) -> &str {
-        FILE_ICON
-    }
+        let mut entries = match fs::read_dir(self.history_path(website_id)).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(versions),
+            Err(e) => return Err(e.into()),
+        };
 
-    fn color(&self) -> &str {
-        "#ffffff"
-    }
+        while let Some(entry) = entries.next_entry().await? {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            // Snapshot files are named {timestamp}-{revision}.json
+            let Some(version_id) = file_name.strip_suffix(".json") else {
+                continue;
+            };
+            let Some((timestamp, revision)) = version_id.split_once('-') else {
+                continue;
+            };
+            let Some(created_at) = timestamp
+                .parse()
+                .ok()
+                .and_then(DateTime::<Utc>::from_timestamp_millis)
+            else {
+                continue;
+            };
+
+            versions.push(WebsiteVersion {
+                version_id: version_id.to_string(),
+                created_at,
+                revision: revision.to_string(),
+                size: entry.metadata().await?.len(),
+            });
+        }
 
-    fn background(&self) -> &str {
-        "#006400"
+        versions.sort_by_key(|v| std::cmp::Reverse(v.created_at));
+        Ok(versions)
     }
 
-    fn disable_logout(&self) -> bool {
-        // FsStorage has no authentication, so hide logout button
-        true
-    }
-}
+    /// Save a snapshot of the website data in its history and prune old versions
+    async fn save_version(&self, website_id: &str, data: &WebsiteData) -> ConnectorResult<()> {
+        if self.history_max_versions == 0 {
+            return Ok(());
+        }
 
-#[async_trait]
-impl StorageConnector for FsStorage {
-    // ==================
-    // Authentication
-    // FsStorage has no authentication - always logged in
-    // ==================
+        let history = self.read_history(website_id).await?;
 
-    async fn is_logged_in(&self, _session: &serde_json::Value) -> ConnectorResult<bool> {
-        // Filesystem storage has no authentication
-        Ok(true)
-    }
+        // Saving the same content again does not make a new version
+        let revision = data.revision()[..12].to_string();
+        if history
+            .first()
+            .map(|v| v.revision == revision)
+            .unwrap_or(false)
+        {
+            return Ok(());
+        }
 
-    async fn get_oauth_url(&self, _session: &serde_json::Value) -> ConnectorResult<Option<String>> {
-        // No OAuth for filesystem storage
-        Ok(None)
-    }
+        let now = Utc::now();
+        let version_id = format!("{}-{}", now.timestamp_millis(), revision);
+        let history_path = self.history_path(website_id);
+        fs::create_dir_all(&history_path).await?;
+        write_atomic(
+            &history_path.join(format!("{}.json", version_id)),
+            website_files::serialize_json(data)?.as_bytes(),
+        )
+        .await?;
 
-    async fn set_token(
-        &self,
-        _session: &mut serde_json::Value,
-        _token: &serde_json::Value,
-    ) -> ConnectorResult<()> {
-        // No tokens for filesystem storage
-        Ok(())
-    }
+        // Prune by count and age, the new version comes first and is always kept
+        for (index, version) in history.iter().enumerate() {
+            let position = index + 1;
+            if position >= self.history_max_versions
+                || now - version.created_at > self.history_max_age
+            {
+                let path = history_path.join(format!("{}.json", version.version_id));
+                if let Err(e) = fs::remove_file(&path).await {
+                    tracing::warn!("Could not remove old version {}: {}", path.display(), e);
+                }
+            }
+        }
 
-    async fn logout(&self, _session: &mut serde_json::Value) -> ConnectorResult<()> {
-        // No logout for filesystem storage
         Ok(())
     }
 
-    async fn get_user(&self, session: &serde_json::Value) -> ConnectorResult<ConnectorUser> {
-        // Return the current system username
-        let username = whoami::username().unwrap_or_else(|_| "unknown".to_string());
-
-        Ok(ConnectorUser {
-            name: username,
-            email: None,
-            picture: Some(USER_ICON.to_string()),
-            storage: to_connector_data(session, self).await?,
-        })
-    }
-
-    fn get_options(&self, _form_data: &serde_json::Value) -> ConnectorOptions {
-        // No options for filesystem storage
-        ConnectorOptions::default()
-    }
-
-    // ==================
-    // Website CRUD
-    // ==================
-
-    async fn list_websites(&self, session: &serde_json::Value) -> ConnectorResult<Vec<WebsiteMeta>> {
-        let mut websites = Vec::new();
+    /// Recover from interrupted saves
+    ///
+    /// Saves write every file to a temporary file which is then renamed, and
+    /// write page files before the website.json which references them.
+    /// So after a crash, the data is consistent and only needs cleaning:
+    /// temporary files and directories are removed. Page files which
+    /// website.json does not reference are left from an interrupted save, or
+    /// are pages it lost: they are renamed with an `.orphan` extension rather
+    /// than deleted, so that they can still be restored by hand.
+    pub async fn recover(&self) -> ConnectorResult<()> {
+        let blobs_path = self.data_path.join(BLOBS_FOLDER);
+        if fs::metadata(&blobs_path).await.is_ok() {
+            remove_temp_files(blobs_path).await?;
+        }
 
-        // List all directories in the data path
         let mut entries = fs::read_dir(&self.data_path).await?;
 
         while let Some(entry) = entries.next_entry().await? {
-            let metadata = fs::metadata(entry.path()).await?;
-            if !metadata.is_dir() {
+            let name = entry.file_name().to_string_lossy().to_string();
+
+            // Interrupted duplicate
+            if is_temp_name(&name) {
+                tracing::warn!("Removing interrupted copy {}", entry.path().display());
+                remove_temp(&entry.path()).await;
                 continue;
             }
 
-            let website_id = entry.file_name().to_string_lossy().to_string();
-
-            // Try to get metadata for this website
-            match self.get_website_meta(session, &website_id).await {
-                Ok(meta) => websites.push(meta),
-                Err(e) => {
-                    tracing::warn!("Failed to get metadata for website {}: {}", website_id, e);
-                }
+            // Not a website, e.g. the lock files
+            if name.starts_with('.') || !entry.file_type().await?.is_dir() {
+                continue;
             }
-        }
 
-        Ok(websites)
-    }
+            remove_temp_files(entry.path()).await?;
 
-    async fn read_website(
-        &self,
-        _session: &serde_json::Value,
-        website_id: &WebsiteId,
-    ) -> ConnectorResult<WebsiteData> {
-        let path = self.website_data_path(website_id);
+            // Set aside the page files left by a save interrupted before its cleanup
+            let Ok(content) = fs::read_to_string(self.website_data_path(&name)).await else {
+                continue;
+            };
+            let Ok(parsed) = serde_json::from_str::<serde_json::Value>(&content) else {
+                tracing::warn!("Could not parse {}, skipping recovery", name);
+                continue;
+            };
+            let Some(pages_folder) = parsed.get("pagesFolder").and_then(|f| f.as_str()) else {
+                continue;
+            };
+            let referenced = website_files::referenced_page_files(&parsed);
+            set_aside_orphan_pages(&entry.path().join(pages_folder), &referenced).await;
+        }
 
-        // Read the main website data file
-        let content = fs::read_to_string(&path).await.map_err(|e| {
-            if e.kind() == std::io::ErrorKind::NotFound {
-                ConnectorError::NotFound(format!("Website '{}' not found", website_id))
-            } else {
-                ConnectorError::Io(e)
+        Ok(())
+    }
+
+    /// Initialize the data directory and create a default website if needed
+    pub async fn init(&self, default_website_id: &str) -> ConnectorResult<()> {
+        // Clean up after saves interrupted by a crash, the server can start without it
+        if fs::metadata(&self.data_path).await.is_ok() {
+            if let Err(e) = self.recover().await {
+                tracing::error!("Could not recover from interrupted saves: {}", e);
             }
-        })?;
+        }
 
-        // Merge with page files if using split format
-        self.merge_website_data(website_id, &content).await
-    }
+        let default_path = self.website_path(default_website_id);
 
-    async fn create_website(
-        &self,
-        session: &serde_json::Value,
-        meta: &WebsiteMetaFileContent,
-    ) -> ConnectorResult<WebsiteId> {
-        // Generate a new UUID for the website
-        let website_id = Uuid::new_v4().to_string();
+        // Check if the default website already exists
+        if fs::metadata(&default_path).await.is_ok() {
+            return Ok(());
+        }
 
-        // Create the website directory with assets folder
-        fs::create_dir_all(self.assets_path(&website_id)).await?;
+        // Create the default website directory with assets folder
+        fs::create_dir_all(self.assets_path(default_website_id)).await?;
 
-        // Save the metadata
-        self.set_website_meta(session, &website_id, meta).await?;
+        // Create the default metadata
+        let meta = WebsiteMetaFileContent {
+            name: "Default website".to_string(),
+            image_url: None,
+            connector_user_settings: Default::default(),
+            is_template: false,
+            ..Default::default()
+        };
+        let default_id = default_website_id.to_string();
+        self.set_website_meta(&serde_json::json!({}), &default_id, &meta)
+            .await?;
 
-        // Save the default website data
-        self.update_website(session, &website_id, &WebsiteData::default())
+        // Create the default website data
+        self.update_website(&serde_json::json!({}), &default_id, &WebsiteData::default())
             .await?;
 
-        Ok(website_id)
+        tracing::info!(
+            "Created default website '{}' in {}",
+            default_website_id,
+            self.data_path.display()
+        );
+
+        Ok(())
     }
 
-    async fn update_website(
+    /// Merge website data from main file and page files
+    async fn merge_website_data(
         &self,
-        _session: &serde_json::Value,
-        website_id: &WebsiteId,
-        data: &WebsiteData,
-    ) -> ConnectorResult<()> {
+        website_id: &str,
+        website_content: &str,
+    ) -> ConnectorResult<WebsiteData> {
         let website_path = self.website_path(website_id);
+        website_files::merge_website_data(website_content, |path| {
+            read_page_file(website_path.clone(), path)
+        })
+        .await
+    }
 
-        // Ensure the website directory exists
-        fs::create_dir_all(&website_path).await?;
-
-        // Split the website data into separate files
-        let files = Self::split_website_data(data)?;
+    // ==================
+    // Unlocked operations
+    // The callers hold the website's lock
+    // ==================
 
-        // Get the pages folder path
-        let pages_folder = Self::get_pages_folder(data);
-        let pages_path = website_path.join(pages_folder);
+    /// Read a website's data
+    async fn read_data(&self, website_id: &str) -> ConnectorResult<WebsiteData> {
+        let content = self.read_website_file(website_id).await?;
 
-        // Ensure pages directory exists if we have page files
-        let has_page_files = files.iter().any(|(path, _)| path.starts_with(pages_folder));
-        if has_page_files {
-            fs::create_dir_all(&pages_path).await?;
-        }
+        // Merge with page files if using split format
+        self.merge_website_data(website_id, &content).await
+    }
 
-        // Delete pages that are no longer in the website data
-        if let Ok(mut entries) = fs::read_dir(&pages_path).await {
-            // Collect the new page file names
-            let new_page_files: HashSet<_> = files
-                .iter()
-                .filter(|(path, _)| path.starts_with(pages_folder))
-                .map(|(path, _)| path.replace(&format!("{}/", pages_folder), ""))
-                .collect();
-
-            while let Ok(Some(entry)) = entries.next_entry().await {
-                let file_name = entry.file_name().to_string_lossy().to_string();
-                if file_name.ends_with(".json") && !new_page_files.contains(&file_name) {
-                    let _ = fs::remove_file(entry.path()).await;
-                }
+    /// Read the main website data file, website.json
+    async fn read_website_file(&self, website_id: &str) -> ConnectorResult<String> {
+        let path = self.website_data_path(website_id);
+        fs::read_to_string(&path).await.map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                ConnectorError::NotFound(format!("Website '{}' not found", website_id))
+            } else {
+                ConnectorError::Io(e)
             }
-        }
+        })
+    }
+
+    /// Read the page index of a website, empty when it has no readable website.json
+    async fn read_page_index(&self, website_id: &str) -> website_files::PageIndex {
+        let Ok(content) = fs::read_to_string(self.website_data_path(website_id)).await else {
+            return Default::default();
+        };
+        serde_json::from_str(&content)
+            .map(|parsed| website_files::page_index(&parsed))
+            .unwrap_or_default()
+    }
 
-        // Write all files
-        for (path, content) in files {
-            let file_path = website_path.join(&path);
-            fs::write(&file_path, content).await?;
+    /// Read website.json when its pages can be saved one by one
+    ///
+    /// Returns None for websites with embedded pages or of an older schema
+    /// version, which are saved whole.
+    async fn read_page_refs(&self, website_id: &str) -> ConnectorResult<Option<serde_json::Value>> {
+        let parsed: serde_json::Value =
+            serde_json::from_str(&self.read_website_file(website_id).await?)?;
+        let schema_version = parsed.get("schemaVersion").and_then(|v| v.as_u64());
+        if schema_version != Some(u64::from(CURRENT_SCHEMA_VERSION))
+            || !website_files::has_page_files(&parsed)
+        {
+            return Ok(None);
         }
+        Ok(Some(parsed))
+    }
 
-        Ok(())
+    /// Read the page file of a page reference
+    async fn read_page_ref(
+        &self,
+        website_id: &str,
+        pages_folder: &str,
+        page_ref: &serde_json::Value,
+    ) -> ConnectorResult<serde_json::Value> {
+        let file_name = website_files::page_file_name(page_ref).unwrap_or_default();
+        let content = read_page_file(
+            self.website_path(website_id),
+            format!("{}/{}", pages_folder, file_name),
+        )
+        .await?;
+        Ok(serde_json::from_str(&content)?)
     }
 
-    async fn delete_website(
+    /// Write website.json after one of its pages changed, and keep a snapshot in the history
+    async fn write_page_refs(
         &self,
-        _session: &serde_json::Value,
-        website_id: &WebsiteId,
+        website_id: &str,
+        parsed: &serde_json::Value,
     ) -> ConnectorResult<()> {
-        let path = self.website_path(website_id);
-
-        fs::remove_dir_all(&path).await.map_err(|e| {
-            if e.kind() == std::io::ErrorKind::NotFound {
-                ConnectorError::NotFound(format!("Website '{}' not found", website_id))
-            } else {
-                ConnectorError::Io(e)
-            }
-        })?;
+        write_atomic(
+            &self.website_data_path(website_id),
+            website_files::serialize_json(parsed)?.as_bytes(),
+        )
+        .await?;
+        self.save_page_version(website_id).await
+    }
 
-        Ok(())
+    /// Keep a snapshot of the whole website data in the history, after a page was saved
+    ///
+    /// The editor saves pages one after the other while they are edited: a
+    /// snapshot is only kept when the latest version is older than
+    /// `PAGE_VERSION_INTERVAL_MINUTES`, so that the history is not filled with
+    /// near identical versions of the whole website.
+    async fn save_page_version(&self, website_id: &str) -> ConnectorResult<()> {
+        if self.history_max_versions == 0 {
+            return Ok(());
+        }
+        let history = self.read_history(website_id).await?;
+        let interval = Duration::minutes(PAGE_VERSION_INTERVAL_MINUTES);
+        if history
+            .first()
+            .is_some_and(|version| Utc::now() - version.created_at < interval)
+        {
+            return Ok(());
+        }
+        let data = self.read_data(website_id).await?;
+        self.save_version(website_id, &data).await
     }
 
-    async fn duplicate_website(
+    /// Save one page of a website
+    ///
+    /// Only the page file, and website.json when the page is added or renamed,
+    /// are written. Websites which are not split in page files are saved whole.
+    async fn write_page_data(
         &self,
-        session: &serde_json::Value,
-        website_id: &WebsiteId,
-    ) -> ConnectorResult<WebsiteId> {
-        // Generate a new ID for the duplicate
-        let new_website_id = Uuid::new_v4().to_string();
+        website_id: &str,
+        page: &serde_json::Value,
+        if_match: Option<&str>,
+    ) -> ConnectorResult<String> {
+        let page_id = page_id_of(page).unwrap_or_default();
+
+        let Some(mut parsed) = self.read_page_refs(website_id).await? else {
+            let mut data = self.read_data(website_id).await?;
+            let index = data
+                .pages
+                .iter()
+                .position(|p| page_id_of(p) == Some(page_id));
+            if let Some(expected) = if_match {
+                let current = index
+                    .map(|index| page_revision(&data.pages[index]))
+                    .ok_or_else(|| page_not_found(website_id, page_id))?;
+                if current != expected {
+                    return Err(ConnectorError::Conflict(current));
+                }
+            }
+            match index {
+                Some(index) => data.pages[index] = page.clone(),
+                None => data.pages.push(page.clone()),
+            }
+            self.write_data(website_id, &data).await?;
+            return Ok(page_revision(page));
+        };
 
-        let source_path = self.website_path(website_id);
-        let dest_path = self.website_path(&new_website_id);
+        let pages_folder = website_files::get_pages_folder_of(&parsed).to_string();
+        let pages = parsed["pages"].as_array_mut().ok_or_else(|| {
+            ConnectorError::InvalidInput(format!("Website '{}' has no pages", website_id))
+        })?;
+        let index = pages.iter().position(|p| page_id_of(p) == Some(page_id));
+
+        if let Some(expected) = if_match {
+            let index = index.ok_or_else(|| page_not_found(website_id, page_id))?;
+            let current = page_revision(
+                &self
+                    .read_page_ref(website_id, &pages_folder, &pages[index])
+                    .await?,
+            );
+            if current != expected {
+                return Err(ConnectorError::Conflict(current));
+            }
+        }
 
-        // Copy the entire directory
-        copy_dir_recursive(source_path, dest_path).await?;
+        // Pages keep their file, renaming a page only changes its reference
+        let file_name = match index {
+            Some(index) => website_files::page_file_name(&pages[index]),
+            None => {
+                let used = pages
+                    .iter()
+                    .filter_map(website_files::page_file_name)
+                    .collect();
+                website_files::new_page_file_name(page, &used)
+            }
+        }
+        .unwrap_or_default();
+
+        // Write the page file first, then website.json which references it
+        let pages_path = self.website_path(website_id).join(&pages_folder);
+        fs::create_dir_all(&pages_path).await?;
+        write_atomic(
+            &pages_path.join(&file_name),
+            website_files::serialize_json(page)?.as_bytes(),
+        )
+        .await?;
 
-        // Update the metadata with a new name
-        let mut meta = self.get_website_meta(session, website_id).await?;
-        let new_meta = WebsiteMetaFileContent {
-            name: format!("{} copy", meta.name),
-            image_url: meta.image_url.take(),
-            connector_user_settings: meta.connector_user_settings,
-        };
-        self.set_website_meta(session, &new_website_id, &new_meta)
-            .await?;
+        let page_ref = website_files::page_ref(page, &file_name);
+        match index {
+            Some(index) if pages[index] == page_ref => {
+                // Same reference, website.json is unchanged
+                self.save_page_version(website_id).await?;
+                return Ok(page_revision(page));
+            }
+            Some(index) => pages[index] = page_ref,
+            None => pages.push(page_ref),
+        }
+        self.write_page_refs(website_id, &parsed).await?;
 
-        Ok(new_website_id)
+        Ok(page_revision(page))
     }
 
-    // ==================
-    // Assets
-    // ==================
+    /// Delete one page of a website
+    async fn delete_page_data(&self, website_id: &str, page_id: &str) -> ConnectorResult<()> {
+        let Some(mut parsed) = self.read_page_refs(website_id).await? else {
+            let mut data = self.read_data(website_id).await?;
+            let index = data
+                .pages
+                .iter()
+                .position(|p| page_id_of(p) == Some(page_id))
+                .ok_or_else(|| page_not_found(website_id, page_id))?;
+            data.pages.remove(index);
+            return self.write_data(website_id, &data).await;
+        };
 
-    async fn write_assets(
-        &self,
-        _session: &serde_json::Value,
-        website_id: &WebsiteId,
-        files: Vec<ConnectorFile>,
-    ) -> ConnectorResult<Vec<String>> {
-        let assets_path = self.assets_path(website_id);
+        let pages_folder = website_files::get_pages_folder_of(&parsed).to_string();
+        let page_ref = parsed["pages"]
+            .as_array_mut()
+            .and_then(|pages| {
+                let index = pages.iter().position(|p| page_id_of(p) == Some(page_id))?;
+                Some(pages.remove(index))
+            })
+            .ok_or_else(|| page_not_found(website_id, page_id))?;
+
+        // Remove the reference first, so that website.json never references a missing page
+        self.write_page_refs(website_id, &parsed).await?;
+        if let Some(file_name) = website_files::page_file_name(&page_ref) {
+            let pages_path = self.website_path(website_id).join(&pages_folder);
+            let _ = fs::remove_file(pages_path.join(file_name)).await;
+        }
 
-        // Ensure assets directory exists
-        fs::create_dir_all(&assets_path).await?;
+        Ok(())
+    }
 
-        let mut written_paths = Vec::new();
+    /// Write a website's data and keep a snapshot in its history
+    async fn write_data(&self, website_id: &str, data: &WebsiteData) -> ConnectorResult<()> {
+        let website_path = self.website_path(website_id);
 
-        for file in files {
-            // Normalize the path (remove leading slash if present)
-            let relative_path = file.path.trim_start_matches('/');
-            let file_path = assets_path.join(relative_path);
+        // Ensure the website directory exists
+        fs::create_dir_all(&website_path).await?;
 
-            // Ensure parent directory exists
-            if let Some(parent) = file_path.parent() {
-                fs::create_dir_all(parent).await?;
-            }
+        // Split the website data into separate files, keeping the page files of the index
+        let index = self.read_page_index(website_id).await;
+        let files = website_files::split_website_data(data, &index)?;
 
-            // Write the file
-            fs::write(&file_path, &file.content).await?;
+        // Get the pages folder path
+        let pages_folder = website_files::get_pages_folder(data);
+        let pages_path = website_path.join(pages_folder);
 
-            // Return the path as stored (with leading slash)
-            written_paths.push(format!("/{}", relative_path));
+        // Ensure pages directory exists if we have page files
+        let has_page_files = files.iter().any(|(path, _)| path.starts_with(pages_folder));
+        if has_page_files {
+            fs::create_dir_all(&pages_path).await?;
         }
 
-        Ok(written_paths)
-    }
+        // Collect the new page file names
+        let new_page_files: HashSet<_> = files
+            .iter()
+            .filter(|(path, _)| path.starts_with(pages_folder))
+            .map(|(path, _)| path.replace(&format!("{}/", pages_folder), ""))
+            .collect();
+
+        // Write the page files first, then website.json which references them,
+        // so that an interrupted save never leaves references to missing pages
+        let (website_files, page_files): (Vec<_>, Vec<_>) = files
+            .into_iter()
+            .partition(|(path, _)| path == constants::WEBSITE_DATA_FILE);
+        for (path, content) in page_files.into_iter().chain(website_files) {
+            write_atomic(&website_path.join(&path), content.as_bytes()).await?;
+        }
 
-    async fn read_asset(
-        &self,
-        _session: &serde_json::Value,
-        website_id: &WebsiteId,
-        file_name: &str,
-    ) -> ConnectorResult<Vec<u8>> {
-        // Normalize the path (remove leading slash if present)
-        let relative_path = file_name.trim_start_matches('/');
-        let path = self.assets_path(website_id).join(relative_path);
+        // Delete pages that are no longer in the website data
+        remove_orphan_pages(&pages_path, &new_page_files).await;
 
-        fs::read(&path).await.map_err(|e| {
-            if e.kind() == std::io::ErrorKind::NotFound {
-                ConnectorError::NotFound(format!("Asset '{}' not found", file_name))
-            } else {
-                ConnectorError::Io(e)
-            }
-        })
-    }
+        // Keep a snapshot in the history
+        self.save_version(website_id, data).await?;
 
-    // ==================
-    // Metadata
-    // ==================
+        Ok(())
+    }
 
-    async fn get_website_meta(
-        &self,
-        _session: &serde_json::Value,
-        website_id: &WebsiteId,
-    ) -> ConnectorResult<WebsiteMeta> {
+    /// Read a website's metadata
+    async fn read_meta(&self, website_id: &str) -> ConnectorResult<WebsiteMeta> {
         let meta_path = self.website_meta_path(website_id);
         let website_path = self.website_path(website_id);
 
@@ -603,19 +1005,11 @@ impl StorageConnector for FsStorage {
 
         let file_content: WebsiteMetaFileContent = serde_json::from_str(&content)?;
 
-        // Get file stats for created/updated timestamps
-        let metadata = fs::metadata(&website_path).await?;
-        let created_at = metadata
-            .created()
-            .ok()
-            .map(|t| DateTime::<Utc>::from(t));
-        let updated_at = metadata
-            .modified()
-            .ok()
-            .map(|t| DateTime::<Utc>::from(t));
+        // Websites saved before the times were recorded in meta.json get those of their directory
+        let (created_at, updated_at) = self.directory_times(website_id).await?;
 
         let mut meta = WebsiteMeta::from_file_content(
-            website_id.clone(),
+            website_id.to_string(),
             file_content,
             created_at,
             updated_at,
@@ -632,40 +1026,1148 @@ impl StorageConnector for FsStorage {
         Ok(meta)
     }
 
-    async fn set_website_meta(
+    /// Write a website's metadata
+    async fn write_meta(
         &self,
-        _session: &serde_json::Value,
-        website_id: &WebsiteId,
+        website_id: &str,
         meta: &WebsiteMetaFileContent,
     ) -> ConnectorResult<()> {
         let path = self.website_meta_path(website_id);
-        let content = Self::serialize_json(meta)?;
+        let content = website_files::serialize_json(meta)?;
 
-        fs::write(&path, content).await?;
+        fs::create_dir_all(self.website_path(website_id)).await?;
+        write_atomic(&path, content.as_bytes()).await?;
 
         Ok(())
     }
-}
 
-/// Recursively copy a directory
-///
-/// Uses Box::pin to handle the recursive async calls.
-fn copy_dir_recursive(
-    source: PathBuf,
-    dest: PathBuf,
-) -> std::pin::Pin<Box<dyn std::future::Future<Output = ConnectorResult<()>> + Send>> {
-    Box::pin(async move {
-        fs::create_dir_all(&dest).await?;
+    /// Record a modification of a website by `author` in its metadata
+    ///
+    /// Writes `meta`, or the stored metadata when None. The creation time and
+    /// author are kept from the stored metadata, and set when there is none yet.
+    /// The author is the user of the session (see `session_user`), None when
+    /// nobody logged in: the system user running the server is not the one
+    /// making the change.
+    async fn stamp_meta(
+        &self,
+        website_id: &str,
+        meta: Option<&WebsiteMetaFileContent>,
+        author: Option<&str>,
+    ) -> ConnectorResult<()> {
+        let stored: Option<WebsiteMetaFileContent> =
+            match fs::read_to_string(self.website_meta_path(website_id)).await {
+                Ok(content) => Some(serde_json::from_str(&content)?),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => return Err(e.into()),
+            };
+        let Some(mut meta) = meta.or(stored.as_ref()).cloned() else {
+            // No metadata to record the modification in
+            return Ok(());
+        };
 
-        let mut entries = fs::read_dir(&source).await?;
+        let now = Utc::now();
+        match stored {
+            Some(stored) => {
+                meta.created_at = match stored.created_at {
+                    Some(created_at) => Some(created_at),
+                    None => self.directory_times(website_id).await?.0,
+                };
+                meta.created_by = stored.created_by;
+            }
+            None => {
+                meta.created_at = Some(now);
+                meta.created_by = author.map(String::from);
+            }
+        }
+        meta.updated_at = Some(now);
+        meta.updated_by = author.map(String::from);
 
-        while let Some(entry) = entries.next_entry().await? {
-            let entry_path = entry.path();
-            let dest_path = dest.join(entry.file_name());
+        self.write_meta(website_id, &meta).await
+    }
 
-            if entry.file_type().await?.is_dir() {
-                copy_dir_recursive(entry_path, dest_path).await?;
+    /// Get the creation and modification times of a website's directory
+    ///
+    /// The creation time is not available on all filesystems.
+    async fn directory_times(
+        &self,
+        website_id: &str,
+    ) -> ConnectorResult<(Option<DateTime<Utc>>, Option<DateTime<Utc>>)> {
+        let metadata = fs::metadata(self.website_path(website_id)).await?;
+        Ok((
+            metadata.created().ok().map(DateTime::<Utc>::from),
+            metadata.modified().ok().map(DateTime::<Utc>::from),
+        ))
+    }
+
+    /// Check a website's page and asset files
+    async fn check(&self, website_id: &str) -> ConnectorResult<IntegrityReport> {
+        let website_path = self.website_path(website_id);
+        let content = fs::read_to_string(self.website_data_path(website_id))
+            .await
+            .map_err(|e| {
+                if e.kind() == std::io::ErrorKind::NotFound {
+                    ConnectorError::NotFound(format!("Website '{}' not found", website_id))
+                } else {
+                    ConnectorError::Io(e)
+                }
+            })?;
+
+        // Missing and invalid pages
+        let (data, mut report) = website_files::load_website_data(&content, |path| {
+            read_page_file(website_path.clone(), path)
+        })
+        .await?;
+
+        // Page files which website.json does not reference
+        let parsed: serde_json::Value = serde_json::from_str(&content)?;
+        let referenced = website_files::referenced_page_files(&parsed);
+        let pages_folder = parsed
+            .get("pagesFolder")
+            .and_then(|f| f.as_str())
+            .unwrap_or(constants::LEGACY_WEBSITE_PAGES_FOLDER);
+        report.orphan_pages = list_file_names(&website_path.join(pages_folder))
+            .await?
+            .into_iter()
+            .filter(|name| !referenced.contains(name))
+            .map(|name| format!("{}/{}", pages_folder, name))
+            .collect();
+
+        // Asset files which are mentioned nowhere in the website data
+        report.orphan_assets = website_files::unused_asset_paths(
+            &data,
+            list_files_recursive(self.assets_path(website_id), String::new()).await?,
+        )?;
+
+        Ok(report)
+    }
+
+    /// Rebuild a website's data from its page files
+    ///
+    /// Pages keep their order in website.json, the pages it does not reference
+    /// are added at the end. Page files which can not be loaded are renamed with
+    /// a `.corrupted` extension and left aside.
+    async fn repair(&self, website_id: &str) -> ConnectorResult<()> {
+        let website_path = self.website_path(website_id);
+        if fs::metadata(&website_path).await.is_err() {
+            return Err(ConnectorError::NotFound(format!(
+                "Website '{}' not found",
+                website_id
+            )));
+        }
+
+        // Start from website.json, or from an empty website when it is unreadable
+        let parsed = fs::read_to_string(self.website_data_path(website_id))
+            .await
+            .ok()
+            .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+            .filter(|parsed| parsed.is_object());
+        let mut parsed = match parsed {
+            Some(parsed) => parsed,
+            None => {
+                tracing::warn!("Rebuilding {} from scratch", constants::WEBSITE_DATA_FILE);
+                serde_json::to_value(WebsiteData::default())?
+            }
+        };
+        let pages_folder = parsed
+            .get("pagesFolder")
+            .and_then(|f| f.as_str())
+            .unwrap_or(constants::LEGACY_WEBSITE_PAGES_FOLDER)
+            .to_string();
+        let referenced = website_files::referenced_page_files(&parsed);
+
+        // Load the page files, by page ID, in file name order
+        let pages_path = website_path.join(&pages_folder);
+        let mut found: HashMap<String, serde_json::Value> = HashMap::new();
+        let mut found_ids = Vec::new();
+        for file_name in list_file_names(&pages_path).await? {
+            if !file_name.ends_with(".json") {
+                continue;
+            }
+            let path = pages_path.join(&file_name);
+            let page = fs::read_to_string(&path)
+                .await
+                .ok()
+                .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok());
+            let Some((id, page)) = page.and_then(|page| {
+                let id = page.get("id")?.as_str()?.to_string();
+                Some((id, page))
+            }) else {
+                tracing::warn!("Setting aside invalid page file {}", path.display());
+                fs::rename(&path, pages_path.join(format!("{}.corrupted", file_name))).await?;
+                continue;
+            };
+
+            // When a page has several files, prefer the one website.json references
+            match found.entry(id) {
+                Entry::Vacant(entry) => {
+                    found_ids.push(entry.key().clone());
+                    entry.insert(page);
+                }
+                Entry::Occupied(mut entry) => {
+                    if referenced.contains(&file_name) {
+                        entry.insert(page);
+                    }
+                }
+            }
+        }
+
+        // Referenced pages first, missing ones are dropped
+        let page_refs = parsed
+            .get("pages")
+            .and_then(|p| p.as_array())
+            .cloned()
+            .unwrap_or_default();
+        let mut pages = Vec::new();
+        for page_ref in page_refs {
+            if page_ref.get("isFile").and_then(|f| f.as_bool()) != Some(true) {
+                pages.push(page_ref);
+                continue;
+            }
+            if let Some(page) = page_ref
+                .get("id")
+                .and_then(|id| id.as_str())
+                .and_then(|id| found.remove(id))
+            {
+                pages.push(page);
+            }
+        }
+        pages.extend(found_ids.iter().filter_map(|id| found.remove(id)));
+
+        parsed["pages"] = serde_json::Value::Array(pages);
+        parsed["pagesFolder"] = serde_json::Value::String(pages_folder);
+        let data = website_files::from_stored(parsed)?;
+
+        self.write_data(website_id, &data).await
+    }
+}
+
+impl ConnectorInfo for FsStorage {
+    fn connector_id(&self) -> &str {
+        "fs-storage"
+    }
+
+    fn connector_type(&self) -> ConnectorType {
+        ConnectorType::Storage
+    }
+
+    fn display_name(&self) -> &str {
+        "File system storage"
+    }
+
+    fn icon(&self) -> &str {
+        FILE_ICON
+    }
+
+    fn color(&self) -> &str {
+        "#ffffff"
+    }
+
+    fn background(&self) -> &str {
+        "#006400"
+    }
+
+    fn disable_logout(&self) -> bool {
+        // FsStorage has no authentication, so hide logout button
+        true
+    }
+}
+
+#[async_trait]
+impl StorageConnector for FsStorage {
+    // ==================
+    // Authentication
+    // FsStorage has no authentication - always logged in
+    // ==================
+
+    async fn is_logged_in(&self, _session: &serde_json::Value) -> ConnectorResult<bool> {
+        // Filesystem storage has no authentication
+        Ok(true)
+    }
+
+    async fn get_oauth_url(&self, _session: &serde_json::Value) -> ConnectorResult<Option<String>> {
+        // No OAuth for filesystem storage
+        Ok(None)
+    }
+
+    async fn set_token(
+        &self,
+        _session: &mut serde_json::Value,
+        _token: &serde_json::Value,
+    ) -> ConnectorResult<()> {
+        // No tokens for filesystem storage
+        Ok(())
+    }
+
+    async fn logout(&self, _session: &mut serde_json::Value) -> ConnectorResult<()> {
+        // No logout for filesystem storage
+        Ok(())
+    }
+
+    async fn get_user(&self, session: &serde_json::Value) -> ConnectorResult<ConnectorUser> {
+        // Return the current system username
+        let username = whoami::username().unwrap_or_else(|_| "unknown".to_string());
+
+        Ok(ConnectorUser {
+            name: username,
+            email: None,
+            picture: Some(USER_ICON.to_string()),
+            storage: to_connector_data(session, self).await?,
+        })
+    }
+
+    fn get_options(&self, _form_data: &serde_json::Value) -> ConnectorOptions {
+        // No options for filesystem storage
+        ConnectorOptions::default()
+    }
+
+    // ==================
+    // Website CRUD
+    // ==================
+
+    async fn list_websites(
+        &self,
+        session: &serde_json::Value,
+    ) -> ConnectorResult<Vec<WebsiteMeta>> {
+        let mut websites = Vec::new();
+
+        // List all directories in the data path
+        let mut entries = fs::read_dir(&self.data_path).await?;
+
+        while let Some(entry) = entries.next_entry().await? {
+            let metadata = fs::metadata(entry.path()).await?;
+            if !metadata.is_dir() {
+                continue;
+            }
+
+            let website_id = entry.file_name().to_string_lossy().to_string();
+
+            // Skip temporary directories (see `write_atomic`) and the lock files
+            if website_id.starts_with('.') {
+                continue;
+            }
+
+            // Try to get metadata for this website
+            match self.get_website_meta(session, &website_id).await {
+                Ok(meta) => websites.push(meta),
+                Err(e) => {
+                    tracing::warn!("Failed to get metadata for website {}: {}", website_id, e);
+                }
+            }
+        }
+
+        Ok(websites)
+    }
+
+    async fn read_website(
+        &self,
+        _session: &serde_json::Value,
+        website_id: &WebsiteId,
+    ) -> ConnectorResult<WebsiteData> {
+        let _lock = self.read_lock(website_id).await?;
+        self.read_data(website_id).await
+    }
+
+    async fn create_website(
+        &self,
+        session: &serde_json::Value,
+        meta: &WebsiteMetaFileContent,
+    ) -> ConnectorResult<WebsiteId> {
+        // Generate a new UUID for the website
+        let website_id = Uuid::new_v4().to_string();
+        self.create_website_with_id(session, &website_id, meta)
+            .await?;
+
+        Ok(website_id)
+    }
+
+    async fn create_website_with_id(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+        meta: &WebsiteMetaFileContent,
+    ) -> ConnectorResult<()> {
+        let author = session_user(session);
+        let _lock = self.new_website_lock(website_id).await?;
+        if fs::metadata(self.website_path(website_id)).await.is_ok() {
+            return Err(ConnectorError::InvalidInput(format!(
+                "Website '{}' already exists",
+                website_id
+            )));
+        }
+
+        // Create the website directory with assets folder,
+        // then save the metadata and the default website data
+        fs::create_dir_all(self.assets_path(website_id)).await?;
+        self.write_data(website_id, &WebsiteData::default()).await?;
+        self.stamp_meta(website_id, Some(meta), author.as_deref())
+            .await
+    }
+
+    async fn update_website(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+        data: &WebsiteData,
+    ) -> ConnectorResult<()> {
+        let author = session_user(session);
+        let _lock = self.write_lock(website_id).await?;
+        self.write_data(website_id, data).await?;
+        self.stamp_meta(website_id, None, author.as_deref()).await
+    }
+
+    async fn update_website_if_match(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+        data: &WebsiteData,
+        if_match: Option<&str>,
+    ) -> ConnectorResult<String> {
+        let author = session_user(session);
+        // Check and write under the same lock
+        let _lock = self.write_lock(website_id).await?;
+
+        if let Some(expected) = if_match {
+            let current = self.read_data(website_id).await?.revision();
+            if current != expected {
+                return Err(ConnectorError::Conflict(current));
+            }
+        }
+
+        self.write_data(website_id, data).await?;
+        self.stamp_meta(website_id, None, author.as_deref()).await?;
+
+        Ok(self.read_data(website_id).await?.revision())
+    }
+
+    async fn delete_website(
+        &self,
+        _session: &serde_json::Value,
+        website_id: &WebsiteId,
+    ) -> ConnectorResult<()> {
+        // Wait for the saves in progress
+        let _lock = self.write_lock(website_id).await?;
+        let path = self.website_path(website_id);
+        let not_found = |e: std::io::Error| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                ConnectorError::NotFound(format!("Website '{}' not found", website_id))
+            } else {
+                ConnectorError::Io(e)
+            }
+        };
+
+        if self.trash_retention.is_zero() {
+            return fs::remove_dir_all(&path).await.map_err(not_found);
+        }
+
+        // Move the website to the trash, in one rename
+        let trash_path = self.trash_path();
+        fs::create_dir_all(&trash_path).await?;
+        let trash_id = format!("{}-{}", Utc::now().timestamp_millis(), website_id);
+        fs::rename(&path, trash_path.join(&trash_id))
+            .await
+            .map_err(not_found)?;
+        sync_dir(&self.data_path).await?;
+        sync_dir(&trash_path).await?;
+
+        Ok(())
+    }
+
+    async fn purge_website(
+        &self,
+        _session: &serde_json::Value,
+        website_id: &WebsiteId,
+    ) -> ConnectorResult<()> {
+        let _lock = self.write_lock(website_id).await?;
+        fs::remove_dir_all(self.website_path(website_id)).await?;
+        sync_dir(&self.data_path).await?;
+        Ok(())
+    }
+
+    async fn duplicate_website(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+    ) -> ConnectorResult<WebsiteId> {
+        // Generate a new ID for the duplicate
+        let new_website_id = Uuid::new_v4().to_string();
+        let author = session_user(session);
+
+        let source_path = self.website_path(website_id);
+        let dest_path = self.website_path(&new_website_id);
+
+        // Copy a consistent state, saves wait for the copy to complete
+        let _lock = self.read_lock(website_id).await?;
+
+        // The metadata with a new name, the copy is a new website
+        let mut meta = self.read_meta(website_id).await?;
+        let now = Utc::now();
+        let new_meta = WebsiteMetaFileContent {
+            name: format!("{} copy", meta.name),
+            image_url: meta.image_url.take(),
+            connector_user_settings: meta.connector_user_settings,
+            // A copy of a template is a website to work on
+            is_template: false,
+            created_at: Some(now),
+            updated_at: Some(now),
+            created_by: author.clone(),
+            updated_by: author,
+        };
+
+        // Copy the directory to a temporary one, then move it in place, so that
+        // an interrupted copy never shows up as a website. The copy starts
+        // without history, and without the temporary files of the original.
+        let temp_path = temp_path_for(&dest_path);
+        let copied = async {
+            copy_dir_recursive(
+                source_path,
+                temp_path.clone(),
+                &[HISTORY_FOLDER],
+                self.deduplicate_assets,
+            )
+            .await?;
+            let content = website_files::serialize_json(&new_meta)?;
+            write_atomic(
+                &temp_path.join(constants::WEBSITE_META_DATA_FILE),
+                content.as_bytes(),
+            )
+            .await?;
+            fs::rename(&temp_path, &dest_path).await?;
+            sync_dir(&self.data_path).await
+        };
+        if let Err(e) = copied.await {
+            let _ = fs::remove_dir_all(&temp_path).await;
+            return Err(e);
+        }
+
+        Ok(new_website_id)
+    }
+
+    // ==================
+    // Pages
+    // ==================
+
+    async fn read_page(
+        &self,
+        _session: &serde_json::Value,
+        website_id: &WebsiteId,
+        page_id: &str,
+    ) -> ConnectorResult<serde_json::Value> {
+        let _lock = self.read_lock(website_id).await?;
+
+        let Some(parsed) = self.read_page_refs(website_id).await? else {
+            let data = self.read_data(website_id).await?;
+            return data
+                .page(page_id)
+                .cloned()
+                .ok_or_else(|| page_not_found(website_id, page_id));
+        };
+        let pages_folder = website_files::get_pages_folder_of(&parsed);
+        let page_ref = parsed["pages"]
+            .as_array()
+            .and_then(|pages| pages.iter().find(|p| page_id_of(p) == Some(page_id)))
+            .ok_or_else(|| page_not_found(website_id, page_id))?;
+
+        self.read_page_ref(website_id, pages_folder, page_ref).await
+    }
+
+    async fn write_page(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+        page: &serde_json::Value,
+        if_match: Option<&str>,
+    ) -> ConnectorResult<String> {
+        let author = session_user(session);
+        validate_page(page)?;
+
+        // Check and write under the same lock
+        let _lock = self.write_lock(website_id).await?;
+        let revision = self.write_page_data(website_id, page, if_match).await?;
+        self.stamp_meta(website_id, None, author.as_deref()).await?;
+        Ok(revision)
+    }
+
+    async fn delete_page(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+        page_id: &str,
+    ) -> ConnectorResult<()> {
+        let author = session_user(session);
+        let _lock = self.write_lock(website_id).await?;
+        self.delete_page_data(website_id, page_id).await?;
+        self.stamp_meta(website_id, None, author.as_deref()).await
+    }
+
+    // ==================
+    // History
+    // ==================
+
+    async fn list_versions(
+        &self,
+        _session: &serde_json::Value,
+        website_id: &WebsiteId,
+    ) -> ConnectorResult<Vec<WebsiteVersion>> {
+        let _lock = self.read_lock(website_id).await?;
+        if fs::metadata(self.website_path(website_id)).await.is_err() {
+            return Err(ConnectorError::NotFound(format!(
+                "Website '{}' not found",
+                website_id
+            )));
+        }
+
+        self.read_history(website_id).await
+    }
+
+    async fn read_version(
+        &self,
+        _session: &serde_json::Value,
+        website_id: &WebsiteId,
+        version_id: &str,
+    ) -> ConnectorResult<WebsiteData> {
+        let path = self.version_path(website_id, version_id)?;
+        let _lock = self.read_lock(website_id).await?;
+
+        let content = fs::read_to_string(&path).await.map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                ConnectorError::NotFound(format!("Version '{}' not found", version_id))
+            } else {
+                ConnectorError::Io(e)
+            }
+        })?;
+
+        website_files::from_stored(serde_json::from_str(&content)?)
+    }
+
+    // ==================
+    // Schema
+    // ==================
+
+    async fn migrate_website(
+        &self,
+        _session: &serde_json::Value,
+        website_id: &WebsiteId,
+    ) -> ConnectorResult<bool> {
+        let _lock = self.write_lock(website_id).await?;
+
+        // Only rewrite the websites stored with a previous version, or without page index
+        let content = fs::read_to_string(self.website_data_path(website_id))
+            .await
+            .map_err(|e| {
+                if e.kind() == std::io::ErrorKind::NotFound {
+                    ConnectorError::NotFound(format!("Website '{}' not found", website_id))
+                } else {
+                    ConnectorError::Io(e)
+                }
+            })?;
+        let stored: serde_json::Value = serde_json::from_str(&content)?;
+        let version = stored.get("schemaVersion").and_then(|v| v.as_u64());
+        if version == Some(CURRENT_SCHEMA_VERSION as u64) && website_files::has_page_index(&stored)
+        {
+            return Ok(false);
+        }
+
+        let data = self.read_data(website_id).await?;
+        self.write_data(website_id, &data).await?;
+        Ok(true)
+    }
+
+    // ==================
+    // Integrity
+    // ==================
+
+    async fn check_integrity(
+        &self,
+        _session: &serde_json::Value,
+        website_id: &WebsiteId,
+    ) -> ConnectorResult<IntegrityReport> {
+        let _lock = self.read_lock(website_id).await?;
+        self.check(website_id).await
+    }
+
+    async fn repair_website(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+    ) -> ConnectorResult<IntegrityReport> {
+        let author = session_user(session);
+        let _lock = self.write_lock(website_id).await?;
+        self.repair(website_id).await?;
+        self.stamp_meta(website_id, None, author.as_deref()).await?;
+        self.check(website_id).await
+    }
+
+    // ==================
+    // Trash
+    // ==================
+
+    async fn list_trash(
+        &self,
+        _session: &serde_json::Value,
+    ) -> ConnectorResult<Vec<TrashedWebsite>> {
+        self.read_trash().await
+    }
+
+    async fn restore_website(
+        &self,
+        _session: &serde_json::Value,
+        trash_id: &str,
+    ) -> ConnectorResult<WebsiteId> {
+        let (entry_path, website_id) = self.trash_entry_path(trash_id)?;
+
+        let _lock = self.new_website_lock(&website_id).await?;
+        let path = self.website_path(&website_id);
+        if fs::metadata(&path).await.is_ok() {
+            return Err(ConnectorError::InvalidInput(format!(
+                "Website '{}' already exists, delete or rename it first",
+                website_id
+            )));
+        }
+
+        fs::rename(&entry_path, &path).await.map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                ConnectorError::NotFound(format!("Trash entry '{}' not found", trash_id))
+            } else {
+                ConnectorError::Io(e)
+            }
+        })?;
+        sync_dir(&self.trash_path()).await?;
+        sync_dir(&self.data_path).await?;
+
+        Ok(website_id)
+    }
+
+    async fn purge_trash(
+        &self,
+        _session: &serde_json::Value,
+        trash_id: Option<&str>,
+    ) -> ConnectorResult<usize> {
+        match trash_id {
+            Some(trash_id) => {
+                self.remove_trash_entry(trash_id).await?;
+                Ok(1)
+            }
+            None => {
+                let websites = self.read_trash().await?;
+                for website in &websites {
+                    self.remove_trash_entry(&website.trash_id).await?;
+                }
+                Ok(websites.len())
+            }
+        }
+    }
+
+    // ==================
+    // Assets
+    // ==================
+
+    async fn write_assets(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+        files: Vec<ConnectorFile>,
+    ) -> ConnectorResult<Vec<String>> {
+        let author = session_user(session);
+        // Check all the paths before writing any file
+        let file_paths = files
+            .iter()
+            .map(|file| self.asset_file_path(website_id, &file.path))
+            .collect::<ConnectorResult<Vec<_>>>()?;
+
+        let _lock = self.write_lock(website_id).await?;
+
+        // Ensure assets directory exists
+        fs::create_dir_all(self.assets_path(website_id)).await?;
+
+        let mut written_paths = Vec::new();
+
+        for (file, file_path) in files.into_iter().zip(file_paths) {
+            // Normalize the path (remove leading slash if present)
+            let relative_path = file.path.trim_start_matches('/');
+
+            // Ensure parent directory exists
+            if let Some(parent) = file_path.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+
+            // Write the file
+            self.write_asset_file(&file_path, &file.content).await?;
+
+            // Return the path as stored (with leading slash)
+            written_paths.push(format!("/{}", relative_path));
+        }
+        self.stamp_meta(website_id, None, author.as_deref()).await?;
+
+        Ok(written_paths)
+    }
+
+    async fn read_asset(
+        &self,
+        _session: &serde_json::Value,
+        website_id: &WebsiteId,
+        file_name: &str,
+    ) -> ConnectorResult<Vec<u8>> {
+        let path = self.asset_file_path(website_id, file_name)?;
+
+        let _lock = self.read_lock(website_id).await?;
+        fs::read(&path).await.map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                ConnectorError::NotFound(format!("Asset '{}' not found", file_name))
+            } else {
+                ConnectorError::Io(e)
+            }
+        })
+    }
+
+    async fn read_asset_stream(
+        &self,
+        _session: &serde_json::Value,
+        website_id: &WebsiteId,
+        file_name: &str,
+    ) -> ConnectorResult<AssetStream> {
+        let path = self.asset_file_path(website_id, file_name)?;
+
+        // Files are replaced by renaming, the open file keeps its content after the lock
+        let _lock = self.read_lock(website_id).await?;
+        let file = fs::File::open(&path).await.map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                ConnectorError::NotFound(format!("Asset '{}' not found", file_name))
+            } else {
+                ConnectorError::Io(e)
+            }
+        })?;
+        let metadata = file.metadata().await?;
+        if !metadata.is_file() {
+            return Err(ConnectorError::NotFound(format!(
+                "Asset '{}' not found",
+                file_name
+            )));
+        }
+
+        let modified_at = metadata.modified().ok().map(DateTime::<Utc>::from);
+        let etag = format!(
+            "{:x}-{:x}",
+            modified_at
+                .map(|date| date.timestamp_micros())
+                .unwrap_or_default(),
+            metadata.len()
+        );
+        Ok(AssetStream {
+            size: metadata.len(),
+            modified_at,
+            etag,
+            reader: Box::new(file),
+        })
+    }
+
+    async fn write_asset_stream(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+        file_name: &str,
+        mut reader: Box<dyn AsyncRead + Send + Unpin>,
+    ) -> ConnectorResult<String> {
+        let author = session_user(session);
+        let path = self.asset_file_path(website_id, file_name)?;
+
+        // Receive the file in the data path without lock, uploads can be slow.
+        // An interrupted upload leaves a temporary file, removed by `recover`.
+        fs::create_dir_all(&self.data_path).await?;
+        let upload = TempFile(temp_path_for(&self.data_path.join("upload")));
+        let mut file = fs::File::create(&upload.0).await?;
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0; 64 * 1024];
+        loop {
+            let read = reader.read(&mut buffer).await?;
+            if read == 0 {
+                break;
+            }
+            if self.deduplicate_assets {
+                hasher.update(&buffer[..read]);
+            }
+            file.write_all(&buffer[..read]).await?;
+        }
+        file.sync_all().await?;
+        drop(file);
+
+        let _lock = self.write_lock(website_id).await?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        // The upload becomes the blob when it is a new content
+        let mut linked = false;
+        if self.deduplicate_assets {
+            let blob_path = self.blob_path_for_hash(hasher.finalize().as_slice());
+            if fs::metadata(&blob_path).await.is_err() {
+                if let Some(parent) = blob_path.parent() {
+                    fs::create_dir_all(parent).await?;
+                }
+                let _ = fs::hard_link(&upload.0, &blob_path).await;
+            }
+            linked = link_blob(&blob_path, &path).await?;
+        }
+        if !linked {
+            fs::rename(&upload.0, &path).await?;
+            if let Some(parent) = path.parent() {
+                sync_dir(parent).await?;
+            }
+        }
+        self.stamp_meta(website_id, None, author.as_deref()).await?;
+
+        Ok(format!("/{}", file_name.trim_start_matches('/')))
+    }
+
+    async fn list_assets(
+        &self,
+        _session: &serde_json::Value,
+        website_id: &WebsiteId,
+    ) -> ConnectorResult<Vec<AssetInfo>> {
+        let _lock = self.read_lock(website_id).await?;
+        if fs::metadata(self.website_path(website_id)).await.is_err() {
+            return Err(ConnectorError::NotFound(format!(
+                "Website '{}' not found",
+                website_id
+            )));
+        }
+
+        let assets_path = self.assets_path(website_id);
+        let mut assets = Vec::new();
+        for path in list_files_recursive(assets_path.clone(), String::new()).await? {
+            let metadata = fs::metadata(assets_path.join(&path)).await?;
+            assets.push(AssetInfo {
+                content_type: mime_guess::from_path(&path)
+                    .first_or_octet_stream()
+                    .to_string(),
+                path: format!("/{}", path),
+                size: metadata.len(),
+                modified_at: metadata.modified().ok().map(DateTime::<Utc>::from),
+            });
+        }
+
+        Ok(assets)
+    }
+
+    async fn delete_asset(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+        file_name: &str,
+    ) -> ConnectorResult<()> {
+        let author = session_user(session);
+        let path = self.asset_file_path(website_id, file_name)?;
+
+        let _lock = self.write_lock(website_id).await?;
+        fs::remove_file(&path).await.map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                ConnectorError::NotFound(format!("Asset '{}' not found", file_name))
+            } else {
+                ConnectorError::Io(e)
+            }
+        })?;
+        remove_empty_dirs(&path, &self.assets_path(website_id)).await;
+
+        self.stamp_meta(website_id, None, author.as_deref()).await
+    }
+
+    async fn move_asset(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+        from: &str,
+        to: &str,
+    ) -> ConnectorResult<()> {
+        let author = session_user(session);
+        let from_path = self.asset_file_path(website_id, from)?;
+        let to_path = self.asset_file_path(website_id, to)?;
+
+        let _lock = self.write_lock(website_id).await?;
+        if fs::metadata(&from_path).await.is_err() {
+            return Err(ConnectorError::NotFound(format!(
+                "Asset '{}' not found",
+                from
+            )));
+        }
+        if fs::metadata(&to_path).await.is_ok() {
+            return Err(ConnectorError::InvalidInput(format!(
+                "Asset '{}' already exists",
+                to
+            )));
+        }
+
+        if let Some(parent) = to_path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::rename(&from_path, &to_path).await?;
+        remove_empty_dirs(&from_path, &self.assets_path(website_id)).await;
+
+        self.stamp_meta(website_id, None, author.as_deref()).await
+    }
+
+    // ==================
+    // Metadata
+    // ==================
+
+    async fn get_website_meta(
+        &self,
+        _session: &serde_json::Value,
+        website_id: &WebsiteId,
+    ) -> ConnectorResult<WebsiteMeta> {
+        let _lock = self.read_lock(website_id).await?;
+        self.read_meta(website_id).await
+    }
+
+    async fn set_website_meta(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+        meta: &WebsiteMetaFileContent,
+    ) -> ConnectorResult<()> {
+        let author = session_user(session);
+        let _lock = self.write_lock(website_id).await?;
+        self.stamp_meta(website_id, Some(meta), author.as_deref())
+            .await
+    }
+
+    async fn set_website_meta_if_match(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+        meta: &WebsiteMetaFileContent,
+        if_match: Option<&str>,
+    ) -> ConnectorResult<String> {
+        let author = session_user(session);
+        // Check and write under the same lock
+        let _lock = self.write_lock(website_id).await?;
+
+        if let Some(expected) = if_match {
+            let current = self
+                .read_meta(website_id)
+                .await?
+                .to_file_content()
+                .revision();
+            if current != expected {
+                return Err(ConnectorError::Conflict(current));
+            }
+        }
+
+        self.stamp_meta(website_id, Some(meta), author.as_deref())
+            .await?;
+
+        Ok(meta.revision())
+    }
+}
+
+/// Read a page file, given its path relative to the website directory
+async fn read_page_file(website_path: PathBuf, path: String) -> ConnectorResult<String> {
+    fs::read_to_string(website_path.join(&path))
+        .await
+        .map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                ConnectorError::NotFound(format!("Page file '{}' not found", path))
+            } else {
+                ConnectorError::Io(e)
+            }
+        })
+}
+
+/// List the names of the files in a directory, sorted, without temporary files
+///
+/// A missing directory has no files.
+async fn list_file_names(dir: &Path) -> ConnectorResult<Vec<String>> {
+    let mut names = Vec::new();
+
+    let mut entries = match fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(names),
+        Err(e) => return Err(e.into()),
+    };
+
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !is_temp_name(&name) && entry.file_type().await?.is_file() {
+            names.push(name);
+        }
+    }
+
+    names.sort();
+    Ok(names)
+}
+
+/// Recursively list the files in a directory, as sorted paths relative to it
+///
+/// Uses Box::pin to handle the recursive async calls.
+fn list_files_recursive(
+    dir: PathBuf,
+    prefix: String,
+) -> std::pin::Pin<Box<dyn std::future::Future<Output = ConnectorResult<Vec<String>>> + Send>> {
+    Box::pin(async move {
+        let mut paths = Vec::new();
+
+        let mut entries = match fs::read_dir(&dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(paths),
+            Err(e) => return Err(e.into()),
+        };
+
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if is_temp_name(&name) {
+                continue;
+            }
+            let path = format!("{}{}", prefix, name);
+            if entry.file_type().await?.is_dir() {
+                paths.extend(list_files_recursive(entry.path(), format!("{}/", path)).await?);
             } else {
+                paths.push(path);
+            }
+        }
+
+        paths.sort();
+        Ok(paths)
+    })
+}
+
+/// Remove the directories left empty above a removed file, up to `root` excluded
+async fn remove_empty_dirs(file_path: &Path, root: &Path) {
+    let mut dir = file_path.parent();
+    while let Some(path) = dir {
+        // Fails when the directory is not empty
+        if path == root || !path.starts_with(root) || fs::remove_dir(path).await.is_err() {
+            break;
+        }
+        dir = path.parent();
+    }
+}
+
+/// Recursively copy a directory
+///
+/// The entries of `source` named in `skip` are left out, and so are the
+/// temporary files of interrupted writes at any depth.
+/// With `link_files`, files are hard links to the originals when possible.
+/// Uses Box::pin to handle the recursive async calls.
+fn copy_dir_recursive(
+    source: PathBuf,
+    dest: PathBuf,
+    skip: &'static [&'static str],
+    link_files: bool,
+) -> std::pin::Pin<Box<dyn std::future::Future<Output = ConnectorResult<()>> + Send>> {
+    Box::pin(async move {
+        fs::create_dir_all(&dest).await?;
+
+        let mut entries = fs::read_dir(&source).await?;
+
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if skip.contains(&name.as_str()) || is_temp_name(&name) {
+                continue;
+            }
+            let entry_path = entry.path();
+            let dest_path = dest.join(entry.file_name());
+
+            if entry.file_type().await?.is_dir() {
+                copy_dir_recursive(entry_path, dest_path, &[], link_files).await?;
+            } else if !link_files || fs::hard_link(&entry_path, &dest_path).await.is_err() {
                 fs::copy(&entry_path, &dest_path).await?;
             }
         }
@@ -674,21 +2176,651 @@ fn copy_dir_recursive(
     })
 }
 
-/// Sort JSON object keys recursively for stable serialization
-fn sort_json_keys(value: &serde_json::Value) -> serde_json::Value {
-    match value {
-        serde_json::Value::Object(map) => {
-            let mut sorted: serde_json::Map<String, serde_json::Value> = serde_json::Map::new();
-            let mut keys: Vec<_> = map.keys().collect();
-            keys.sort();
-            for key in keys {
-                sorted.insert(key.clone(), sort_json_keys(&map[key]));
+/// Get the number of hard links to a file, when the platform tells it
+#[cfg(unix)]
+fn link_count(metadata: &std::fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.nlink())
+}
+
+/// Get the number of hard links to a file, when the platform tells it
+#[cfg(not(unix))]
+fn link_count(_metadata: &std::fs::Metadata) -> Option<u64> {
+    None
+}
+
+/// Parse a trash entry name, `{deleted_at_millis}-{website_id}`
+fn parse_trash_id(trash_id: &str) -> Option<(DateTime<Utc>, WebsiteId)> {
+    let (timestamp, website_id) = trash_id.split_once('-')?;
+    check_website_id(website_id).ok()?;
+    let deleted_at = DateTime::<Utc>::from_timestamp_millis(timestamp.parse().ok()?)?;
+    Some((deleted_at, website_id.to_string()))
+}
+
+/// Marker in the names of temporary files and directories
+const TEMP_MARKER: &str = ".tmp-";
+
+/// Get a temporary path next to the given path, in the same directory
+///
+/// The name is hidden and unique: `.{name}.tmp-{uuid}`.
+fn temp_path_for(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!(
+        ".{}{}{}",
+        name,
+        TEMP_MARKER,
+        Uuid::new_v4().simple()
+    ))
+}
+
+/// Whether a file or directory name is a temporary one (see `temp_path_for`)
+fn is_temp_name(name: &str) -> bool {
+    name.starts_with('.') && name.contains(TEMP_MARKER)
+}
+
+/// A temporary file, removed when dropped, e.g. when its upload is cancelled
+struct TempFile(PathBuf);
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        // Already renamed into place in most cases
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Make a file a hard link to a blob, atomically
+///
+/// Links next to the target, then renames. Returns false when the blob can
+/// not be linked, e.g. when it was purged in between or the filesystem has
+/// no hard links.
+async fn link_blob(blob_path: &Path, path: &Path) -> ConnectorResult<bool> {
+    let temp_path = temp_path_for(path);
+    if let Err(e) = fs::hard_link(blob_path, &temp_path).await {
+        tracing::debug!("Could not link blob {}: {}", blob_path.display(), e);
+        return Ok(false);
+    }
+    if let Err(e) = fs::rename(&temp_path, path).await {
+        let _ = fs::remove_file(&temp_path).await;
+        return Err(e.into());
+    }
+    if let Some(parent) = path.parent() {
+        sync_dir(parent).await?;
+    }
+    Ok(true)
+}
+
+/// Write a file atomically
+///
+/// Writes to a temporary file, flushes it to disk, then renames it over the
+/// target. Readers and crashes see either the old or the new content.
+async fn write_atomic(path: &Path, content: &[u8]) -> ConnectorResult<()> {
+    let temp_path = temp_path_for(path);
+
+    let written = async {
+        let mut file = fs::File::create(&temp_path).await?;
+        file.write_all(content).await?;
+        file.sync_all().await?;
+        drop(file);
+        fs::rename(&temp_path, path).await
+    };
+    if let Err(e) = written.await {
+        let _ = fs::remove_file(&temp_path).await;
+        return Err(e.into());
+    }
+
+    // Make the rename itself durable
+    if let Some(parent) = path.parent() {
+        sync_dir(parent).await?;
+    }
+
+    Ok(())
+}
+
+/// Flush a directory entry changes (creations, renames) to disk
+async fn sync_dir(path: &Path) -> ConnectorResult<()> {
+    #[cfg(unix)]
+    fs::File::open(path).await?.sync_all().await?;
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+/// Remove a temporary file or directory, logging failures
+async fn remove_temp(path: &Path) {
+    let removed = if path.is_dir() {
+        fs::remove_dir_all(path).await
+    } else {
+        fs::remove_file(path).await
+    };
+    if let Err(e) = removed {
+        tracing::warn!("Could not remove {}: {}", path.display(), e);
+    }
+}
+
+/// Recursively remove the temporary files left in a directory
+///
+/// Uses Box::pin to handle the recursive async calls.
+fn remove_temp_files(
+    dir: PathBuf,
+) -> std::pin::Pin<Box<dyn std::future::Future<Output = ConnectorResult<()>> + Send>> {
+    Box::pin(async move {
+        let mut entries = fs::read_dir(&dir).await?;
+
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if is_temp_name(&name) {
+                tracing::warn!("Removing interrupted write {}", entry.path().display());
+                remove_temp(&entry.path()).await;
+            } else if entry.file_type().await?.is_dir() {
+                remove_temp_files(entry.path()).await?;
+            }
+        }
+
+        Ok(())
+    })
+}
+
+/// Rename the page files which are not in the given set of file names with an `.orphan` extension
+async fn set_aside_orphan_pages(pages_path: &Path, page_files: &HashSet<String>) {
+    let Ok(mut entries) = fs::read_dir(pages_path).await else {
+        return;
+    };
+
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if file_name.ends_with(".json") && !page_files.contains(&file_name) {
+            let path = entry.path();
+            tracing::warn!("Setting aside unreferenced page file {}", path.display());
+            let orphan = pages_path.join(format!("{}.orphan", file_name));
+            if let Err(e) = fs::rename(&path, &orphan).await {
+                tracing::warn!("Could not set aside {}: {}", path.display(), e);
             }
-            serde_json::Value::Object(sorted)
         }
-        serde_json::Value::Array(arr) => {
-            serde_json::Value::Array(arr.iter().map(sort_json_keys).collect())
+    }
+}
+
+/// Check that a website ID can be used as a directory and lock file name
+///
+/// IDs are generated UUIDs, or folder names of the data path: they can not hold
+/// path separators, be `..`, or start with a `.` like the storage's own folders.
+pub(crate) fn check_website_id(website_id: &str) -> ConnectorResult<()> {
+    if website_id.is_empty()
+        || website_id.starts_with('.')
+        || website_id.contains(['/', '\\', '\0'])
+    {
+        return Err(ConnectorError::InvalidInput(format!(
+            "Invalid website ID '{}'",
+            website_id
+        )));
+    }
+    Ok(())
+}
+
+/// Delete the page files which are not in the given set of file names
+async fn remove_orphan_pages(pages_path: &Path, page_files: &HashSet<String>) {
+    let Ok(mut entries) = fs::read_dir(pages_path).await else {
+        return;
+    };
+
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if file_name.ends_with(".json") && !page_files.contains(&file_name) {
+            let _ = fs::remove_file(entry.path()).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// A storage in a temporary folder
+    fn storage() -> (tempfile::TempDir, FsStorage) {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = FsStorage::new(dir.path().to_path_buf(), "assets".to_string());
+        (dir, storage)
+    }
+
+    /// A website with two pages
+    async fn website(storage: &FsStorage) -> WebsiteId {
+        let session = json!({});
+        let website_id = storage
+            .create_website(&session, &WebsiteMetaFileContent::default())
+            .await
+            .unwrap();
+        let data = WebsiteData {
+            pages: vec![
+                json!({ "id": "home", "name": "Home" }),
+                json!({ "id": "about", "name": "About" }),
+            ],
+            ..Default::default()
+        };
+        storage
+            .update_website(&session, &website_id, &data)
+            .await
+            .unwrap();
+        website_id
+    }
+
+    /// The names of the files in a directory, sorted
+    fn file_names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<_> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[tokio::test]
+    async fn write_atomic_replaces_the_file_and_leaves_no_temp_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("website.json");
+
+        write_atomic(&path, b"old").await.unwrap();
+        write_atomic(&path, b"new").await.unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), b"new");
+        assert_eq!(file_names(dir.path()), ["website.json"]);
+    }
+
+    #[test]
+    fn temp_names_are_hidden_and_unique() {
+        let path = Path::new("/data/site/website.json");
+        let (a, b) = (temp_path_for(path), temp_path_for(path));
+        let name = a.file_name().unwrap().to_string_lossy().to_string();
+
+        assert_ne!(a, b);
+        assert_eq!(a.parent(), path.parent());
+        assert!(name.starts_with(".website.json.tmp-"));
+        assert!(is_temp_name(&name));
+        assert!(!is_temp_name("website.json"));
+        assert!(!is_temp_name(".locks"));
+    }
+
+    #[tokio::test]
+    async fn saves_remove_the_files_of_removed_pages() {
+        let (dir, storage) = storage();
+        let website_id = website(&storage).await;
+        let pages_path = dir.path().join(&website_id).join("pages");
+        assert_eq!(file_names(&pages_path).len(), 2);
+
+        let data = WebsiteData {
+            pages: vec![json!({ "id": "home", "name": "Home" })],
+            ..Default::default()
+        };
+        storage
+            .update_website(&json!({}), &website_id, &data)
+            .await
+            .unwrap();
+
+        assert_eq!(file_names(&pages_path).len(), 1);
+        let read = storage.read_website(&json!({}), &website_id).await.unwrap();
+        assert_eq!(read.pages, data.pages);
+    }
+
+    #[tokio::test]
+    async fn recover_removes_temp_files_and_sets_aside_orphan_pages() {
+        let (dir, storage) = storage();
+        let website_id = website(&storage).await;
+        let website_path = dir.path().join(&website_id);
+        let pages_path = website_path.join("pages");
+        let pages = file_names(&pages_path);
+
+        // Left by interrupted saves and duplicates
+        std::fs::write(website_path.join(".website.json.tmp-1"), "{").unwrap();
+        std::fs::write(pages_path.join(".home.json.tmp-2"), "{").unwrap();
+        std::fs::write(pages_path.join("lost-page.json"), "{}").unwrap();
+        std::fs::create_dir(dir.path().join(".copy.tmp-3")).unwrap();
+
+        storage.recover().await.unwrap();
+
+        let mut expected = pages.clone();
+        expected.push("lost-page.json.orphan".to_string());
+        expected.sort();
+        assert_eq!(file_names(&pages_path), expected);
+        assert!(!website_path.join(".website.json.tmp-1").exists());
+        assert!(!dir.path().join(".copy.tmp-3").exists());
+
+        // The website is still whole
+        let read = storage.read_website(&json!({}), &website_id).await.unwrap();
+        assert_eq!(read.pages.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn recover_skips_websites_it_can_not_read() {
+        let (dir, storage) = storage();
+        let website_path = dir.path().join("broken");
+        std::fs::create_dir_all(website_path.join("pages")).unwrap();
+        std::fs::write(website_path.join("website.json"), "{").unwrap();
+        std::fs::write(website_path.join("pages/page.json"), "{}").unwrap();
+
+        storage.recover().await.unwrap();
+
+        assert_eq!(file_names(&website_path.join("pages")), ["page.json"]);
+    }
+
+    #[test]
+    fn trash_ids_hold_the_deletion_time_and_website() {
+        let (deleted_at, website_id) = parse_trash_id("1700000000000-my-site").unwrap();
+        assert_eq!(deleted_at.timestamp_millis(), 1_700_000_000_000);
+        assert_eq!(website_id, "my-site");
+
+        for trash_id in [
+            "my-site",
+            "x-my-site",
+            "1700000000000-",
+            "1700000000000-.locks",
+        ] {
+            assert!(
+                parse_trash_id(trash_id).is_none(),
+                "{} was accepted",
+                trash_id
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn deleted_websites_go_to_the_trash_and_can_be_restored() {
+        let (_dir, storage) = storage();
+        let session = json!({});
+        let website_id = website(&storage).await;
+
+        storage.delete_website(&session, &website_id).await.unwrap();
+
+        assert!(storage.list_websites(&session).await.unwrap().is_empty());
+        assert!(matches!(
+            storage.read_website(&session, &website_id).await,
+            Err(ConnectorError::NotFound(_))
+        ));
+        let trash = storage.list_trash(&session).await.unwrap();
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].website_id, website_id);
+        assert_eq!(
+            trash[0].expires_at - trash[0].deleted_at,
+            Duration::days(DEFAULT_TRASH_RETENTION_DAYS)
+        );
+
+        let restored = storage
+            .restore_website(&session, &trash[0].trash_id)
+            .await
+            .unwrap();
+
+        assert_eq!(restored, website_id);
+        let data = storage.read_website(&session, &website_id).await.unwrap();
+        assert_eq!(data.pages.len(), 2);
+        assert!(storage.list_trash(&session).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn restore_does_not_replace_a_website() {
+        let (_dir, storage) = storage();
+        let session = json!({});
+        let website_id = website(&storage).await;
+        storage.delete_website(&session, &website_id).await.unwrap();
+        let trash_id = storage.list_trash(&session).await.unwrap()[0]
+            .trash_id
+            .clone();
+        storage
+            .create_website_with_id(&session, &website_id, &WebsiteMetaFileContent::default())
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            storage.restore_website(&session, &trash_id).await,
+            Err(ConnectorError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            storage.restore_website(&session, "../evil").await,
+            Err(ConnectorError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            storage.restore_website(&session, "1-missing").await,
+            Err(ConnectorError::NotFound(_))
+        ));
+        assert_eq!(storage.list_trash(&session).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn expired_websites_are_purged_from_the_trash() {
+        let (dir, storage) = storage();
+        let session = json!({});
+        let (old, recent) = (website(&storage).await, website(&storage).await);
+        storage.delete_website(&session, &old).await.unwrap();
+        storage.delete_website(&session, &recent).await.unwrap();
+
+        // Deleted before the retention period
+        let trash_path = dir.path().join(TRASH_FOLDER);
+        let old_entry = storage
+            .list_trash(&session)
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|w| w.website_id == old)
+            .unwrap();
+        let deleted_at = Utc::now() - Duration::days(DEFAULT_TRASH_RETENTION_DAYS + 1);
+        std::fs::rename(
+            trash_path.join(&old_entry.trash_id),
+            trash_path.join(format!("{}-{}", deleted_at.timestamp_millis(), old)),
+        )
+        .unwrap();
+
+        assert_eq!(storage.purge_expired_trash().await.unwrap(), 1);
+
+        let trash = storage.list_trash(&session).await.unwrap();
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].website_id, recent);
+    }
+
+    #[tokio::test]
+    async fn purge_the_trash() {
+        let (_dir, storage) = storage();
+        let session = json!({});
+        for _ in 0..3 {
+            let website_id = website(&storage).await;
+            storage.delete_website(&session, &website_id).await.unwrap();
         }
-        _ => value.clone(),
+        let trash_id = storage.list_trash(&session).await.unwrap()[0]
+            .trash_id
+            .clone();
+
+        assert_eq!(
+            storage
+                .purge_trash(&session, Some(&trash_id))
+                .await
+                .unwrap(),
+            1
+        );
+        assert!(matches!(
+            storage.purge_trash(&session, Some(&trash_id)).await,
+            Err(ConnectorError::NotFound(_))
+        ));
+        assert_eq!(storage.purge_trash(&session, None).await.unwrap(), 2);
+        assert!(storage.list_trash(&session).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn without_retention_websites_are_deleted_at_once() {
+        let (dir, storage) = storage();
+        let storage = storage.with_trash(Duration::zero());
+        let session = json!({});
+        let website_id = website(&storage).await;
+
+        storage.delete_website(&session, &website_id).await.unwrap();
+
+        assert!(!dir.path().join(&website_id).exists());
+        assert!(storage.list_trash(&session).await.unwrap().is_empty());
+        assert!(matches!(
+            storage.delete_website(&session, &website_id).await,
+            Err(ConnectorError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn pages_are_saved_in_their_own_file() {
+        let (dir, storage) = storage();
+        let session = json!({});
+        let website_id = website(&storage).await;
+        let pages_path = dir.path().join(&website_id).join("pages");
+        let files = file_names(&pages_path);
+
+        // Renamed, the page keeps its file
+        let home = json!({ "id": "home", "name": "Welcome", "html": "<p>Hi</p>" });
+        let revision = storage
+            .write_page(&session, &website_id, &home, None)
+            .await
+            .unwrap();
+        assert_eq!(revision, page_revision(&home));
+        assert_eq!(file_names(&pages_path), files);
+        assert_eq!(
+            storage
+                .read_page(&session, &website_id, "home")
+                .await
+                .unwrap(),
+            home
+        );
+
+        let contact = json!({ "id": "contact", "name": "Contact" });
+        storage
+            .write_page(&session, &website_id, &contact, None)
+            .await
+            .unwrap();
+        storage
+            .delete_page(&session, &website_id, "about")
+            .await
+            .unwrap();
+
+        assert_eq!(file_names(&pages_path).len(), 2);
+        let data = storage.read_website(&session, &website_id).await.unwrap();
+        assert_eq!(data.pages, [home, contact]);
+        assert!(matches!(
+            storage.read_page(&session, &website_id, "about").await,
+            Err(ConnectorError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn page_saves_check_the_page_revision() {
+        let (_dir, storage) = storage();
+        let session = json!({});
+        let website_id = website(&storage).await;
+        let home = storage
+            .read_page(&session, &website_id, "home")
+            .await
+            .unwrap();
+        let edited = json!({ "id": "home", "name": "Edited" });
+
+        let result = storage
+            .write_page(&session, &website_id, &edited, Some("stale"))
+            .await;
+        assert!(
+            matches!(result, Err(ConnectorError::Conflict(current)) if current == page_revision(&home))
+        );
+        storage
+            .write_page(&session, &website_id, &edited, Some(&page_revision(&home)))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn website_and_meta_saves_check_their_revision() {
+        let (_dir, storage) = storage();
+        let session = json!({});
+        let website_id = website(&storage).await;
+
+        let mut data = storage.read_website(&session, &website_id).await.unwrap();
+        data.pages.pop();
+        // The revision of the data sent, not of the stored data
+        assert!(matches!(
+            storage
+                .update_website_if_match(&session, &website_id, &data, Some(&data.revision()))
+                .await,
+            Err(ConnectorError::Conflict(_))
+        ));
+
+        let current = storage.read_website(&session, &website_id).await.unwrap();
+        let revision = storage
+            .update_website_if_match(&session, &website_id, &data, Some(&current.revision()))
+            .await
+            .unwrap();
+        assert_eq!(revision, data.revision());
+        assert!(matches!(
+            storage
+                .update_website_if_match(&session, &website_id, &data, Some(&current.revision()))
+                .await,
+            Err(ConnectorError::Conflict(current)) if current == revision
+        ));
+
+        // The modification time and author are not part of the revision
+        let meta = storage
+            .get_website_meta(&session, &website_id)
+            .await
+            .unwrap()
+            .to_file_content();
+        let renamed = WebsiteMetaFileContent {
+            name: "Renamed".to_string(),
+            ..meta.clone()
+        };
+        let revision = storage
+            .set_website_meta_if_match(&session, &website_id, &renamed, Some(&meta.revision()))
+            .await
+            .unwrap();
+        assert_eq!(
+            storage
+                .get_website_meta(&session, &website_id)
+                .await
+                .unwrap()
+                .to_file_content()
+                .revision(),
+            revision
+        );
+        assert!(matches!(
+            storage
+                .set_website_meta_if_match(&session, &website_id, &meta, Some(&meta.revision()))
+                .await,
+            Err(ConnectorError::Conflict(current)) if current == revision
+        ));
+    }
+
+    #[tokio::test]
+    async fn damaged_websites_are_reported_and_repaired() {
+        let (dir, storage) = storage();
+        let session = json!({});
+        let website_id = website(&storage).await;
+        let pages_path = dir.path().join(&website_id).join("pages");
+
+        // Lose the file of a page, and add one website.json does not reference
+        let parsed: serde_json::Value = serde_json::from_slice(
+            &std::fs::read(dir.path().join(&website_id).join("website.json")).unwrap(),
+        )
+        .unwrap();
+        let about = website_files::page_file_name(&parsed["pages"][1]).unwrap();
+        std::fs::remove_file(pages_path.join(&about)).unwrap();
+        std::fs::write(pages_path.join("lost.json"), r#"{ "id": "lost" }"#).unwrap();
+        std::fs::write(pages_path.join("bad.json"), "{").unwrap();
+
+        let report = storage
+            .check_integrity(&session, &website_id)
+            .await
+            .unwrap();
+        assert!(report.is_corrupted());
+        assert_eq!(report.missing_pages.len(), 1);
+        assert_eq!(report.missing_pages[0].id, "about");
+        assert_eq!(report.orphan_pages, ["pages/bad.json", "pages/lost.json"]);
+
+        let report = storage.repair_website(&session, &website_id).await.unwrap();
+
+        assert!(!report.is_corrupted());
+        // Set aside, and still reported until it is restored or removed by hand
+        assert_eq!(report.orphan_pages, ["pages/bad.json.corrupted"]);
+        let data = storage.read_website(&session, &website_id).await.unwrap();
+        assert_eq!(
+            data.pages.iter().filter_map(page_id_of).collect::<Vec<_>>(),
+            ["home", "lost"]
+        );
     }
 }
@@ -0,0 +1,810 @@
+/*
+ * Silex website builder, free/libre no-code tool for makers.
+ * Copyright (c) 2023 lexoyo and Silex Labs foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or any later version.
+ */
+
+//! GitLab API client
+//!
+//! Shared by the GitLab storage and hosting connectors.
+//! Handles the OAuth flow, the session token and calls to the GitLab REST API (v4).
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use base64::Engine;
+use reqwest::{Method, RequestBuilder, StatusCode};
+
+use crate::error::{ConnectorError, ConnectorResult};
+use crate::models::{ConnectorType, WebsiteId};
+
+/// Key of the GitLab token in the session
+///
+/// Storage and hosting connectors share the same token,
+/// so logging into one logs into the other.
+const SESSION_KEY: &str = "gitlab";
+
+/// Branch where website files are committed
+pub(crate) const BRANCH: &str = "main";
+
+/// Topic added to the GitLab projects created by Silex
+pub(crate) const PROJECT_TOPIC: &str = "silex";
+
+/// Number of items per page for paginated API calls (GitLab maximum)
+const PER_PAGE: usize = 100;
+
+/// GraphQL query of the sizes of repository files
+const BLOB_SIZES_QUERY: &str = "query($project: ID!, $ref: String!, $paths: [String!]!) {
+  project(fullPath: $project) {
+    repository { blobs(ref: $ref, paths: $paths) { nodes { path size } } }
+  }
+}";
+
+/// GitLab connector options
+#[derive(Debug, Clone)]
+pub struct GitlabOptions {
+    /// GitLab instance URL (e.g., "https://gitlab.com")
+    pub domain: String,
+
+    /// OAuth application ID
+    pub client_id: String,
+
+    /// OAuth application secret
+    pub client_secret: String,
+
+    /// Base URL of this server, used to build the OAuth redirect URI
+    pub server_url: String,
+
+    /// Name displayed in the UI
+    pub display_name: String,
+}
+
+/// Client for the GitLab REST API
+#[derive(Clone)]
+pub(crate) struct GitlabClient {
+    options: Arc<GitlabOptions>,
+    http: reqwest::Client,
+}
+
+impl GitlabClient {
+    /// Create a new client
+    pub(crate) fn new(options: GitlabOptions) -> Self {
+        GitlabClient {
+            options: Arc::new(options),
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Get the connector options
+    pub(crate) fn options(&self) -> &GitlabOptions {
+        &self.options
+    }
+
+    // ==================
+    // OAuth
+    // ==================
+
+    /// Get the access token stored in the session, if any
+    pub(crate) fn token<'a>(&self, session: &'a serde_json::Value) -> Option<&'a str> {
+        session
+            .get(SESSION_KEY)
+            .and_then(|t| t.get("access_token"))
+            .and_then(|t| t.as_str())
+    }
+
+    /// The URI GitLab redirects to after the user authorized the application
+    fn redirect_uri(&self, connector_id: &str, connector_type: ConnectorType) -> String {
+        let connector_type = match connector_type {
+            ConnectorType::Storage => "STORAGE",
+            ConnectorType::Hosting => "HOSTING",
+        };
+        format!(
+            "{}/api/connector/login/callback?connectorId={}&type={}",
+            self.options.server_url.trim_end_matches('/'),
+            connector_id,
+            connector_type
+        )
+    }
+
+    /// Build the URL of the GitLab authorization page
+    pub(crate) fn oauth_url(&self, connector_id: &str, connector_type: ConnectorType) -> String {
+        format!(
+            "{}/oauth/authorize?client_id={}&redirect_uri={}&response_type=code&scope=api",
+            self.domain(),
+            encode(&self.options.client_id),
+            encode(&self.redirect_uri(connector_id, connector_type)),
+        )
+    }
+
+    /// Exchange the authorization code for a token and store it in the session
+    ///
+    /// `token` is the data received by the OAuth callback: `{ code, state }`.
+    pub(crate) async fn set_token(
+        &self,
+        session: &mut serde_json::Value,
+        token: &serde_json::Value,
+        connector_id: &str,
+        connector_type: ConnectorType,
+    ) -> ConnectorResult<()> {
+        let code = token
+            .get("code")
+            .and_then(|c| c.as_str())
+            .ok_or_else(|| ConnectorError::InvalidInput("Missing OAuth code".to_string()))?;
+
+        let redirect_uri = self.redirect_uri(connector_id, connector_type);
+        let params = [
+            ("client_id", self.options.client_id.as_str()),
+            ("client_secret", self.options.client_secret.as_str()),
+            ("code", code),
+            ("grant_type", "authorization_code"),
+            ("redirect_uri", redirect_uri.as_str()),
+        ];
+
+        let response = self
+            .http
+            .post(format!("{}/oauth/token", self.domain()))
+            .form(&params)
+            .send()
+            .await
+            .map_err(|e| ConnectorError::Remote(e.to_string()))?;
+
+        let token: serde_json::Value = check_response(response, "oauth/token")
+            .await?
+            .json()
+            .await
+            .map_err(|e| ConnectorError::Remote(e.to_string()))?;
+
+        if token.get("access_token").is_none() {
+            return Err(ConnectorError::Remote(format!(
+                "GitLab did not return an access token: {}",
+                token
+            )));
+        }
+
+        if !session.is_object() {
+            *session = serde_json::json!({});
+        }
+        session[SESSION_KEY] = token;
+
+        Ok(())
+    }
+
+    /// Remove the token from the session
+    pub(crate) fn logout(&self, session: &mut serde_json::Value) {
+        if let Some(map) = session.as_object_mut() {
+            map.remove(SESSION_KEY);
+        }
+    }
+
+    // ==================
+    // REST API
+    // ==================
+
+    /// GitLab instance URL without trailing slash
+    fn domain(&self) -> &str {
+        self.options.domain.trim_end_matches('/')
+    }
+
+    /// Build an authenticated request to the API
+    fn request(
+        &self,
+        session: &serde_json::Value,
+        method: Method,
+        path: &str,
+    ) -> ConnectorResult<RequestBuilder> {
+        let token = self
+            .token(session)
+            .ok_or(ConnectorError::NotAuthenticated)?;
+        Ok(self
+            .http
+            .request(method, format!("{}/api/v4/{}", self.domain(), path))
+            .bearer_auth(token))
+    }
+
+    /// Send a request and parse the JSON response
+    pub(crate) async fn call(
+        &self,
+        session: &serde_json::Value,
+        method: Method,
+        path: &str,
+        body: Option<&serde_json::Value>,
+    ) -> ConnectorResult<serde_json::Value> {
+        let mut request = self.request(session, method, path)?;
+        if let Some(body) = body {
+            request = request.json(body);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| ConnectorError::Remote(e.to_string()))?;
+        let response = check_response(response, path).await?;
+
+        // Some endpoints (e.g. DELETE) return an empty body
+        let bytes = response
+            .bytes()
+            .await
+            .map_err(|e| ConnectorError::Remote(e.to_string()))?;
+        if bytes.is_empty() {
+            return Ok(serde_json::Value::Null);
+        }
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    /// GET a JSON resource
+    pub(crate) async fn get(
+        &self,
+        session: &serde_json::Value,
+        path: &str,
+    ) -> ConnectorResult<serde_json::Value> {
+        self.call(session, Method::GET, path, None).await
+    }
+
+    /// GET every page of a paginated JSON list
+    pub(crate) async fn get_all(
+        &self,
+        session: &serde_json::Value,
+        path: &str,
+    ) -> ConnectorResult<Vec<serde_json::Value>> {
+        let separator = if path.contains('?') { '&' } else { '?' };
+        let mut items = Vec::new();
+        let mut page = 1;
+
+        loop {
+            let url = format!("{}{}per_page={}&page={}", path, separator, PER_PAGE, page);
+            let batch = match self.get(session, &url).await? {
+                serde_json::Value::Array(batch) => batch,
+                _ => break,
+            };
+            let count = batch.len();
+            items.extend(batch);
+            if count < PER_PAGE {
+                break;
+            }
+            page += 1;
+        }
+
+        Ok(items)
+    }
+
+    /// Read a raw file from a project repository
+    pub(crate) async fn read_file(
+        &self,
+        session: &serde_json::Value,
+        project_id: &WebsiteId,
+        file_path: &str,
+    ) -> ConnectorResult<Vec<u8>> {
+        let path = format!(
+            "projects/{}/repository/files/{}/raw?ref={}",
+            encode(project_id),
+            encode(file_path),
+            BRANCH
+        );
+
+        let response = self
+            .request(session, Method::GET, &path)?
+            .send()
+            .await
+            .map_err(|e| ConnectorError::Remote(e.to_string()))?;
+        let response = check_response(response, file_path).await?;
+
+        let bytes = response
+            .bytes()
+            .await
+            .map_err(|e| ConnectorError::Remote(e.to_string()))?;
+        Ok(bytes.to_vec())
+    }
+
+    /// List all file paths of a project repository
+    pub(crate) async fn list_files(
+        &self,
+        session: &serde_json::Value,
+        project_id: &WebsiteId,
+    ) -> ConnectorResult<HashSet<String>> {
+        let path = format!(
+            "projects/{}/repository/tree?recursive=true&ref={}",
+            encode(project_id),
+            BRANCH
+        );
+
+        // An empty repository has no tree yet
+        let entries = match self.get_all(session, &path).await {
+            Ok(entries) => entries,
+            Err(ConnectorError::NotFound(_)) => Vec::new(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(entries
+            .iter()
+            .filter(|e| e.get("type").and_then(|t| t.as_str()) == Some("blob"))
+            .filter_map(|e| e.get("path").and_then(|p| p.as_str()).map(String::from))
+            .collect())
+    }
+
+    /// Get the sizes in bytes of files of a project repository
+    ///
+    /// The REST tree has no sizes, they are asked to the GraphQL API by
+    /// batches of paths instead of one request per file. Files GitLab does
+    /// not return are missing from the result.
+    pub(crate) async fn file_sizes(
+        &self,
+        session: &serde_json::Value,
+        project_id: &WebsiteId,
+        file_paths: &[String],
+    ) -> ConnectorResult<HashMap<String, u64>> {
+        let mut sizes = HashMap::with_capacity(file_paths.len());
+        if file_paths.is_empty() {
+            return Ok(sizes);
+        }
+
+        // GraphQL finds projects by path, not by ID
+        let project = self
+            .get(session, &format!("projects/{}", encode(project_id)))
+            .await?;
+        let full_path = project
+            .get("path_with_namespace")
+            .and_then(|p| p.as_str())
+            .ok_or_else(|| {
+                ConnectorError::Remote(format!("GitLab project {} has no path", project_id))
+            })?;
+
+        for batch in file_paths.chunks(PER_PAGE) {
+            let body = serde_json::json!({
+                "query": BLOB_SIZES_QUERY,
+                "variables": { "project": full_path, "ref": BRANCH, "paths": batch },
+            });
+            let result = self.graphql(session, &body).await?;
+            let nodes = result
+                .pointer("/data/project/repository/blobs/nodes")
+                .and_then(|n| n.as_array())
+                .cloned()
+                .unwrap_or_default();
+            for node in nodes {
+                let path = node.get("path").and_then(|p| p.as_str());
+                // BigInt values are serialized as strings
+                let size = node.get("size").and_then(|s| match s {
+                    serde_json::Value::String(s) => s.parse().ok(),
+                    s => s.as_u64(),
+                });
+                if let (Some(path), Some(size)) = (path, size) {
+                    sizes.insert(path.to_string(), size);
+                }
+            }
+        }
+
+        Ok(sizes)
+    }
+
+    /// Send a query to the GraphQL API
+    ///
+    /// GraphQL reports errors with a success status, they are mapped here.
+    async fn graphql(
+        &self,
+        session: &serde_json::Value,
+        body: &serde_json::Value,
+    ) -> ConnectorResult<serde_json::Value> {
+        let token = self
+            .token(session)
+            .ok_or(ConnectorError::NotAuthenticated)?;
+        let response = self
+            .http
+            .post(format!("{}/api/graphql", self.domain()))
+            .bearer_auth(token)
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| ConnectorError::Remote(e.to_string()))?;
+
+        let result: serde_json::Value = check_response(response, "graphql")
+            .await?
+            .json()
+            .await
+            .map_err(|e| ConnectorError::Remote(e.to_string()))?;
+        if let Some(errors) = result.get("errors") {
+            return Err(ConnectorError::Remote(format!(
+                "GitLab GraphQL error: {}",
+                errors
+            )));
+        }
+
+        Ok(result)
+    }
+
+    /// Create a commit moving a file of a project repository
+    ///
+    /// The move is a single commit, the file is never lost nor duplicated.
+    pub(crate) async fn move_file(
+        &self,
+        session: &serde_json::Value,
+        project_id: &WebsiteId,
+        message: &str,
+        from: &str,
+        to: &str,
+    ) -> ConnectorResult<()> {
+        let body = serde_json::json!({
+            "branch": BRANCH,
+            "commit_message": message,
+            "actions": [{ "action": "move", "previous_path": from, "file_path": to }],
+        });
+
+        self.call(
+            session,
+            Method::POST,
+            &format!("projects/{}/repository/commits", encode(project_id)),
+            Some(&body),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Create a commit writing and deleting files in a project repository
+    ///
+    /// Files are created or updated depending on whether they already exist.
+    /// Returns the SHA of the new commit.
+    pub(crate) async fn commit(
+        &self,
+        session: &serde_json::Value,
+        project_id: &WebsiteId,
+        message: &str,
+        files: Vec<(String, Vec<u8>)>,
+        deleted: Vec<String>,
+    ) -> ConnectorResult<String> {
+        let existing = self.list_files(session, project_id).await?;
+
+        let mut actions: Vec<serde_json::Value> = files
+            .into_iter()
+            .map(|(path, content)| {
+                let action = if existing.contains(&path) {
+                    "update"
+                } else {
+                    "create"
+                };
+                serde_json::json!({
+                    "action": action,
+                    "file_path": path,
+                    "encoding": "base64",
+                    "content": base64::engine::general_purpose::STANDARD.encode(content),
+                })
+            })
+            .collect();
+
+        actions.extend(
+            deleted
+                .into_iter()
+                .filter(|path| existing.contains(path))
+                .map(|path| serde_json::json!({ "action": "delete", "file_path": path })),
+        );
+
+        let body = serde_json::json!({
+            "branch": BRANCH,
+            "commit_message": message,
+            "actions": actions,
+        });
+
+        let commit = self
+            .call(
+                session,
+                Method::POST,
+                &format!("projects/{}/repository/commits", encode(project_id)),
+                Some(&body),
+            )
+            .await?;
+
+        Ok(commit
+            .get("id")
+            .and_then(|id| id.as_str())
+            .unwrap_or_default()
+            .to_string())
+    }
+}
+
+/// Map an HTTP error status to a ConnectorError
+async fn check_response(
+    response: reqwest::Response,
+    resource: &str,
+) -> ConnectorResult<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let body = response.text().await.unwrap_or_default();
+    match status {
+        StatusCode::UNAUTHORIZED => Err(ConnectorError::NotAuthenticated),
+        StatusCode::NOT_FOUND => Err(ConnectorError::NotFound(format!(
+            "GitLab resource not found: {}",
+            resource
+        ))),
+        StatusCode::BAD_REQUEST => Err(ConnectorError::InvalidInput(format!(
+            "GitLab rejected the request: {}",
+            body
+        ))),
+        _ => Err(ConnectorError::Remote(format!(
+            "GitLab API error {}: {}",
+            status, body
+        ))),
+    }
+}
+
+/// Percent-encode a value for use in a URL path segment or query parameter
+///
+/// GitLab expects file paths and project paths to be fully encoded (`/` as `%2F`).
+pub(crate) fn encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use wiremock::matchers::{body_string_contains, header, method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    /// A client of the mock GitLab instance
+    fn client(server: &MockServer) -> GitlabClient {
+        GitlabClient::new(GitlabOptions {
+            domain: server.uri(),
+            client_id: "app".to_string(),
+            client_secret: "secret".to_string(),
+            server_url: "http://silex.test".to_string(),
+            display_name: "GitLab".to_string(),
+        })
+    }
+
+    /// A session logged in to GitLab
+    fn session() -> serde_json::Value {
+        json!({ SESSION_KEY: { "access_token": "token" } })
+    }
+
+    #[tokio::test]
+    async fn set_token_exchanges_the_code() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/oauth/token"))
+            .and(body_string_contains("code=abc"))
+            .and(body_string_contains("grant_type=authorization_code"))
+            .and(body_string_contains("client_secret=secret"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "access_token": "token",
+                "token_type": "Bearer",
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut session = serde_json::Value::Null;
+        client(&server)
+            .set_token(
+                &mut session,
+                &json!({ "code": "abc", "state": "xyz" }),
+                "gitlab",
+                ConnectorType::Storage,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(client(&server).token(&session), Some("token"));
+    }
+
+    #[tokio::test]
+    async fn set_token_fails_without_access_token() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/oauth/token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "scope": "api" })))
+            .mount(&server)
+            .await;
+
+        let mut session = json!({});
+        let result = client(&server)
+            .set_token(
+                &mut session,
+                &json!({ "code": "abc" }),
+                "gitlab",
+                ConnectorType::Storage,
+            )
+            .await;
+
+        assert!(matches!(result, Err(ConnectorError::Remote(_))));
+        assert!(client(&server).token(&session).is_none());
+    }
+
+    #[tokio::test]
+    async fn set_token_requires_a_code() {
+        let server = MockServer::start().await;
+        let result = client(&server)
+            .set_token(
+                &mut json!({}),
+                &json!({ "state": "xyz" }),
+                "gitlab",
+                ConnectorType::Storage,
+            )
+            .await;
+
+        assert!(matches!(result, Err(ConnectorError::InvalidInput(_))));
+    }
+
+    #[test]
+    fn oauth_url_points_back_to_the_callback() {
+        let client = GitlabClient::new(GitlabOptions {
+            domain: "https://gitlab.test/".to_string(),
+            client_id: "app".to_string(),
+            client_secret: "secret".to_string(),
+            server_url: "http://silex.test/".to_string(),
+            display_name: "GitLab".to_string(),
+        });
+
+        assert_eq!(
+            client.oauth_url("gitlab-hosting", ConnectorType::Hosting),
+            "https://gitlab.test/oauth/authorize?client_id=app&redirect_uri=\
+             http%3A%2F%2Fsilex%2Etest%2Fapi%2Fconnector%2Flogin%2Fcallback%3F\
+             connectorId%3Dgitlab-hosting%26type%3DHOSTING&response_type=code&scope=api"
+        );
+    }
+
+    #[tokio::test]
+    async fn requests_send_the_session_token() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v4/user"))
+            .and(header("authorization", "Bearer token"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "name": "Alice" })))
+            .mount(&server)
+            .await;
+
+        let user = client(&server).get(&session(), "user").await.unwrap();
+        assert_eq!(user["name"], "Alice");
+
+        let result = client(&server).get(&json!({}), "user").await;
+        assert!(matches!(result, Err(ConnectorError::NotAuthenticated)));
+    }
+
+    #[tokio::test]
+    async fn error_statuses_are_mapped() {
+        let server = MockServer::start().await;
+        for (status, resource) in [(401, "a"), (404, "b"), (400, "c"), (500, "d")] {
+            Mock::given(path(format!("/api/v4/{}", resource)))
+                .respond_with(ResponseTemplate::new(status).set_body_string("details"))
+                .mount(&server)
+                .await;
+        }
+
+        let client = client(&server);
+        let session = session();
+        assert!(matches!(
+            client.get(&session, "a").await,
+            Err(ConnectorError::NotAuthenticated)
+        ));
+        assert!(matches!(
+            client.get(&session, "b").await,
+            Err(ConnectorError::NotFound(_))
+        ));
+        assert!(matches!(
+            client.get(&session, "c").await,
+            Err(ConnectorError::InvalidInput(message)) if message.contains("details")
+        ));
+        assert!(matches!(
+            client.get(&session, "d").await,
+            Err(ConnectorError::Remote(message)) if message.contains("500")
+        ));
+    }
+
+    #[tokio::test]
+    async fn get_all_reads_every_page() {
+        let server = MockServer::start().await;
+        let first: Vec<_> = (0..PER_PAGE).map(|id| json!({ "id": id })).collect();
+        Mock::given(path("/api/v4/projects"))
+            .and(query_param("page", "1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(first))
+            .mount(&server)
+            .await;
+        Mock::given(path("/api/v4/projects"))
+            .and(query_param("page", "2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([{ "id": 100 }])))
+            .mount(&server)
+            .await;
+
+        let items = client(&server)
+            .get_all(&session(), "projects?topic=silex")
+            .await
+            .unwrap();
+
+        assert_eq!(items.len(), PER_PAGE + 1);
+        assert_eq!(items[PER_PAGE]["id"], 100);
+    }
+
+    #[tokio::test]
+    async fn list_files_of_an_empty_repository() {
+        let server = MockServer::start().await;
+        Mock::given(path("/api/v4/projects/1/repository/tree"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+
+        let files = client(&server)
+            .list_files(&session(), &"1".to_string())
+            .await
+            .unwrap();
+        assert!(files.is_empty());
+    }
+
+    #[tokio::test]
+    async fn file_sizes_are_read_with_graphql() {
+        let server = MockServer::start().await;
+        Mock::given(path("/api/v4/projects/1"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({ "id": 1, "path_with_namespace": "alice/site" })),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/api/graphql"))
+            .and(body_string_contains("alice/site"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "data": { "project": { "repository": { "blobs": { "nodes": [
+                    { "path": "assets/a.png", "size": "1234" },
+                    { "path": "assets/b.css", "size": 56 },
+                ] } } } }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let paths = vec![
+            "assets/a.png".to_string(),
+            "assets/b.css".to_string(),
+            "assets/gone.txt".to_string(),
+        ];
+        let sizes = client(&server)
+            .file_sizes(&session(), &"1".to_string(), &paths)
+            .await
+            .unwrap();
+
+        assert_eq!(sizes.len(), 2);
+        assert_eq!(sizes["assets/a.png"], 1234);
+        assert_eq!(sizes["assets/b.css"], 56);
+    }
+
+    #[tokio::test]
+    async fn graphql_errors_are_mapped() {
+        let server = MockServer::start().await;
+        Mock::given(path("/api/v4/projects/1"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(json!({ "path_with_namespace": "a/b" })),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(path("/api/graphql"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "errors": [{ "message": "Field 'blobs' doesn't exist" }]
+            })))
+            .mount(&server)
+            .await;
+
+        let result = client(&server)
+            .file_sizes(&session(), &"1".to_string(), &["a".to_string()])
+            .await;
+        assert!(
+            matches!(result, Err(ConnectorError::Remote(message)) if message.contains("blobs"))
+        );
+    }
+
+    #[test]
+    fn encode_escapes_slashes() {
+        assert_eq!(encode("assets/a b.png"), "assets%2Fa%20b%2Epng");
+        assert_eq!(encode("my-site_1~"), "my-site_1~");
+    }
+}
@@ -0,0 +1,309 @@
+/*
+ * Silex website builder, free/libre no-code tool for makers.
+ * Copyright (c) 2023 lexoyo and Silex Labs foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or any later version.
+ */
+
+//! GitLab Pages hosting connector
+//!
+//! Publishes websites with GitLab Pages.
+//! The published files are committed to the `public/` folder of the website's
+//! project, along with a `.gitlab-ci.yml` which deploys this folder.
+//! This connector requires the website to be stored with GitlabStorage.
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::connectors::gitlab::{encode, GitlabClient, GitlabOptions};
+use crate::connectors::gitlab_storage::GITLAB_ICON;
+use crate::connectors::traits::{hosting_to_connector_data, ConnectorInfo, HostingConnector};
+use crate::error::{ConnectorError, ConnectorResult};
+use crate::models::{
+    ConnectorFile, ConnectorOptions, ConnectorType, ConnectorUser, PublicationJobData, WebsiteId,
+};
+use crate::services::JobManager;
+
+/// Folder deployed by GitLab Pages
+const PUBLIC_FOLDER: &str = "public";
+
+/// CI configuration which deploys the public folder to GitLab Pages
+const GITLAB_CI_FILE: &str = ".gitlab-ci.yml";
+const GITLAB_CI_CONTENT: &str = r#"# Generated by Silex, deploys the public/ folder to GitLab Pages
+image: alpine:latest
+pages:
+  stage: deploy
+  script:
+    - echo "The site will be deployed to $CI_PAGES_URL"
+  artifacts:
+    paths:
+      - public
+  rules:
+    - if: $CI_COMMIT_BRANCH == $CI_DEFAULT_BRANCH
+"#;
+
+/// Delay between two checks of the deployment pipeline
+const PIPELINE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Maximum number of pipeline checks before giving up (10 minutes)
+const PIPELINE_POLL_MAX_ATTEMPTS: u32 = 120;
+
+/// GitLab Pages hosting connector
+pub struct GitlabHosting {
+    /// GitLab API client
+    client: GitlabClient,
+}
+
+impl GitlabHosting {
+    /// Create a new GitlabHosting connector
+    ///
+    /// # Arguments
+    /// * `options` - GitLab instance and OAuth application settings
+    pub fn new(options: GitlabOptions) -> Self {
+        GitlabHosting {
+            client: GitlabClient::new(options),
+        }
+    }
+}
+
+/// Follow the deployment pipeline of a commit and update the job accordingly
+async fn watch_pipeline(
+    client: GitlabClient,
+    session: serde_json::Value,
+    website_id: WebsiteId,
+    sha: String,
+    mut job: PublicationJobData,
+    job_manager: JobManager,
+) {
+    let path = format!("projects/{}/pipelines?sha={}", encode(&website_id), sha);
+
+    for _ in 0..PIPELINE_POLL_MAX_ATTEMPTS {
+        tokio::time::sleep(PIPELINE_POLL_INTERVAL).await;
+
+        let pipelines = match client.get(&session, &path).await {
+            Ok(serde_json::Value::Array(pipelines)) => pipelines,
+            Ok(_) => continue,
+            Err(e) => {
+                job.fail(format!("Could not get the deployment status: {}", e));
+                job_manager.update_job(&job);
+                return;
+            }
+        };
+
+        let status = pipelines
+            .first()
+            .and_then(|p| p.get("status"))
+            .and_then(|s| s.as_str())
+            .unwrap_or("pending");
+
+        match status {
+            "success" => {
+                job.success("<p>Website published successfully.</p>".to_string());
+                job_manager.update_job(&job);
+                return;
+            }
+            "failed" | "canceled" | "skipped" => {
+                job.fail(format!("Deployment pipeline {}", status));
+                job_manager.update_job(&job);
+                return;
+            }
+            _ => {
+                job.base.message = format!("Deploying to GitLab Pages ({})", status);
+                job_manager.update_job(&job);
+            }
+        }
+    }
+
+    job.fail("Timed out waiting for the deployment pipeline".to_string());
+    job_manager.update_job(&job);
+}
+
+impl ConnectorInfo for GitlabHosting {
+    fn connector_id(&self) -> &str {
+        "gitlab-hosting"
+    }
+
+    fn connector_type(&self) -> ConnectorType {
+        ConnectorType::Hosting
+    }
+
+    fn display_name(&self) -> &str {
+        &self.client.options().display_name
+    }
+
+    fn icon(&self) -> &str {
+        GITLAB_ICON
+    }
+
+    fn color(&self) -> &str {
+        "#ffffff"
+    }
+
+    fn background(&self) -> &str {
+        "#fc6d26"
+    }
+}
+
+#[async_trait]
+impl HostingConnector for GitlabHosting {
+    // ==================
+    // Authentication
+    // Shares the token with GitlabStorage
+    // ==================
+
+    async fn is_logged_in(&self, session: &serde_json::Value) -> ConnectorResult<bool> {
+        Ok(self.client.token(session).is_some())
+    }
+
+    async fn get_oauth_url(&self, _session: &serde_json::Value) -> ConnectorResult<Option<String>> {
+        Ok(Some(
+            self.client
+                .oauth_url(self.connector_id(), self.connector_type()),
+        ))
+    }
+
+    async fn set_token(
+        &self,
+        session: &mut serde_json::Value,
+        token: &serde_json::Value,
+    ) -> ConnectorResult<()> {
+        self.client
+            .set_token(session, token, self.connector_id(), self.connector_type())
+            .await
+    }
+
+    async fn logout(&self, session: &mut serde_json::Value) -> ConnectorResult<()> {
+        self.client.logout(session);
+        Ok(())
+    }
+
+    async fn get_user(&self, session: &serde_json::Value) -> ConnectorResult<ConnectorUser> {
+        let user = self.client.get(session, "user").await?;
+        let field = |name: &str| user.get(name).and_then(|v| v.as_str()).map(String::from);
+
+        Ok(ConnectorUser {
+            name: field("name")
+                .or_else(|| field("username"))
+                .unwrap_or_else(|| "unknown".to_string()),
+            email: field("email"),
+            picture: field("avatar_url"),
+            storage: hosting_to_connector_data(session, self).await?,
+        })
+    }
+
+    fn get_options(&self, _form_data: &serde_json::Value) -> ConnectorOptions {
+        // No options for GitLab hosting
+        ConnectorOptions::default()
+    }
+
+    // ==================
+    // Publication
+    // ==================
+
+    async fn publish(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+        files: Vec<ConnectorFile>,
+        job_manager: &JobManager,
+    ) -> ConnectorResult<PublicationJobData> {
+        let mut job = job_manager.start_job(format!("Publishing to {}", self.display_name()));
+
+        job.log(format!("Publishing {} files to GitLab Pages", files.len()));
+
+        // Commit the files in the public folder, with the CI configuration
+        let mut commit_files: Vec<(String, Vec<u8>)> = files
+            .into_iter()
+            .map(|file| {
+                let relative_path = file.path.trim_start_matches('/').to_string();
+                (format!("{}/{}", PUBLIC_FOLDER, relative_path), file.content)
+            })
+            .collect();
+        commit_files.push((
+            GITLAB_CI_FILE.to_string(),
+            GITLAB_CI_CONTENT.as_bytes().to_vec(),
+        ));
+
+        // Remove previously published files which are not part of this publication
+        let commit = async {
+            let public_prefix = format!("{}/", PUBLIC_FOLDER);
+            let deleted = self
+                .client
+                .list_files(session, website_id)
+                .await?
+                .into_iter()
+                .filter(|path| path.starts_with(&public_prefix))
+                .filter(|path| !commit_files.iter().any(|(p, _)| p == path))
+                .collect();
+
+            self.client
+                .commit(
+                    session,
+                    website_id,
+                    "Publish website",
+                    commit_files,
+                    deleted,
+                )
+                .await
+        };
+
+        match commit.await {
+            Ok(sha) => {
+                job.log(format!(
+                    "Committed {}, waiting for the deployment pipeline",
+                    sha
+                ));
+                job.base.message = "Deploying to GitLab Pages".to_string();
+                job_manager.update_job(&job);
+
+                tokio::spawn(watch_pipeline(
+                    self.client.clone(),
+                    session.clone(),
+                    website_id.clone(),
+                    sha,
+                    job.clone(),
+                    job_manager.clone(),
+                ));
+            }
+            Err(e) => {
+                job.fail(format!("Publication failed: {}", e));
+                job_manager.fail_job(&job.base.job_id, &e.to_string());
+            }
+        }
+
+        Ok(job)
+    }
+
+    async fn get_url(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+    ) -> ConnectorResult<String> {
+        let project_path = format!("projects/{}", encode(website_id));
+
+        // The Pages URL is only known once the site has been deployed once
+        match self
+            .client
+            .get(session, &format!("{}/pages", project_path))
+            .await
+        {
+            Ok(pages) => {
+                if let Some(url) = pages.get("url").and_then(|u| u.as_str()) {
+                    return Ok(url.to_string());
+                }
+            }
+            Err(ConnectorError::NotFound(_)) => {}
+            Err(e) => return Err(e),
+        }
+
+        let project = self.client.get(session, &project_path).await?;
+        project
+            .get("web_url")
+            .and_then(|u| u.as_str())
+            .map(String::from)
+            .ok_or_else(|| ConnectorError::NotFound(format!("Website '{}' not found", website_id)))
+    }
+}
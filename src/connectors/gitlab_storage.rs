@@ -0,0 +1,1038 @@
+/*
+ * Silex website builder, free/libre no-code tool for makers.
+ * Copyright (c) 2023 lexoyo and Silex Labs foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or any later version.
+ */
+
+//! GitLab storage connector
+//!
+//! Stores each website in its own GitLab project.
+//! The repository uses the same layout as FsStorage:
+//! - website.json (main data file)
+//! - meta.json (metadata file)
+//! - assets/ (uploaded assets)
+//! - pages/ (individual page files)
+//!
+//! The website ID is the GitLab project ID.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::Method;
+use uuid::Uuid;
+
+use crate::connectors::gitlab::{encode, GitlabClient, GitlabOptions, PROJECT_TOPIC};
+use crate::connectors::traits::{to_connector_data, ConnectorInfo, StorageConnector};
+use crate::connectors::website_files;
+use crate::error::{ConnectorError, ConnectorResult};
+use crate::models::{
+    constants, AssetInfo, ConnectorFile, ConnectorOptions, ConnectorType, ConnectorUser,
+    WebsiteData, WebsiteId, WebsiteMeta, WebsiteMetaFileContent,
+};
+
+/// Icon for the GitLab connectors
+pub(crate) const GITLAB_ICON: &str = "/assets/gitlab.png";
+
+/// GitLab storage connector
+///
+/// Authenticates with OAuth and stores websites in GitLab projects
+/// tagged with the `silex` topic.
+pub struct GitlabStorage {
+    /// GitLab API client
+    client: GitlabClient,
+
+    /// Folder name for assets within each website
+    assets_folder: String,
+}
+
+impl GitlabStorage {
+    /// Create a new GitlabStorage connector
+    ///
+    /// # Arguments
+    /// * `options` - GitLab instance and OAuth application settings
+    /// * `assets_folder` - Name of the assets folder within each website
+    pub fn new(options: GitlabOptions, assets_folder: String) -> Self {
+        GitlabStorage {
+            client: GitlabClient::new(options),
+            assets_folder,
+        }
+    }
+
+    /// Get the path to an asset within the repository
+    fn asset_path(&self, file_name: &str) -> String {
+        format!(
+            "{}/{}",
+            self.assets_folder,
+            file_name.trim_start_matches('/')
+        )
+    }
+
+    /// Get the API path of a project
+    fn project_path(website_id: &str) -> String {
+        format!("projects/{}", encode(website_id))
+    }
+
+    /// Build a unique project path (URL slug) from a website name
+    fn new_project_path(name: &str) -> String {
+        let slug = website_files::get_page_slug(name);
+        let suffix = &Uuid::new_v4().simple().to_string()[..8];
+        if slug.is_empty() {
+            format!("silex-{}", suffix)
+        } else {
+            format!("{}-{}", slug, suffix)
+        }
+    }
+
+    /// Read meta.json from a project, if present
+    async fn read_meta_file(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+    ) -> ConnectorResult<Option<WebsiteMetaFileContent>> {
+        match self
+            .client
+            .read_file(session, website_id, constants::WEBSITE_META_DATA_FILE)
+            .await
+        {
+            Ok(content) => Ok(Some(serde_json::from_slice(&content)?)),
+            Err(ConnectorError::NotFound(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Get the name of the GitLab user making a change, recorded in meta.json
+    async fn author(&self, session: &serde_json::Value) -> ConnectorResult<Option<String>> {
+        let user = self.client.get(session, "user").await?;
+        let field = |name: &str| user.get(name).and_then(|v| v.as_str()).map(String::from);
+        Ok(field("name").or_else(|| field("username")))
+    }
+
+    /// Build meta.json recording a modification of a website by the session's user
+    ///
+    /// Writes `meta`, or the stored metadata when None, keeping the creation
+    /// author of the stored metadata. The times are left out, they are those
+    /// of the project. Returns None when there is no metadata to record it in.
+    async fn stamped_meta_file(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+        meta: Option<&WebsiteMetaFileContent>,
+    ) -> ConnectorResult<Option<(String, Vec<u8>)>> {
+        let stored = self.read_meta_file(session, website_id).await?;
+        let Some(meta) = meta.or(stored.as_ref()) else {
+            return Ok(None);
+        };
+        let author = self.author(session).await?;
+        let meta = WebsiteMetaFileContent {
+            created_by: match &stored {
+                Some(stored) => stored.created_by.clone(),
+                None => author.clone(),
+            },
+            updated_by: author,
+            ..meta.without_history()
+        };
+        Ok(Some((
+            constants::WEBSITE_META_DATA_FILE.to_string(),
+            website_files::serialize_json(&meta)?.into_bytes(),
+        )))
+    }
+}
+
+/// Convert a GitLab project to website metadata
+///
+/// The project name takes precedence over the name stored in meta.json.
+fn project_to_meta(
+    project: &serde_json::Value,
+    file_content: Option<WebsiteMetaFileContent>,
+) -> WebsiteMeta {
+    let website_id = match project.get("id") {
+        Some(serde_json::Value::Number(id)) => id.to_string(),
+        Some(serde_json::Value::String(id)) => id.clone(),
+        _ => String::new(),
+    };
+    let name = project
+        .get("name")
+        .and_then(|n| n.as_str())
+        .unwrap_or_default()
+        .to_string();
+    let date = |field: &str| {
+        project
+            .get(field)
+            .and_then(|d| d.as_str())
+            .and_then(|d| DateTime::parse_from_rfc3339(d).ok())
+            .map(|d| d.with_timezone(&Utc))
+    };
+
+    let file_content = file_content.unwrap_or_else(|| WebsiteMetaFileContent {
+        name: name.clone(),
+        image_url: project
+            .get("avatar_url")
+            .and_then(|a| a.as_str())
+            .map(String::from),
+        connector_user_settings: Default::default(),
+        is_template: false,
+        ..Default::default()
+    });
+
+    let mut meta = WebsiteMeta::from_file_content(
+        website_id,
+        WebsiteMetaFileContent {
+            name,
+            ..file_content
+        },
+        date("created_at"),
+        date("last_activity_at"),
+    );
+    meta.repo_url = project
+        .get("web_url")
+        .and_then(|u| u.as_str())
+        .map(String::from);
+
+    meta
+}
+
+impl ConnectorInfo for GitlabStorage {
+    fn connector_id(&self) -> &str {
+        "gitlab"
+    }
+
+    fn connector_type(&self) -> ConnectorType {
+        ConnectorType::Storage
+    }
+
+    fn display_name(&self) -> &str {
+        &self.client.options().display_name
+    }
+
+    fn icon(&self) -> &str {
+        GITLAB_ICON
+    }
+
+    fn color(&self) -> &str {
+        "#ffffff"
+    }
+
+    fn background(&self) -> &str {
+        "#fc6d26"
+    }
+}
+
+#[async_trait]
+impl StorageConnector for GitlabStorage {
+    // ==================
+    // Authentication
+    // ==================
+
+    async fn is_logged_in(&self, session: &serde_json::Value) -> ConnectorResult<bool> {
+        Ok(self.client.token(session).is_some())
+    }
+
+    async fn get_oauth_url(&self, _session: &serde_json::Value) -> ConnectorResult<Option<String>> {
+        Ok(Some(
+            self.client
+                .oauth_url(self.connector_id(), self.connector_type()),
+        ))
+    }
+
+    async fn set_token(
+        &self,
+        session: &mut serde_json::Value,
+        token: &serde_json::Value,
+    ) -> ConnectorResult<()> {
+        self.client
+            .set_token(session, token, self.connector_id(), self.connector_type())
+            .await
+    }
+
+    async fn logout(&self, session: &mut serde_json::Value) -> ConnectorResult<()> {
+        self.client.logout(session);
+        Ok(())
+    }
+
+    async fn get_user(&self, session: &serde_json::Value) -> ConnectorResult<ConnectorUser> {
+        let user = self.client.get(session, "user").await?;
+        let field = |name: &str| user.get(name).and_then(|v| v.as_str()).map(String::from);
+
+        Ok(ConnectorUser {
+            name: field("name")
+                .or_else(|| field("username"))
+                .unwrap_or_else(|| "unknown".to_string()),
+            email: field("email"),
+            picture: field("avatar_url"),
+            storage: to_connector_data(session, self).await?,
+        })
+    }
+
+    fn get_options(&self, _form_data: &serde_json::Value) -> ConnectorOptions {
+        // No options for GitLab storage
+        ConnectorOptions::default()
+    }
+
+    // ==================
+    // Website CRUD
+    // ==================
+
+    async fn list_websites(
+        &self,
+        session: &serde_json::Value,
+    ) -> ConnectorResult<Vec<WebsiteMeta>> {
+        let projects = self
+            .client
+            .get_all(
+                session,
+                &format!(
+                    "projects?membership=true&simple=true&topic={}",
+                    PROJECT_TOPIC
+                ),
+            )
+            .await?;
+
+        // meta.json is not read, it would take an API call per project: the list
+        // has no authorship, get_website_meta returns it
+        Ok(projects.iter().map(|p| project_to_meta(p, None)).collect())
+    }
+
+    async fn read_website(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+    ) -> ConnectorResult<WebsiteData> {
+        // Read the main website data file
+        let content = self
+            .client
+            .read_file(session, website_id, constants::WEBSITE_DATA_FILE)
+            .await
+            .map_err(|e| match e {
+                ConnectorError::NotFound(_) => {
+                    ConnectorError::NotFound(format!("Website '{}' not found", website_id))
+                }
+                e => e,
+            })?;
+        let content = String::from_utf8_lossy(&content);
+
+        // Merge with page files
+        website_files::merge_website_data(&content, |path| async move {
+            let page = self.client.read_file(session, website_id, &path).await?;
+            Ok(String::from_utf8_lossy(&page).into_owned())
+        })
+        .await
+    }
+
+    async fn create_website(
+        &self,
+        session: &serde_json::Value,
+        meta: &WebsiteMetaFileContent,
+    ) -> ConnectorResult<WebsiteId> {
+        let body = serde_json::json!({
+            "name": meta.name,
+            "path": Self::new_project_path(&meta.name),
+            "topics": [PROJECT_TOPIC],
+            "visibility": "private",
+        });
+        let project = self
+            .client
+            .call(session, Method::POST, "projects", Some(&body))
+            .await?;
+        let website_id = project_to_meta(&project, None).website_id;
+        let author = self.author(session).await?;
+        let meta = WebsiteMetaFileContent {
+            created_by: author.clone(),
+            updated_by: author,
+            ..meta.without_history()
+        };
+
+        // Initial commit with the metadata and the default website data
+        let mut files: Vec<(String, Vec<u8>)> =
+            website_files::split_website_data(&WebsiteData::default(), &Default::default())?
+                .into_iter()
+                .map(|(path, content)| (path, content.into_bytes()))
+                .collect();
+        files.push((
+            constants::WEBSITE_META_DATA_FILE.to_string(),
+            website_files::serialize_json(&meta)?.into_bytes(),
+        ));
+        self.client
+            .commit(session, &website_id, "Create website", files, Vec::new())
+            .await?;
+
+        Ok(website_id)
+    }
+
+    async fn update_website(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+        data: &WebsiteData,
+    ) -> ConnectorResult<()> {
+        // Split the website data into separate files, keeping the page files of the index
+        let index = match self
+            .client
+            .read_file(session, website_id, constants::WEBSITE_DATA_FILE)
+            .await
+        {
+            Ok(content) => serde_json::from_slice(&content)
+                .map(|parsed| website_files::page_index(&parsed))
+                .unwrap_or_default(),
+            Err(ConnectorError::NotFound(_)) => Default::default(),
+            Err(e) => return Err(e),
+        };
+        let files = website_files::split_website_data(data, &index)?;
+        let pages_folder = format!("{}/", website_files::get_pages_folder(data));
+
+        // Delete pages that are no longer in the website data
+        let deleted = self
+            .client
+            .list_files(session, website_id)
+            .await?
+            .into_iter()
+            .filter(|path| path.starts_with(&pages_folder) && path.ends_with(".json"))
+            .filter(|path| !files.iter().any(|(p, _)| p == path))
+            .collect();
+
+        let mut files: Vec<(String, Vec<u8>)> = files
+            .into_iter()
+            .map(|(path, content)| (path, content.into_bytes()))
+            .collect();
+        files.extend(self.stamped_meta_file(session, website_id, None).await?);
+
+        self.client
+            .commit(session, website_id, "Update website", files, deleted)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn delete_website(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+    ) -> ConnectorResult<()> {
+        self.client
+            .call(
+                session,
+                Method::DELETE,
+                &Self::project_path(website_id),
+                None,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn duplicate_website(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+    ) -> ConnectorResult<WebsiteId> {
+        let meta = self.get_website_meta(session, website_id).await?;
+        let name = format!("{} copy", meta.name);
+
+        // Fork the project, GitLab copies the repository in the background
+        let body = serde_json::json!({
+            "name": name,
+            "path": Self::new_project_path(&name),
+        });
+        let project = self
+            .client
+            .call(
+                session,
+                Method::POST,
+                &format!("{}/fork", Self::project_path(website_id)),
+                Some(&body),
+            )
+            .await?;
+
+        Ok(project_to_meta(&project, None).website_id)
+    }
+
+    // ==================
+    // Assets
+    // ==================
+
+    async fn write_assets(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+        files: Vec<ConnectorFile>,
+    ) -> ConnectorResult<Vec<String>> {
+        let mut written_paths = Vec::new();
+        let mut commit_files = Vec::new();
+
+        for file in files {
+            // Normalize the path (remove leading slash if present)
+            let relative_path = file.path.trim_start_matches('/').to_string();
+            commit_files.push((self.asset_path(&relative_path), file.content));
+
+            // Return the path as stored (with leading slash)
+            written_paths.push(format!("/{}", relative_path));
+        }
+        commit_files.extend(self.stamped_meta_file(session, website_id, None).await?);
+
+        self.client
+            .commit(
+                session,
+                website_id,
+                "Upload assets",
+                commit_files,
+                Vec::new(),
+            )
+            .await?;
+
+        Ok(written_paths)
+    }
+
+    async fn read_asset(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+        file_name: &str,
+    ) -> ConnectorResult<Vec<u8>> {
+        self.client
+            .read_file(session, website_id, &self.asset_path(file_name))
+            .await
+            .map_err(|e| match e {
+                ConnectorError::NotFound(_) => {
+                    ConnectorError::NotFound(format!("Asset '{}' not found", file_name))
+                }
+                e => e,
+            })
+    }
+
+    async fn list_assets(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+    ) -> ConnectorResult<Vec<AssetInfo>> {
+        let prefix = format!("{}/", self.assets_folder);
+        let mut paths: Vec<String> = self
+            .client
+            .list_files(session, website_id)
+            .await?
+            .into_iter()
+            .filter(|path| path.starts_with(&prefix))
+            .collect();
+        paths.sort();
+
+        let sizes = self.client.file_sizes(session, website_id, &paths).await?;
+        let mut assets = Vec::with_capacity(paths.len());
+        for path in paths {
+            let size = sizes.get(&path).copied().unwrap_or_default();
+            let relative_path = &path[prefix.len()..];
+            assets.push(AssetInfo {
+                content_type: mime_guess::from_path(relative_path)
+                    .first_or_octet_stream()
+                    .to_string(),
+                path: format!("/{}", relative_path),
+                size,
+                // The repository tree has no modification times, so
+                // these assets are never collected as unused
+                modified_at: None,
+            });
+        }
+
+        Ok(assets)
+    }
+
+    async fn delete_asset(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+        file_name: &str,
+    ) -> ConnectorResult<()> {
+        let path = self.asset_path(file_name);
+        if !self
+            .client
+            .list_files(session, website_id)
+            .await?
+            .contains(&path)
+        {
+            return Err(ConnectorError::NotFound(format!(
+                "Asset '{}' not found",
+                file_name
+            )));
+        }
+
+        self.client
+            .commit(session, website_id, "Delete asset", Vec::new(), vec![path])
+            .await?;
+        Ok(())
+    }
+
+    async fn move_asset(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+        from: &str,
+        to: &str,
+    ) -> ConnectorResult<()> {
+        let from_path = self.asset_path(from);
+        let to_path = self.asset_path(to);
+        let files = self.client.list_files(session, website_id).await?;
+        if !files.contains(&from_path) {
+            return Err(ConnectorError::NotFound(format!(
+                "Asset '{}' not found",
+                from
+            )));
+        }
+        if files.contains(&to_path) {
+            return Err(ConnectorError::InvalidInput(format!(
+                "Asset '{}' already exists",
+                to
+            )));
+        }
+
+        self.client
+            .move_file(session, website_id, "Move asset", &from_path, &to_path)
+            .await
+    }
+
+    // ==================
+    // Metadata
+    // ==================
+
+    async fn get_website_meta(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+    ) -> ConnectorResult<WebsiteMeta> {
+        let project = self
+            .client
+            .get(session, &Self::project_path(website_id))
+            .await
+            .map_err(|e| match e {
+                ConnectorError::NotFound(_) => {
+                    ConnectorError::NotFound(format!("Website '{}' not found", website_id))
+                }
+                e => e,
+            })?;
+        let file_content = self.read_meta_file(session, website_id).await?;
+
+        Ok(project_to_meta(&project, file_content))
+    }
+
+    async fn set_website_meta(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+        meta: &WebsiteMetaFileContent,
+    ) -> ConnectorResult<()> {
+        // Keep the project name in sync, it is what GitLab displays
+        let body = serde_json::json!({ "name": meta.name });
+        self.client
+            .call(
+                session,
+                Method::PUT,
+                &Self::project_path(website_id),
+                Some(&body),
+            )
+            .await?;
+
+        // The creation and modification times are those of the project
+        let files = self
+            .stamped_meta_file(session, website_id, Some(meta))
+            .await?
+            .into_iter()
+            .collect();
+        self.client
+            .commit(
+                session,
+                website_id,
+                "Update website metadata",
+                files,
+                Vec::new(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::Engine;
+    use serde_json::json;
+    use wiremock::matchers::{method, path, query_param};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    /// A storage on the mock GitLab instance
+    fn storage(server: &MockServer) -> GitlabStorage {
+        GitlabStorage::new(
+            GitlabOptions {
+                domain: server.uri(),
+                client_id: "app".to_string(),
+                client_secret: "secret".to_string(),
+                server_url: "http://silex.test".to_string(),
+                display_name: "GitLab".to_string(),
+            },
+            "assets".to_string(),
+        )
+    }
+
+    /// A session logged in to GitLab
+    fn session() -> serde_json::Value {
+        json!({ "gitlab": { "access_token": "token" } })
+    }
+
+    /// Answer a GET with JSON
+    async fn mock_json(server: &MockServer, url_path: &str, body: serde_json::Value) {
+        Mock::given(method("GET"))
+            .and(path(url_path))
+            .respond_with(ResponseTemplate::new(200).set_body_json(body))
+            .mount(server)
+            .await;
+    }
+
+    /// Answer a raw file of project 1
+    async fn mock_file(server: &MockServer, file_path: &str, content: ResponseTemplate) {
+        Mock::given(method("GET"))
+            .and(path(format!(
+                "/api/v4/projects/1/repository/files/{}/raw",
+                encode(file_path)
+            )))
+            .respond_with(content)
+            .mount(server)
+            .await;
+    }
+
+    /// Answer the repository tree of project 1
+    async fn mock_tree(server: &MockServer, paths: &[&str]) {
+        let entries: Vec<_> = paths
+            .iter()
+            .map(|path| json!({ "path": path, "type": "blob" }))
+            .collect();
+        mock_json(server, "/api/v4/projects/1/repository/tree", json!(entries)).await;
+    }
+
+    /// Accept commits to project 1
+    async fn mock_commits(server: &MockServer) {
+        Mock::given(method("POST"))
+            .and(path("/api/v4/projects/1/repository/commits"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(json!({ "id": "abc" })))
+            .expect(1)
+            .mount(server)
+            .await;
+    }
+
+    /// The actions of the commit received by the mock server, by file path
+    async fn committed_actions(
+        server: &MockServer,
+    ) -> std::collections::HashMap<String, serde_json::Value> {
+        let requests = server.received_requests().await.unwrap();
+        let commit = requests
+            .iter()
+            .find(|request| request.url.path().ends_with("/repository/commits"))
+            .expect("no commit");
+        let body: serde_json::Value = serde_json::from_slice(&commit.body).unwrap();
+        body["actions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|action| {
+                (
+                    action["file_path"].as_str().unwrap().to_string(),
+                    action.clone(),
+                )
+            })
+            .collect()
+    }
+
+    /// Decode the content of a commit action
+    fn action_content(action: &serde_json::Value) -> serde_json::Value {
+        let content = base64::engine::general_purpose::STANDARD
+            .decode(action["content"].as_str().unwrap())
+            .unwrap();
+        serde_json::from_slice(&content).unwrap()
+    }
+
+    #[tokio::test]
+    async fn list_websites_of_silex_projects() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v4/projects"))
+            .and(query_param("topic", PROJECT_TOPIC))
+            .and(query_param("membership", "true"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+                "id": 1,
+                "name": "My site",
+                "web_url": "https://gitlab.test/alice/my-site",
+                "created_at": "2024-01-02T03:04:05Z",
+                "last_activity_at": "2024-02-03T04:05:06Z",
+            }])))
+            .mount(&server)
+            .await;
+
+        let websites = storage(&server).list_websites(&session()).await.unwrap();
+
+        assert_eq!(websites.len(), 1);
+        assert_eq!(websites[0].website_id, "1");
+        assert_eq!(websites[0].name, "My site");
+        assert_eq!(
+            websites[0].repo_url.as_deref(),
+            Some("https://gitlab.test/alice/my-site")
+        );
+        assert_eq!(
+            websites[0].created_at.map(|d| d.to_rfc3339()),
+            Some("2024-01-02T03:04:05+00:00".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn read_website_merges_page_files() {
+        let server = MockServer::start().await;
+        mock_file(
+            &server,
+            "website.json",
+            ResponseTemplate::new(200).set_body_json(json!({
+                "pages": [{ "id": "home", "name": "Home", "isFile": true, "file": "home-home.json" }],
+                "pagesFolder": "pages",
+            })),
+        )
+        .await;
+        mock_file(
+            &server,
+            "pages/home-home.json",
+            ResponseTemplate::new(200)
+                .set_body_json(json!({ "id": "home", "name": "Home", "frames": [] })),
+        )
+        .await;
+
+        let data = storage(&server)
+            .read_website(&session(), &"1".to_string())
+            .await
+            .unwrap();
+
+        assert_eq!(data.pages.len(), 1);
+        assert_eq!(data.pages[0]["frames"], json!([]));
+    }
+
+    #[tokio::test]
+    async fn read_missing_website() {
+        let server = MockServer::start().await;
+        mock_file(&server, "website.json", ResponseTemplate::new(404)).await;
+
+        let result = storage(&server)
+            .read_website(&session(), &"1".to_string())
+            .await;
+
+        assert!(
+            matches!(result, Err(ConnectorError::NotFound(message)) if message.contains("'1'"))
+        );
+    }
+
+    #[tokio::test]
+    async fn update_website_commits_pages_and_authorship() {
+        let server = MockServer::start().await;
+        mock_file(
+            &server,
+            "website.json",
+            ResponseTemplate::new(200).set_body_json(json!({
+                "pages": [{ "id": "old", "name": "Old", "isFile": true, "file": "old-old.json" }],
+            })),
+        )
+        .await;
+        mock_file(
+            &server,
+            "meta.json",
+            ResponseTemplate::new(200)
+                .set_body_json(json!({ "name": "My site", "createdBy": "Bob" })),
+        )
+        .await;
+        mock_tree(
+            &server,
+            &["website.json", "meta.json", "pages/old-old.json"],
+        )
+        .await;
+        mock_json(&server, "/api/v4/user", json!({ "name": "Alice" })).await;
+        mock_commits(&server).await;
+
+        let data = WebsiteData {
+            pages: vec![json!({ "id": "home", "name": "Home" })],
+            ..Default::default()
+        };
+        storage(&server)
+            .update_website(&session(), &"1".to_string(), &data)
+            .await
+            .unwrap();
+
+        let actions = committed_actions(&server).await;
+        assert_eq!(actions["pages/home-home.json"]["action"], "create");
+        assert_eq!(actions["pages/old-old.json"]["action"], "delete");
+        assert_eq!(actions["website.json"]["action"], "update");
+        let meta = action_content(&actions["meta.json"]);
+        assert_eq!(meta["createdBy"], "Bob");
+        assert_eq!(meta["updatedBy"], "Alice");
+    }
+
+    #[tokio::test]
+    async fn get_website_meta_reads_meta_file() {
+        let server = MockServer::start().await;
+        mock_json(
+            &server,
+            "/api/v4/projects/1",
+            json!({ "id": 1, "name": "Renamed", "created_at": "2024-01-02T03:04:05Z" }),
+        )
+        .await;
+        mock_file(
+            &server,
+            "meta.json",
+            ResponseTemplate::new(200).set_body_json(json!({
+                "name": "Old name",
+                "imageUrl": "/assets/cover.png",
+                "createdBy": "Bob",
+            })),
+        )
+        .await;
+
+        let meta = storage(&server)
+            .get_website_meta(&session(), &"1".to_string())
+            .await
+            .unwrap();
+
+        // The project name is the one GitLab displays
+        assert_eq!(meta.name, "Renamed");
+        assert_eq!(meta.image_url.as_deref(), Some("/assets/cover.png"));
+        assert_eq!(meta.created_by.as_deref(), Some("Bob"));
+    }
+
+    #[tokio::test]
+    async fn get_meta_of_missing_website() {
+        let server = MockServer::start().await;
+        Mock::given(path("/api/v4/projects/1"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+
+        let result = storage(&server)
+            .get_website_meta(&session(), &"1".to_string())
+            .await;
+
+        assert!(matches!(result, Err(ConnectorError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn set_website_meta_renames_the_project() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path("/api/v4/projects/1"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "id": 1 })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        mock_file(&server, "meta.json", ResponseTemplate::new(404)).await;
+        mock_tree(&server, &["website.json"]).await;
+        mock_json(&server, "/api/v4/user", json!({ "username": "alice" })).await;
+        mock_commits(&server).await;
+
+        let meta = WebsiteMetaFileContent {
+            name: "New name".to_string(),
+            ..Default::default()
+        };
+        storage(&server)
+            .set_website_meta(&session(), &"1".to_string(), &meta)
+            .await
+            .unwrap();
+
+        let actions = committed_actions(&server).await;
+        assert_eq!(actions["meta.json"]["action"], "create");
+        let meta = action_content(&actions["meta.json"]);
+        assert_eq!(meta["name"], "New name");
+        assert_eq!(meta["createdBy"], "alice");
+        assert_eq!(meta["updatedBy"], "alice");
+    }
+
+    #[tokio::test]
+    async fn write_assets_in_assets_folder() {
+        let server = MockServer::start().await;
+        mock_file(&server, "meta.json", ResponseTemplate::new(404)).await;
+        mock_tree(&server, &["website.json", "assets/a.png"]).await;
+        mock_commits(&server).await;
+
+        let paths = storage(&server)
+            .write_assets(
+                &session(),
+                &"1".to_string(),
+                vec![
+                    ConnectorFile {
+                        path: "/a.png".to_string(),
+                        content: vec![1, 2, 3],
+                    },
+                    ConnectorFile {
+                        path: "img/b.png".to_string(),
+                        content: vec![4],
+                    },
+                ],
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(paths, ["/a.png", "/img/b.png"]);
+        let actions = committed_actions(&server).await;
+        assert_eq!(actions["assets/a.png"]["action"], "update");
+        assert_eq!(actions["assets/img/b.png"]["action"], "create");
+        assert_eq!(actions["assets/img/b.png"]["content"], "BA==");
+    }
+
+    #[tokio::test]
+    async fn list_assets_with_sizes() {
+        let server = MockServer::start().await;
+        mock_tree(
+            &server,
+            &["website.json", "assets/b.css", "assets/img/a.png"],
+        )
+        .await;
+        mock_json(
+            &server,
+            "/api/v4/projects/1",
+            json!({ "id": 1, "path_with_namespace": "alice/site" }),
+        )
+        .await;
+        Mock::given(method("POST"))
+            .and(path("/api/graphql"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "data": { "project": { "repository": { "blobs": { "nodes": [
+                    { "path": "assets/img/a.png", "size": "1234" },
+                    { "path": "assets/b.css", "size": "56" },
+                ] } } } }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let assets = storage(&server)
+            .list_assets(&session(), &"1".to_string())
+            .await
+            .unwrap();
+
+        let listed: Vec<_> = assets
+            .iter()
+            .map(|a| (a.path.as_str(), a.size, a.content_type.as_str()))
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                ("/b.css", 56, "text/css"),
+                ("/img/a.png", 1234, "image/png")
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn move_asset_refuses_to_overwrite() {
+        let server = MockServer::start().await;
+        mock_tree(&server, &["assets/a.png", "assets/b.png"]).await;
+
+        let storage = storage(&server);
+        let result = storage
+            .move_asset(&session(), &"1".to_string(), "/a.png", "/b.png")
+            .await;
+        assert!(matches!(result, Err(ConnectorError::InvalidInput(_))));
+
+        let result = storage
+            .move_asset(&session(), &"1".to_string(), "/c.png", "/d.png")
+            .await;
+        assert!(matches!(result, Err(ConnectorError::NotFound(_))));
+    }
+}
@@ -14,13 +14,23 @@
 
 mod fs_hosting;
 mod fs_storage;
+mod gitlab;
+mod gitlab_hosting;
+mod gitlab_storage;
+mod plugin;
 mod registry;
 mod traits;
+pub(crate) mod website_files;
 
 pub use fs_hosting::FsHosting;
+pub(crate) use fs_storage::check_website_id;
 pub use fs_storage::FsStorage;
+pub use gitlab::GitlabOptions;
+pub use gitlab_hosting::GitlabHosting;
+pub use gitlab_storage::GitlabStorage;
+pub use plugin::{PluginConnector, PluginHosting, PluginInfo, PluginStorage};
 pub use registry::ConnectorRegistry;
 pub use traits::{
-    hosting_to_connector_data, to_connector_data, HostingConnector,
-    StorageConnector,
+    clear_session_user, hosting_to_connector_data, session_user, set_session_user,
+    to_connector_data, HostingConnector, StorageConnector,
 };
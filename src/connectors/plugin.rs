@@ -0,0 +1,1144 @@
+/*
+ * Silex website builder, free/libre no-code tool for makers.
+ * Copyright (c) 2023 lexoyo and Silex Labs foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or any later version.
+ */
+
+//! Out-of-process connector plugins
+//!
+//! A plugin is an external executable which speaks JSON-RPC 2.0 on stdio,
+//! one JSON message per line. Its methods mirror the StorageConnector and
+//! HostingConnector traits, with camelCase names and named parameters:
+//!
+//! - `info` → `{ connectorId, type, displayName, icon, color, background, disableLogout }`
+//! - `isLoggedIn`, `getOauthUrl`, `getUser` → `{ session }`
+//! - `setToken` → `{ session, token }`, `logout` → `{ session }`, both return the new session
+//! - `listWebsites`, `readWebsite`, `createWebsite`, `updateWebsite`, `deleteWebsite`,
+//!   `duplicateWebsite`, `getWebsiteMeta`, `setWebsiteMeta` → `{ session, websiteId, data, meta }`
+//! - `writeAssets` → `{ session, websiteId, files: [{ path, content }] }`, `readAsset` →
+//!   `{ session, websiteId, fileName }` returning `{ content }` (contents are base64)
+//! - `listAssets` → `{ session, websiteId }` returning `[{ path, size, contentType, modifiedAt }]`,
+//!   `deleteAsset` → `{ session, websiteId, fileName }`, `moveAsset` → `{ session, websiteId, from, to }`
+//! - `publish` → `{ session, websiteId, files }` returning `{ message, logs }`, `getUrl`
+//!
+//! Errors use the JSON-RPC error object, with codes mapped to ConnectorError:
+//! -32001 not authenticated, -32004 not found, -32602 invalid input, -32601
+//! (method not found) not supported. The asset listing, deletion and move
+//! methods are optional: without them, websites can not be moved away from
+//! the plugin nor cleaned of their unused assets, and assets are moved by
+//! writing a copy and deleting the original.
+//!
+//! A plugin which does not answer a call in time is killed and the pending
+//! calls fail. A plugin which exited is restarted by the next call, after a
+//! delay of 1 second doubled by each restart without an answer, up to 1 minute.
+//! Calls fail during that delay. Publications run in the background, as jobs.
+
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use base64::Engine;
+use serde::Deserialize;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, Command};
+use tokio::sync::oneshot;
+
+use crate::connectors::traits::{
+    hosting_to_connector_data, move_asset_by_copy, to_connector_data, ConnectorInfo,
+    HostingConnector, StorageConnector,
+};
+use crate::error::{ConnectorError, ConnectorResult};
+use crate::models::{
+    AssetInfo, ConnectorFile, ConnectorOptions, ConnectorType, ConnectorUser, PublicationJobData,
+    WebsiteData, WebsiteId, WebsiteMeta, WebsiteMetaFileContent,
+};
+use crate::services::JobManager;
+
+/// JSON-RPC error code: the user is not authenticated
+const ERROR_NOT_AUTHENTICATED: i64 = -32001;
+
+/// JSON-RPC error code: the requested resource does not exist
+const ERROR_NOT_FOUND: i64 = -32004;
+
+/// JSON-RPC error code: the plugin does not implement the method
+const ERROR_METHOD_NOT_FOUND: i64 = -32601;
+
+/// JSON-RPC error code: invalid method parameters
+const ERROR_INVALID_PARAMS: i64 = -32602;
+
+/// Delay before restarting a plugin which exited
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+
+/// Number of times the restart delay is doubled when the plugin keeps exiting
+const MAX_RESTART_DOUBLINGS: u32 = 6;
+
+/// Maximum delay before restarting a plugin
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(60);
+
+/// Pending calls, waiting for their response
+type PendingCalls = Arc<Mutex<HashMap<u64, oneshot::Sender<ConnectorResult<serde_json::Value>>>>>;
+
+/// Connector description returned by the plugin's `info` method
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginInfo {
+    /// Unique identifier for this connector
+    pub connector_id: String,
+
+    /// Type of connector (STORAGE or HOSTING)
+    #[serde(rename = "type")]
+    pub connector_type: ConnectorType,
+
+    /// Human-readable name shown in UI
+    pub display_name: String,
+
+    /// URL or data URI for the connector icon
+    #[serde(default)]
+    pub icon: String,
+
+    /// Primary color for UI styling
+    #[serde(default)]
+    pub color: String,
+
+    /// Background color for UI styling
+    #[serde(default)]
+    pub background: String,
+
+    /// If true, hide the logout button for this connector
+    #[serde(default)]
+    pub disable_logout: bool,
+}
+
+/// A running plugin process
+struct PluginChild {
+    /// The child process, killed when the plugin is dropped or times out
+    child: Mutex<Child>,
+
+    /// Where requests are written
+    stdin: tokio::sync::Mutex<ChildStdin>,
+
+    /// Calls waiting for a response
+    pending: PendingCalls,
+
+    /// When the process exited or was killed, None while it runs
+    exited_at: Arc<Mutex<Option<Instant>>>,
+}
+
+impl PluginChild {
+    /// Start the plugin executable
+    ///
+    /// The command is split on whitespace: the first word is the executable,
+    /// the others are its arguments.
+    fn spawn(command: &str) -> ConnectorResult<Self> {
+        let mut words = command.split_whitespace();
+        let program = words
+            .next()
+            .ok_or_else(|| ConnectorError::InvalidInput("Empty plugin command".to_string()))?;
+
+        let mut child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let stdin = child.stdin.take().expect("plugin stdin is piped");
+        let stdout = child.stdout.take().expect("plugin stdout is piped");
+        let pending: PendingCalls = Arc::new(Mutex::new(HashMap::new()));
+        let exited_at = Arc::new(Mutex::new(None));
+
+        // Dispatch responses to the pending calls
+        let reader_pending = pending.clone();
+        let reader_exited_at = exited_at.clone();
+        let reader_command = command.to_string();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stdout).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let response: serde_json::Value = match serde_json::from_str(&line) {
+                    Ok(response) => response,
+                    Err(e) => {
+                        tracing::warn!("Plugin '{}' sent invalid JSON: {}", reader_command, e);
+                        continue;
+                    }
+                };
+                let Some(id) = response.get("id").and_then(|id| id.as_u64()) else {
+                    tracing::warn!("Plugin '{}' sent a message without ID", reader_command);
+                    continue;
+                };
+                let sender = reader_pending.lock().unwrap().remove(&id);
+                if let Some(sender) = sender {
+                    let _ = sender.send(parse_response(response));
+                }
+            }
+
+            // The plugin exited: fail all pending calls
+            tracing::error!("Plugin '{}' exited", reader_command);
+            reader_exited_at
+                .lock()
+                .unwrap()
+                .get_or_insert_with(Instant::now);
+            for (_, sender) in reader_pending.lock().unwrap().drain() {
+                let _ = sender.send(Err(ConnectorError::Remote(format!(
+                    "Plugin '{}' exited",
+                    reader_command
+                ))));
+            }
+        });
+
+        Ok(PluginChild {
+            child: Mutex::new(child),
+            stdin: tokio::sync::Mutex::new(stdin),
+            pending,
+            exited_at,
+        })
+    }
+
+    /// When the process exited, None while it runs
+    fn exited_at(&self) -> Option<Instant> {
+        *self.exited_at.lock().unwrap()
+    }
+
+    /// Kill the process
+    ///
+    /// The response reader then sees the end of the output and fails the pending calls.
+    fn kill(&self, command: &str) {
+        tracing::error!("Killing plugin '{}' which does not answer", command);
+        self.exited_at
+            .lock()
+            .unwrap()
+            .get_or_insert_with(Instant::now);
+        if let Err(e) = self.child.lock().unwrap().start_kill() {
+            tracing::warn!("Could not kill plugin '{}': {}", command, e);
+        }
+    }
+}
+
+/// A plugin process, restarted when it exits
+struct PluginProcess {
+    /// Command line, to restart the process and for error messages
+    command: String,
+
+    /// The current process, None when it could not be restarted
+    child: tokio::sync::Mutex<Option<Arc<PluginChild>>>,
+
+    /// When the last restart failed, the next one waits for the backoff delay
+    failed_at: Mutex<Option<Instant>>,
+
+    /// Number of restarts since the last answer of the plugin
+    restarts: AtomicU32,
+
+    /// Next request ID
+    next_id: AtomicU64,
+
+    /// Maximum duration of a call, None for no limit
+    timeout: Option<Duration>,
+
+    /// Delay before the first restart, doubled by each restart without an answer
+    restart_delay: Duration,
+}
+
+impl PluginProcess {
+    /// Start the plugin executable
+    fn spawn(command: &str, timeout: Option<Duration>) -> ConnectorResult<Self> {
+        let child = PluginChild::spawn(command)?;
+
+        Ok(PluginProcess {
+            command: command.to_string(),
+            child: tokio::sync::Mutex::new(Some(Arc::new(child))),
+            failed_at: Mutex::new(None),
+            restarts: AtomicU32::new(0),
+            next_id: AtomicU64::new(1),
+            timeout,
+            restart_delay: RESTART_DELAY,
+        })
+    }
+
+    /// Get the running process, restarting it if it exited
+    ///
+    /// The process is restarted on the next call after it exited, once the
+    /// backoff delay has passed. Calls fail until then.
+    async fn running(&self) -> ConnectorResult<Arc<PluginChild>> {
+        let mut current = self.child.lock().await;
+        let exited_at = match current.as_ref() {
+            Some(child) => match child.exited_at() {
+                None => return Ok(child.clone()),
+                Some(exited_at) => exited_at,
+            },
+            None => self.failed_at.lock().unwrap().unwrap_or_else(Instant::now),
+        };
+
+        let restarts = self.restarts.load(Ordering::Relaxed);
+        let delay = self
+            .restart_delay
+            .saturating_mul(1 << restarts.min(MAX_RESTART_DOUBLINGS))
+            .min(MAX_RESTART_DELAY);
+        let elapsed = exited_at.elapsed();
+        if elapsed < delay {
+            return Err(ConnectorError::Remote(format!(
+                "Plugin '{}' stopped, it will be restarted in {} ms",
+                self.command,
+                (delay - elapsed).as_millis()
+            )));
+        }
+
+        tracing::warn!("Restarting plugin '{}'", self.command);
+        self.restarts.fetch_add(1, Ordering::Relaxed);
+        *current = None;
+        match PluginChild::spawn(&self.command) {
+            Ok(child) => {
+                let child = Arc::new(child);
+                *current = Some(child.clone());
+                Ok(child)
+            }
+            Err(e) => {
+                *self.failed_at.lock().unwrap() = Some(Instant::now());
+                Err(ConnectorError::Remote(format!(
+                    "Could not restart plugin '{}': {}",
+                    self.command, e
+                )))
+            }
+        }
+    }
+
+    /// Call a method and wait for its result
+    async fn call(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> ConnectorResult<serde_json::Value> {
+        let child = self.running().await?;
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = oneshot::channel();
+        child.pending.lock().unwrap().insert(id, sender);
+
+        let request = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        });
+        let mut line = serde_json::to_vec(&request)?;
+        line.push(b'\n');
+
+        let written = {
+            let mut stdin = child.stdin.lock().await;
+            match stdin.write_all(&line).await {
+                Ok(()) => stdin.flush().await,
+                Err(e) => Err(e),
+            }
+        };
+        if let Err(e) = written {
+            child.pending.lock().unwrap().remove(&id);
+            return Err(ConnectorError::Remote(format!(
+                "Could not call plugin '{}': {}",
+                self.command, e
+            )));
+        }
+
+        let response = match self.timeout {
+            Some(timeout) => match tokio::time::timeout(timeout, receiver).await {
+                Ok(response) => response,
+                Err(_) => {
+                    child.pending.lock().unwrap().remove(&id);
+                    child.kill(&self.command);
+                    return Err(ConnectorError::Remote(format!(
+                        "Plugin '{}' did not answer '{}' within {} seconds",
+                        self.command,
+                        method,
+                        timeout.as_secs()
+                    )));
+                }
+            },
+            None => receiver.await,
+        };
+        let response = response
+            .map_err(|_| ConnectorError::Remote(format!("Plugin '{}' exited", self.command)))?;
+
+        // The plugin answered, error responses included: it works again
+        if child.exited_at().is_none() {
+            self.restarts.store(0, Ordering::Relaxed);
+        }
+        response
+    }
+
+    /// Call a method and deserialize its result
+    async fn call_as<T: serde::de::DeserializeOwned>(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> ConnectorResult<T> {
+        Ok(serde_json::from_value(self.call(method, params).await?)?)
+    }
+}
+
+/// Convert a JSON-RPC response to a result
+fn parse_response(mut response: serde_json::Value) -> ConnectorResult<serde_json::Value> {
+    let Some(error) = response.get("error") else {
+        return Ok(response
+            .get_mut("result")
+            .map(serde_json::Value::take)
+            .unwrap_or_default());
+    };
+
+    let code = error
+        .get("code")
+        .and_then(|c| c.as_i64())
+        .unwrap_or_default();
+    let message = error
+        .get("message")
+        .and_then(|m| m.as_str())
+        .unwrap_or("Unknown plugin error")
+        .to_string();
+
+    Err(match code {
+        ERROR_NOT_AUTHENTICATED => ConnectorError::NotAuthenticated,
+        ERROR_NOT_FOUND => ConnectorError::NotFound(message),
+        ERROR_INVALID_PARAMS => ConnectorError::InvalidInput(message),
+        ERROR_METHOD_NOT_FOUND => ConnectorError::NotSupported(message),
+        _ => ConnectorError::Remote(message),
+    })
+}
+
+/// Encode files for the plugin (content as base64)
+fn encode_files(files: &[ConnectorFile]) -> serde_json::Value {
+    files
+        .iter()
+        .map(|file| {
+            serde_json::json!({
+                "path": file.path,
+                "content": base64::engine::general_purpose::STANDARD.encode(&file.content),
+            })
+        })
+        .collect()
+}
+
+/// User data returned by the plugin's `getUser` method
+#[derive(Debug, Deserialize)]
+struct PluginUser {
+    name: String,
+    #[serde(default)]
+    email: Option<String>,
+    #[serde(default)]
+    picture: Option<String>,
+}
+
+/// Result of the plugin's `publish` method
+#[derive(Debug, Default, Deserialize)]
+struct PluginPublication {
+    #[serde(default)]
+    message: Option<String>,
+    #[serde(default)]
+    logs: Vec<String>,
+}
+
+/// Connector plugin, started from a command line
+///
+/// Depending on the type declared by the plugin, it is registered
+/// as a storage or a hosting connector.
+pub enum PluginConnector {
+    Storage(PluginStorage),
+    Hosting(PluginHosting),
+}
+
+impl PluginConnector {
+    /// Start a plugin and ask for its connector description
+    ///
+    /// # Arguments
+    /// * `command` - Command line of the plugin executable
+    /// * `timeout` - Maximum duration of a call before the plugin is killed, None for no limit
+    pub async fn spawn(command: &str, timeout: Option<Duration>) -> ConnectorResult<Self> {
+        let process = Arc::new(PluginProcess::spawn(command, timeout)?);
+        let info: PluginInfo = process.call_as("info", serde_json::json!({})).await?;
+
+        tracing::info!(
+            "Plugin '{}' provides {:?} connector '{}'",
+            command,
+            info.connector_type,
+            info.connector_id
+        );
+
+        Ok(match info.connector_type {
+            ConnectorType::Storage => PluginConnector::Storage(PluginStorage { process, info }),
+            ConnectorType::Hosting => PluginConnector::Hosting(PluginHosting { process, info }),
+        })
+    }
+}
+
+/// Implement ConnectorInfo from the plugin's description
+macro_rules! plugin_connector_info {
+    ($connector:ty) => {
+        impl ConnectorInfo for $connector {
+            fn connector_id(&self) -> &str {
+                &self.info.connector_id
+            }
+
+            fn connector_type(&self) -> ConnectorType {
+                self.info.connector_type
+            }
+
+            fn display_name(&self) -> &str {
+                &self.info.display_name
+            }
+
+            fn icon(&self) -> &str {
+                &self.info.icon
+            }
+
+            fn color(&self) -> &str {
+                &self.info.color
+            }
+
+            fn background(&self) -> &str {
+                &self.info.background
+            }
+
+            fn disable_logout(&self) -> bool {
+                self.info.disable_logout
+            }
+        }
+    };
+}
+
+/// Storage connector implemented by a plugin
+pub struct PluginStorage {
+    process: Arc<PluginProcess>,
+    info: PluginInfo,
+}
+
+/// Hosting connector implemented by a plugin
+pub struct PluginHosting {
+    process: Arc<PluginProcess>,
+    info: PluginInfo,
+}
+
+plugin_connector_info!(PluginStorage);
+plugin_connector_info!(PluginHosting);
+
+#[async_trait]
+impl StorageConnector for PluginStorage {
+    // ==================
+    // Authentication
+    // ==================
+
+    async fn is_logged_in(&self, session: &serde_json::Value) -> ConnectorResult<bool> {
+        self.process
+            .call_as("isLoggedIn", serde_json::json!({ "session": session }))
+            .await
+    }
+
+    async fn get_oauth_url(&self, session: &serde_json::Value) -> ConnectorResult<Option<String>> {
+        self.process
+            .call_as("getOauthUrl", serde_json::json!({ "session": session }))
+            .await
+    }
+
+    async fn set_token(
+        &self,
+        session: &mut serde_json::Value,
+        token: &serde_json::Value,
+    ) -> ConnectorResult<()> {
+        *session = self
+            .process
+            .call(
+                "setToken",
+                serde_json::json!({ "session": session, "token": token }),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn logout(&self, session: &mut serde_json::Value) -> ConnectorResult<()> {
+        *session = self
+            .process
+            .call("logout", serde_json::json!({ "session": session }))
+            .await?;
+        Ok(())
+    }
+
+    async fn get_user(&self, session: &serde_json::Value) -> ConnectorResult<ConnectorUser> {
+        let user: PluginUser = self
+            .process
+            .call_as("getUser", serde_json::json!({ "session": session }))
+            .await?;
+
+        Ok(ConnectorUser {
+            name: user.name,
+            email: user.email,
+            picture: user.picture,
+            storage: to_connector_data(session, self).await?,
+        })
+    }
+
+    fn get_options(&self, form_data: &serde_json::Value) -> ConnectorOptions {
+        // Options are taken from the form data as is, plugins can not be called synchronously
+        serde_json::from_value(form_data.clone()).unwrap_or_default()
+    }
+
+    // ==================
+    // Website CRUD
+    // ==================
+
+    async fn list_websites(
+        &self,
+        session: &serde_json::Value,
+    ) -> ConnectorResult<Vec<WebsiteMeta>> {
+        self.process
+            .call_as("listWebsites", serde_json::json!({ "session": session }))
+            .await
+    }
+
+    async fn read_website(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+    ) -> ConnectorResult<WebsiteData> {
+        self.process
+            .call_as(
+                "readWebsite",
+                serde_json::json!({ "session": session, "websiteId": website_id }),
+            )
+            .await
+    }
+
+    async fn create_website(
+        &self,
+        session: &serde_json::Value,
+        meta: &WebsiteMetaFileContent,
+    ) -> ConnectorResult<WebsiteId> {
+        self.process
+            .call_as(
+                "createWebsite",
+                serde_json::json!({ "session": session, "meta": meta }),
+            )
+            .await
+    }
+
+    async fn update_website(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+        data: &WebsiteData,
+    ) -> ConnectorResult<()> {
+        self.process
+            .call(
+                "updateWebsite",
+                serde_json::json!({ "session": session, "websiteId": website_id, "data": data }),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_website(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+    ) -> ConnectorResult<()> {
+        self.process
+            .call(
+                "deleteWebsite",
+                serde_json::json!({ "session": session, "websiteId": website_id }),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn duplicate_website(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+    ) -> ConnectorResult<WebsiteId> {
+        self.process
+            .call_as(
+                "duplicateWebsite",
+                serde_json::json!({ "session": session, "websiteId": website_id }),
+            )
+            .await
+    }
+
+    // ==================
+    // Assets
+    // ==================
+
+    async fn write_assets(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+        files: Vec<ConnectorFile>,
+    ) -> ConnectorResult<Vec<String>> {
+        self.process
+            .call_as(
+                "writeAssets",
+                serde_json::json!({
+                    "session": session,
+                    "websiteId": website_id,
+                    "files": encode_files(&files),
+                }),
+            )
+            .await
+    }
+
+    async fn read_asset(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+        file_name: &str,
+    ) -> ConnectorResult<Vec<u8>> {
+        let result = self
+            .process
+            .call(
+                "readAsset",
+                serde_json::json!({
+                    "session": session,
+                    "websiteId": website_id,
+                    "fileName": file_name,
+                }),
+            )
+            .await?;
+
+        let content = result
+            .get("content")
+            .and_then(|c| c.as_str())
+            .ok_or_else(|| {
+                ConnectorError::Remote("Plugin returned no asset content".to_string())
+            })?;
+
+        base64::engine::general_purpose::STANDARD
+            .decode(content)
+            .map_err(|e| ConnectorError::Remote(format!("Invalid asset content: {}", e)))
+    }
+
+    async fn list_assets(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+    ) -> ConnectorResult<Vec<AssetInfo>> {
+        self.process
+            .call_as(
+                "listAssets",
+                serde_json::json!({ "session": session, "websiteId": website_id }),
+            )
+            .await
+    }
+
+    async fn delete_asset(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+        file_name: &str,
+    ) -> ConnectorResult<()> {
+        self.process
+            .call(
+                "deleteAsset",
+                serde_json::json!({
+                    "session": session,
+                    "websiteId": website_id,
+                    "fileName": file_name,
+                }),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn move_asset(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+        from: &str,
+        to: &str,
+    ) -> ConnectorResult<()> {
+        let moved = self
+            .process
+            .call(
+                "moveAsset",
+                serde_json::json!({
+                    "session": session,
+                    "websiteId": website_id,
+                    "from": from,
+                    "to": to,
+                }),
+            )
+            .await;
+        match moved {
+            // Plugins without moveAsset get the default of the trait
+            Err(ConnectorError::NotSupported(_)) => {
+                move_asset_by_copy(self, session, website_id, from, to).await
+            }
+            moved => moved.map(|_| ()),
+        }
+    }
+
+    // ==================
+    // Metadata
+    // ==================
+
+    async fn get_website_meta(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+    ) -> ConnectorResult<WebsiteMeta> {
+        self.process
+            .call_as(
+                "getWebsiteMeta",
+                serde_json::json!({ "session": session, "websiteId": website_id }),
+            )
+            .await
+    }
+
+    async fn set_website_meta(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+        meta: &WebsiteMetaFileContent,
+    ) -> ConnectorResult<()> {
+        self.process
+            .call(
+                "setWebsiteMeta",
+                serde_json::json!({ "session": session, "websiteId": website_id, "meta": meta }),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl HostingConnector for PluginHosting {
+    // ==================
+    // Authentication
+    // ==================
+
+    async fn is_logged_in(&self, session: &serde_json::Value) -> ConnectorResult<bool> {
+        self.process
+            .call_as("isLoggedIn", serde_json::json!({ "session": session }))
+            .await
+    }
+
+    async fn get_oauth_url(&self, session: &serde_json::Value) -> ConnectorResult<Option<String>> {
+        self.process
+            .call_as("getOauthUrl", serde_json::json!({ "session": session }))
+            .await
+    }
+
+    async fn set_token(
+        &self,
+        session: &mut serde_json::Value,
+        token: &serde_json::Value,
+    ) -> ConnectorResult<()> {
+        *session = self
+            .process
+            .call(
+                "setToken",
+                serde_json::json!({ "session": session, "token": token }),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn logout(&self, session: &mut serde_json::Value) -> ConnectorResult<()> {
+        *session = self
+            .process
+            .call("logout", serde_json::json!({ "session": session }))
+            .await?;
+        Ok(())
+    }
+
+    async fn get_user(&self, session: &serde_json::Value) -> ConnectorResult<ConnectorUser> {
+        let user: PluginUser = self
+            .process
+            .call_as("getUser", serde_json::json!({ "session": session }))
+            .await?;
+
+        Ok(ConnectorUser {
+            name: user.name,
+            email: user.email,
+            picture: user.picture,
+            storage: hosting_to_connector_data(session, self).await?,
+        })
+    }
+
+    fn get_options(&self, form_data: &serde_json::Value) -> ConnectorOptions {
+        // Options are taken from the form data as is, plugins can not be called synchronously
+        serde_json::from_value(form_data.clone()).unwrap_or_default()
+    }
+
+    // ==================
+    // Publication
+    // ==================
+
+    async fn publish(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+        files: Vec<ConnectorFile>,
+        job_manager: &JobManager,
+    ) -> ConnectorResult<PublicationJobData> {
+        let mut job = job_manager.start_job(format!("Publishing to {}", self.display_name()));
+        job.log(format!("Publishing {} files", files.len()));
+        job_manager.update_job(&job);
+
+        // The plugin may take long, the job is followed with the publication status route
+        let params = serde_json::json!({
+            "session": session,
+            "websiteId": website_id,
+            "files": encode_files(&files),
+        });
+        let file_count = files.len();
+        let process = self.process.clone();
+        let background_job = job.clone();
+        let job_manager = job_manager.clone();
+        tokio::spawn(async move {
+            let mut job = background_job;
+            match process
+                .call_as::<PluginPublication>("publish", params)
+                .await
+            {
+                Ok(publication) => {
+                    for log in publication.logs {
+                        job.log(log);
+                    }
+                    job.success(publication.message.unwrap_or_else(|| {
+                        format!("<p>Published {} files successfully.</p>", file_count)
+                    }));
+                    job_manager.update_job(&job);
+                }
+                Err(e) => {
+                    job.fail(format!("Publication failed: {}", e));
+                    job_manager.fail_job(&job.base.job_id, &e.to_string());
+                }
+            }
+        });
+
+        Ok(job)
+    }
+
+    async fn get_url(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+    ) -> ConnectorResult<String> {
+        self.process
+            .call_as(
+                "getUrl",
+                serde_json::json!({ "session": session, "websiteId": website_id }),
+            )
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::JobStatus;
+    use serde_json::json;
+
+    /// A plugin answering the methods used by the tests, written in shell
+    ///
+    /// `getUrl` is answered after a line which is not JSON and a message
+    /// without ID, `hang` is never answered and `exit` stops the plugin.
+    const SCRIPT: &str = r#"
+while IFS= read -r line; do
+  id=$(printf '%s\n' "$line" | sed 's/.*"id":\([0-9]*\).*/\1/')
+  reply() { printf '{"jsonrpc":"2.0","id":%s,%s}\n' "$id" "$1"; }
+  fail() { reply "\"error\":{\"code\":$1,\"message\":\"$2\"}"; }
+  case "$line" in
+    *'"method":"info"'*) reply '"result":{"connectorId":"test","type":"HOSTING","displayName":"Test"}' ;;
+    *'"method":"getUrl"'*) echo 'not json'; echo '{"jsonrpc":"2.0"}'; reply '"result":"https://example.test"' ;;
+    *'"method":"publish"'*) sleep 0.2; reply '"result":{"message":"Done","logs":["Uploaded"]}' ;;
+    *'"method":"notAuthenticated"'*) fail -32001 login ;;
+    *'"method":"notFound"'*) fail -32004 missing ;;
+    *'"method":"invalid"'*) fail -32602 invalid ;;
+    *'"method":"failed"'*) fail -1 failed ;;
+    *'"method":"hang"'*) ;;
+    *'"method":"exit"'*) exit 1 ;;
+    *) fail -32601 unknown ;;
+  esac
+done
+"#;
+
+    /// The test plugin script, removed when dropped
+    struct Script(std::path::PathBuf);
+
+    impl Script {
+        fn new() -> Self {
+            let path =
+                std::env::temp_dir().join(format!("silex-plugin-{}.sh", uuid::Uuid::new_v4()));
+            std::fs::write(&path, SCRIPT).unwrap();
+            Script(path)
+        }
+
+        fn command(&self) -> String {
+            format!("sh {}", self.0.display())
+        }
+    }
+
+    impl Drop for Script {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    /// Start the test plugin, restarted after 100 ms
+    fn process(script: &Script, timeout: Option<Duration>) -> PluginProcess {
+        PluginProcess {
+            restart_delay: Duration::from_millis(100),
+            ..PluginProcess::spawn(&script.command(), timeout).unwrap()
+        }
+    }
+
+    #[test]
+    fn parse_response_maps_error_codes() {
+        let error = |code: i64| {
+            parse_response(json!({ "id": 1, "error": { "code": code, "message": "oops" } }))
+        };
+
+        assert!(matches!(
+            error(ERROR_NOT_AUTHENTICATED),
+            Err(ConnectorError::NotAuthenticated)
+        ));
+        assert!(matches!(error(ERROR_NOT_FOUND), Err(ConnectorError::NotFound(m)) if m == "oops"));
+        assert!(matches!(
+            error(ERROR_INVALID_PARAMS),
+            Err(ConnectorError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            error(ERROR_METHOD_NOT_FOUND),
+            Err(ConnectorError::NotSupported(_))
+        ));
+        assert!(matches!(error(-32000), Err(ConnectorError::Remote(_))));
+        assert_eq!(
+            parse_response(json!({ "id": 1, "result": { "a": 1 } })).unwrap(),
+            json!({ "a": 1 })
+        );
+        assert_eq!(parse_response(json!({ "id": 1 })).unwrap(), json!(null));
+    }
+
+    #[tokio::test]
+    async fn calls_are_framed_as_json_lines() {
+        let script = Script::new();
+        let Ok(PluginConnector::Hosting(hosting)) =
+            PluginConnector::spawn(&script.command(), None).await
+        else {
+            panic!("the plugin is a hosting connector");
+        };
+
+        assert_eq!(hosting.connector_id(), "test");
+        assert_eq!(hosting.display_name(), "Test");
+        // Lines which are not responses are skipped
+        assert_eq!(
+            hosting.get_url(&json!({}), &"1".to_string()).await.unwrap(),
+            "https://example.test"
+        );
+    }
+
+    #[tokio::test]
+    async fn error_responses_are_mapped() {
+        let script = Script::new();
+        let process = process(&script, None);
+        let call = |method: &'static str| process.call(method, json!({}));
+
+        assert!(matches!(
+            call("notAuthenticated").await,
+            Err(ConnectorError::NotAuthenticated)
+        ));
+        assert!(matches!(
+            call("notFound").await,
+            Err(ConnectorError::NotFound(_))
+        ));
+        assert!(matches!(
+            call("invalid").await,
+            Err(ConnectorError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            call("failed").await,
+            Err(ConnectorError::Remote(_))
+        ));
+        assert!(matches!(
+            call("listAssets").await,
+            Err(ConnectorError::NotSupported(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn timed_out_plugin_is_killed_and_restarted() {
+        let script = Script::new();
+        let process = process(&script, Some(Duration::from_millis(300)));
+
+        let result = process.call("hang", json!({})).await;
+        assert!(matches!(result, Err(ConnectorError::Remote(m)) if m.contains("did not answer")));
+
+        // The next calls wait for the restart delay
+        let result = process.call("info", json!({})).await;
+        assert!(matches!(result, Err(ConnectorError::Remote(m)) if m.contains("restarted")));
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        let info = process.call("info", json!({})).await.unwrap();
+        assert_eq!(info["connectorId"], "test");
+    }
+
+    #[tokio::test]
+    async fn restart_delay_doubles_until_the_plugin_answers() {
+        let script = Script::new();
+        let process = process(&script, None);
+
+        let result = process.call("exit", json!({})).await;
+        assert!(matches!(result, Err(ConnectorError::Remote(m)) if m.contains("exited")));
+
+        // Restarted after 100 ms, then exits again without answering
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(process.call("exit", json!({})).await.is_err());
+
+        // Restarted after 200 ms
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(process.call("info", json!({})).await.is_err());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(process.call("info", json!({})).await.is_ok());
+
+        // The plugin answered: back to 100 ms
+        assert!(process.call("exit", json!({})).await.is_err());
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(process.call("info", json!({})).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn publish_runs_as_a_job() {
+        let script = Script::new();
+        let Ok(PluginConnector::Hosting(hosting)) =
+            PluginConnector::spawn(&script.command(), None).await
+        else {
+            panic!("the plugin is a hosting connector");
+        };
+        let job_manager = JobManager::new();
+        let files = vec![ConnectorFile {
+            path: "/index.html".to_string(),
+            content: b"<html></html>".to_vec(),
+        }];
+
+        // The job is returned before the plugin answers
+        let job = hosting
+            .publish(&json!({}), &"1".to_string(), files, &job_manager)
+            .await
+            .unwrap();
+        assert_eq!(job.base.status, JobStatus::InProgress);
+
+        let mut job = job_manager.get_job(&job.base.job_id).unwrap();
+        for _ in 0..50 {
+            if job.base.status != JobStatus::InProgress {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            job = job_manager.get_job(&job.base.job_id).unwrap();
+        }
+        assert_eq!(job.base.status, JobStatus::Success);
+        assert_eq!(job.base.message, "Done");
+        assert!(job.logs[0].contains(&"Uploaded".to_string()));
+    }
+}
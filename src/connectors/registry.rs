@@ -13,8 +13,11 @@
 //! The registry provides methods to look up connectors by ID or type.
 
 use std::sync::Arc;
+use std::time::Duration;
 
+use crate::connectors::plugin::PluginConnector;
 use crate::connectors::traits::{HostingConnector, StorageConnector};
+use crate::error::ConnectorResult;
 
 /// Registry of available connectors
 ///
@@ -47,6 +50,23 @@ impl ConnectorRegistry {
         self.hosting_connectors.push(connector);
     }
 
+    /// Start a connector plugin and register it
+    ///
+    /// The plugin is registered as a storage or hosting connector,
+    /// depending on the type it declares. It is killed when a call lasts
+    /// longer than `timeout`.
+    pub async fn register_plugin(
+        &mut self,
+        command: &str,
+        timeout: Option<Duration>,
+    ) -> ConnectorResult<()> {
+        match PluginConnector::spawn(command, timeout).await? {
+            PluginConnector::Storage(connector) => self.register_storage(Arc::new(connector)),
+            PluginConnector::Hosting(connector) => self.register_hosting(Arc::new(connector)),
+        }
+        Ok(())
+    }
+
     /// Get all storage connectors
     pub fn storage_connectors(&self) -> &[Arc<dyn StorageConnector>] {
         &self.storage_connectors
@@ -104,7 +124,6 @@ impl ConnectorRegistry {
             _ => self.hosting_connectors.first().cloned(),
         }
     }
-
 }
 
 impl Default for ConnectorRegistry {
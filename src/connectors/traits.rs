@@ -14,11 +14,13 @@
 //! HostingConnector: for publishing websites
 
 use async_trait::async_trait;
+use tokio::io::{AsyncRead, AsyncReadExt};
 
-use crate::error::ConnectorResult;
+use crate::error::{ConnectorError, ConnectorResult};
 use crate::models::{
-    ConnectorData, ConnectorFile, ConnectorOptions, ConnectorType, ConnectorUser,
-    PublicationJobData, WebsiteData, WebsiteId, WebsiteMeta, WebsiteMetaFileContent,
+    page_id_of, page_revision, AssetInfo, AssetStream, ConnectorData, ConnectorFile,
+    ConnectorOptions, ConnectorType, ConnectorUser, IntegrityReport, PublicationJobData,
+    TrashedWebsite, WebsiteData, WebsiteId, WebsiteMeta, WebsiteMetaFileContent, WebsiteVersion,
 };
 use crate::services::JobManager;
 
@@ -96,7 +98,8 @@ pub trait StorageConnector: ConnectorInfo {
     // ==================
 
     /// List all websites accessible to the user
-    async fn list_websites(&self, session: &serde_json::Value) -> ConnectorResult<Vec<WebsiteMeta>>;
+    async fn list_websites(&self, session: &serde_json::Value)
+        -> ConnectorResult<Vec<WebsiteMeta>>;
 
     /// Read a website's data
     async fn read_website(
@@ -122,13 +125,74 @@ pub trait StorageConnector: ConnectorInfo {
         data: &WebsiteData,
     ) -> ConnectorResult<()>;
 
+    /// Update a website's data if it was not modified since it was read
+    ///
+    /// When `if_match` is set and differs from the current revision,
+    /// fails with `ConnectorError::Conflict` holding the current revision.
+    /// Returns the new revision.
+    ///
+    /// The default implementation compares with the revision of `read_website`
+    /// then writes: this is not atomic, a save made between the check and the
+    /// write is lost. Connectors override it to make the check and the write
+    /// atomic, as FsStorage does with its website lock. GitLab storage and
+    /// plugins use the default.
+    async fn update_website_if_match(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+        data: &WebsiteData,
+        if_match: Option<&str>,
+    ) -> ConnectorResult<String> {
+        if let Some(expected) = if_match {
+            let current = self.read_website(session, website_id).await?.revision();
+            if current != expected {
+                return Err(ConnectorError::Conflict(current));
+            }
+        }
+
+        self.update_website(session, website_id, data).await?;
+
+        // Read back, the stored data may be normalized
+        Ok(self.read_website(session, website_id).await?.revision())
+    }
+
+    /// Create a website with a given ID, e.g. to import it with its former ID
+    ///
+    /// Fails when a website has this ID. The default implementation fails
+    /// with `ConnectorError::NotSupported`, for connectors whose IDs are
+    /// chosen by their backend.
+    async fn create_website_with_id(
+        &self,
+        _session: &serde_json::Value,
+        _website_id: &WebsiteId,
+        _meta: &WebsiteMetaFileContent,
+    ) -> ConnectorResult<()> {
+        Err(ConnectorError::NotSupported(format!(
+            "{} can not create a website with a given ID",
+            self.display_name()
+        )))
+    }
+
     /// Delete a website
+    ///
+    /// Connectors with a trash keep the website there until it is purged.
     async fn delete_website(
         &self,
         session: &serde_json::Value,
         website_id: &WebsiteId,
     ) -> ConnectorResult<()>;
 
+    /// Delete a website without keeping it in the trash, e.g. a failed import
+    ///
+    /// The default implementation is `delete_website`, for connectors without trash.
+    async fn purge_website(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+    ) -> ConnectorResult<()> {
+        self.delete_website(session, website_id).await
+    }
+
     /// Duplicate a website
     async fn duplicate_website(
         &self,
@@ -136,6 +200,247 @@ pub trait StorageConnector: ConnectorInfo {
         website_id: &WebsiteId,
     ) -> ConnectorResult<WebsiteId>;
 
+    // ==================
+    // Pages
+    // ==================
+
+    /// Read one page of a website
+    ///
+    /// The default implementation reads the whole website data, connectors
+    /// which store pages separately may override it to read the page only.
+    async fn read_page(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+        page_id: &str,
+    ) -> ConnectorResult<serde_json::Value> {
+        let data = self.read_website(session, website_id).await?;
+        data.page(page_id)
+            .cloned()
+            .ok_or_else(|| page_not_found(website_id, page_id))
+    }
+
+    /// Save one page of a website, replacing the page with the same ID or adding it
+    ///
+    /// When `if_match` is set, the page must exist and have this revision (see
+    /// `page_revision`), or the save fails with `ConnectorError::Conflict`
+    /// holding the current revision. Returns the new revision of the page.
+    ///
+    /// The default implementation rewrites the whole website data with
+    /// `update_website_if_match`, so that a page saved by someone else in the
+    /// meantime is not lost: the website is read again and the save retried,
+    /// up to `PAGE_SAVE_ATTEMPTS` times before failing with a conflict.
+    async fn write_page(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+        page: &serde_json::Value,
+        if_match: Option<&str>,
+    ) -> ConnectorResult<String> {
+        crate::services::validate_page(page)?;
+        let page_id = page_id_of(page).unwrap_or_default();
+
+        for _ in 0..PAGE_SAVE_ATTEMPTS {
+            let mut data = self.read_website(session, website_id).await?;
+            let revision = data.revision();
+            let index = data
+                .pages
+                .iter()
+                .position(|p| page_id_of(p) == Some(page_id));
+            if let Some(expected) = if_match {
+                let Some(index) = index else {
+                    return Err(page_not_found(website_id, page_id));
+                };
+                let current = page_revision(&data.pages[index]);
+                if current != expected {
+                    return Err(ConnectorError::Conflict(current));
+                }
+            }
+
+            match index {
+                Some(index) => data.pages[index] = page.clone(),
+                None => data.pages.push(page.clone()),
+            }
+            match self
+                .update_website_if_match(session, website_id, &data, Some(&revision))
+                .await
+            {
+                Err(ConnectorError::Conflict(_)) => continue,
+                result => {
+                    result?;
+                    return Ok(page_revision(page));
+                }
+            }
+        }
+
+        let current = self.read_page(session, website_id, page_id).await?;
+        Err(ConnectorError::Conflict(page_revision(&current)))
+    }
+
+    /// Delete one page of a website
+    ///
+    /// The default implementation rewrites the whole website data, retrying
+    /// when it was modified in the meantime as `write_page` does.
+    async fn delete_page(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+        page_id: &str,
+    ) -> ConnectorResult<()> {
+        let mut conflict = None;
+        for _ in 0..PAGE_SAVE_ATTEMPTS {
+            let mut data = self.read_website(session, website_id).await?;
+            let revision = data.revision();
+            let index = data
+                .pages
+                .iter()
+                .position(|p| page_id_of(p) == Some(page_id))
+                .ok_or_else(|| page_not_found(website_id, page_id))?;
+            data.pages.remove(index);
+            match self
+                .update_website_if_match(session, website_id, &data, Some(&revision))
+                .await
+            {
+                Err(e @ ConnectorError::Conflict(_)) => conflict = Some(e),
+                result => return result.map(|_| ()),
+            }
+        }
+        Err(conflict.unwrap_or_else(|| ConnectorError::Conflict(String::new())))
+    }
+
+    // ==================
+    // History
+    // ==================
+
+    /// List the saved versions of a website, newest first
+    async fn list_versions(
+        &self,
+        _session: &serde_json::Value,
+        _website_id: &WebsiteId,
+    ) -> ConnectorResult<Vec<WebsiteVersion>> {
+        Err(ConnectorError::NotSupported(format!(
+            "{} does not keep a version history",
+            self.display_name()
+        )))
+    }
+
+    /// Read the website data of a saved version
+    async fn read_version(
+        &self,
+        _session: &serde_json::Value,
+        _website_id: &WebsiteId,
+        _version_id: &str,
+    ) -> ConnectorResult<WebsiteData> {
+        Err(ConnectorError::NotSupported(format!(
+            "{} does not keep a version history",
+            self.display_name()
+        )))
+    }
+
+    /// Restore a saved version as the current website data
+    ///
+    /// The replaced data stays in the history, so a restore can be undone.
+    async fn restore_version(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+        version_id: &str,
+    ) -> ConnectorResult<()> {
+        let data = self.read_version(session, website_id, version_id).await?;
+        self.update_website(session, website_id, &data).await
+    }
+
+    // ==================
+    // Schema
+    // ==================
+
+    /// Migrate a stored website to the current schema version
+    ///
+    /// Returns whether the website was rewritten. The default implementation
+    /// always rewrites the website data, which is migrated when read.
+    async fn migrate_website(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+    ) -> ConnectorResult<bool> {
+        let data = self.read_website(session, website_id).await?;
+        self.update_website(session, website_id, &data).await?;
+        Ok(true)
+    }
+
+    // ==================
+    // Integrity
+    // ==================
+
+    /// Check the stored files of a website
+    async fn check_integrity(
+        &self,
+        _session: &serde_json::Value,
+        _website_id: &WebsiteId,
+    ) -> ConnectorResult<IntegrityReport> {
+        Err(ConnectorError::NotSupported(format!(
+            "{} can not check websites integrity",
+            self.display_name()
+        )))
+    }
+
+    /// Rebuild website.json from the page files which can be loaded
+    ///
+    /// Returns the report of the repaired website.
+    async fn repair_website(
+        &self,
+        _session: &serde_json::Value,
+        _website_id: &WebsiteId,
+    ) -> ConnectorResult<IntegrityReport> {
+        Err(ConnectorError::NotSupported(format!(
+            "{} can not repair websites",
+            self.display_name()
+        )))
+    }
+
+    // ==================
+    // Trash
+    // ==================
+
+    /// List the deleted websites which can still be restored, newest first
+    async fn list_trash(
+        &self,
+        _session: &serde_json::Value,
+    ) -> ConnectorResult<Vec<TrashedWebsite>> {
+        Err(ConnectorError::NotSupported(format!(
+            "{} has no trash",
+            self.display_name()
+        )))
+    }
+
+    /// Restore a deleted website with its former ID
+    ///
+    /// Returns the ID of the restored website.
+    async fn restore_website(
+        &self,
+        _session: &serde_json::Value,
+        _trash_id: &str,
+    ) -> ConnectorResult<WebsiteId> {
+        Err(ConnectorError::NotSupported(format!(
+            "{} has no trash",
+            self.display_name()
+        )))
+    }
+
+    /// Permanently delete a website from the trash, or all of them when `trash_id` is None
+    ///
+    /// Returns the number of websites purged.
+    async fn purge_trash(
+        &self,
+        _session: &serde_json::Value,
+        _trash_id: Option<&str>,
+    ) -> ConnectorResult<usize> {
+        Err(ConnectorError::NotSupported(format!(
+            "{} has no trash",
+            self.display_name()
+        )))
+    }
+
     // ==================
     // Assets
     // ==================
@@ -148,6 +453,35 @@ pub trait StorageConnector: ConnectorInfo {
         files: Vec<ConnectorFile>,
     ) -> ConnectorResult<Vec<String>>;
 
+    /// Write a single asset file from a reader, without holding it in memory
+    ///
+    /// Returns the path as stored, like `write_assets`. The default
+    /// implementation reads the whole file, then calls `write_assets`.
+    async fn write_asset_stream(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+        file_name: &str,
+        mut reader: Box<dyn AsyncRead + Send + Unpin>,
+    ) -> ConnectorResult<String> {
+        let mut content = Vec::new();
+        reader.read_to_end(&mut content).await?;
+        let file = ConnectorFile {
+            path: file_name.to_string(),
+            content,
+        };
+        self.write_assets(session, website_id, vec![file])
+            .await?
+            .pop()
+            .ok_or_else(|| {
+                ConnectorError::Remote(format!(
+                    "{} did not write asset '{}'",
+                    self.display_name(),
+                    file_name
+                ))
+            })
+    }
+
     /// Read a single asset file
     async fn read_asset(
         &self,
@@ -156,6 +490,61 @@ pub trait StorageConnector: ConnectorInfo {
         file_name: &str,
     ) -> ConnectorResult<Vec<u8>>;
 
+    /// Open a single asset file, to stream it
+    ///
+    /// The default implementation reads the whole file with `read_asset`.
+    async fn read_asset_stream(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+        file_name: &str,
+    ) -> ConnectorResult<AssetStream> {
+        let content = self.read_asset(session, website_id, file_name).await?;
+        Ok(AssetStream::from_content(content, None))
+    }
+
+    /// List the asset files of a website, sorted by path
+    async fn list_assets(
+        &self,
+        _session: &serde_json::Value,
+        _website_id: &WebsiteId,
+    ) -> ConnectorResult<Vec<AssetInfo>> {
+        Err(ConnectorError::NotSupported(format!(
+            "{} can not list assets",
+            self.display_name()
+        )))
+    }
+
+    /// Delete an asset file
+    async fn delete_asset(
+        &self,
+        _session: &serde_json::Value,
+        _website_id: &WebsiteId,
+        _file_name: &str,
+    ) -> ConnectorResult<()> {
+        Err(ConnectorError::NotSupported(format!(
+            "{} can not delete assets",
+            self.display_name()
+        )))
+    }
+
+    /// Move or rename an asset file
+    ///
+    /// The website data is not updated, the references to the asset are the
+    /// caller's business. The default implementation reads the file, deletes
+    /// it and writes it back under its new path, so that a connector which can
+    /// not delete assets fails before anything changed. When the write fails,
+    /// the original is written back.
+    async fn move_asset(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+        from: &str,
+        to: &str,
+    ) -> ConnectorResult<()> {
+        move_asset_by_copy(self, session, website_id, from, to).await
+    }
+
     // ==================
     // Metadata
     // ==================
@@ -174,6 +563,44 @@ pub trait StorageConnector: ConnectorInfo {
         website_id: &WebsiteId,
         meta: &WebsiteMetaFileContent,
     ) -> ConnectorResult<()>;
+
+    /// Update website metadata if it was not modified since it was read
+    ///
+    /// Same as `update_website_if_match`, for the metadata. The default
+    /// implementation is a check then a write, not atomic either.
+    async fn set_website_meta_if_match(
+        &self,
+        session: &serde_json::Value,
+        website_id: &WebsiteId,
+        meta: &WebsiteMetaFileContent,
+        if_match: Option<&str>,
+    ) -> ConnectorResult<String> {
+        if let Some(expected) = if_match {
+            let current = self
+                .get_website_meta(session, website_id)
+                .await?
+                .to_file_content()
+                .revision();
+            if current != expected {
+                return Err(ConnectorError::Conflict(current));
+            }
+        }
+
+        self.set_website_meta(session, website_id, meta).await?;
+
+        Ok(meta.revision())
+    }
+}
+
+/// Times a page save is tried when the website is modified by another save at the same time
+const PAGE_SAVE_ATTEMPTS: usize = 3;
+
+/// Error of a page which is not in a website
+pub(crate) fn page_not_found(website_id: &str, page_id: &str) -> ConnectorError {
+    ConnectorError::NotFound(format!(
+        "Page '{}' not found in website '{}'",
+        page_id, website_id
+    ))
 }
 
 /// HostingConnector publishes websites to make them accessible
@@ -235,6 +662,90 @@ pub trait HostingConnector: ConnectorInfo {
     ) -> ConnectorResult<String>;
 }
 
+/// Move an asset by writing a copy and deleting the original
+///
+/// The default of `StorageConnector::move_asset`, for connectors which can
+/// not move files. Fails when the target exists.
+pub(crate) async fn move_asset_by_copy<C: StorageConnector + ?Sized>(
+    connector: &C,
+    session: &serde_json::Value,
+    website_id: &WebsiteId,
+    from: &str,
+    to: &str,
+) -> ConnectorResult<()> {
+    match connector.read_asset(session, website_id, to).await {
+        Ok(_) => {
+            return Err(ConnectorError::InvalidInput(format!(
+                "Asset '{}' already exists",
+                to
+            )))
+        }
+        Err(ConnectorError::NotFound(_)) => {}
+        Err(e) => return Err(e),
+    }
+    let content = connector.read_asset(session, website_id, from).await?;
+    connector.delete_asset(session, website_id, from).await?;
+
+    let file = |path: &str| ConnectorFile {
+        path: path.to_string(),
+        content: content.clone(),
+    };
+    if let Err(e) = connector
+        .write_assets(session, website_id, vec![file(to)])
+        .await
+    {
+        if let Err(restore) = connector
+            .write_assets(session, website_id, vec![file(from)])
+            .await
+        {
+            tracing::error!(
+                "Could not restore asset {} of website {}: {}",
+                from,
+                website_id,
+                restore
+            );
+        }
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Session key of the user who logged in, recorded as author of the changes
+const SESSION_USER_KEY: &str = "user";
+
+/// Get the name of the user who logged in to a connector in this session
+///
+/// Connectors without login of their own, like the filesystem storage,
+/// record this user as the author of the changes.
+pub fn session_user(session: &serde_json::Value) -> Option<String> {
+    session
+        .get(SESSION_USER_KEY)
+        .and_then(|user| user.get("name"))
+        .and_then(|name| name.as_str())
+        .map(String::from)
+}
+
+/// Keep the user who logged in to a connector in the session
+pub fn set_session_user(session: &mut serde_json::Value, connector_id: &str, name: &str) {
+    if !session.is_object() {
+        *session = serde_json::json!({});
+    }
+    session[SESSION_USER_KEY] = serde_json::json!({ "name": name, "connectorId": connector_id });
+}
+
+/// Forget the session user when they log out of the connector they logged in with
+pub fn clear_session_user(session: &mut serde_json::Value, connector_id: &str) {
+    let logged_in_with = session
+        .get(SESSION_USER_KEY)
+        .and_then(|user| user.get("connectorId"))
+        .and_then(|id| id.as_str());
+    if logged_in_with == Some(connector_id) {
+        if let Some(map) = session.as_object_mut() {
+            map.remove(SESSION_USER_KEY);
+        }
+    }
+}
+
 /// Helper function to convert a connector to ConnectorData for the frontend
 pub async fn to_connector_data<C: StorageConnector + ?Sized>(
     session: &serde_json::Value,
@@ -247,7 +758,7 @@ pub async fn to_connector_data<C: StorageConnector + ?Sized>(
         icon: connector.icon().to_string(),
         disable_logout: connector.disable_logout(),
         is_logged_in: connector.is_logged_in(session).await?,
-        oauth_url: connector.get_oauth_url(session).await?,
+        oauth_url: login_url(connector.get_oauth_url(session).await?, connector),
         color: connector.color().to_string(),
         background: connector.background().to_string(),
     })
@@ -265,8 +776,337 @@ pub async fn hosting_to_connector_data<C: HostingConnector + ?Sized>(
         icon: connector.icon().to_string(),
         disable_logout: connector.disable_logout(),
         is_logged_in: connector.is_logged_in(session).await?,
-        oauth_url: connector.get_oauth_url(session).await?,
+        oauth_url: login_url(connector.get_oauth_url(session).await?, connector),
         color: connector.color().to_string(),
         background: connector.background().to_string(),
     })
 }
+
+/// URL starting the OAuth login of a connector which has an OAuth URL
+///
+/// The login route redirects to the OAuth URL with a `state` it keeps in the
+/// session, so that the frontend never opens the OAuth URL without one.
+fn login_url<C: ConnectorInfo + ?Sized>(
+    oauth_url: Option<String>,
+    connector: &C,
+) -> Option<String> {
+    let connector_type = match connector.connector_type() {
+        ConnectorType::Storage => "STORAGE",
+        ConnectorType::Hosting => "HOSTING",
+    };
+    oauth_url.map(|_| {
+        format!(
+            "/api/connector/login?connectorId={}&type={}",
+            connector.connector_id(),
+            connector_type
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    /// A storage holding one website in memory, with the default page methods
+    #[derive(Default)]
+    struct MemoryStorage {
+        data: Mutex<WebsiteData>,
+
+        /// Number of saves made by someone else, each right before one of ours
+        concurrent_saves: Mutex<usize>,
+    }
+
+    impl MemoryStorage {
+        fn new(pages: Vec<serde_json::Value>, concurrent_saves: usize) -> Self {
+            MemoryStorage {
+                data: Mutex::new(WebsiteData {
+                    pages,
+                    ..Default::default()
+                }),
+                concurrent_saves: Mutex::new(concurrent_saves),
+            }
+        }
+
+        fn pages(&self) -> Vec<serde_json::Value> {
+            self.data.lock().unwrap().pages.clone()
+        }
+    }
+
+    impl ConnectorInfo for MemoryStorage {
+        fn connector_id(&self) -> &str {
+            "memory"
+        }
+        fn connector_type(&self) -> ConnectorType {
+            ConnectorType::Storage
+        }
+        fn display_name(&self) -> &str {
+            "Memory"
+        }
+        fn icon(&self) -> &str {
+            ""
+        }
+        fn color(&self) -> &str {
+            ""
+        }
+        fn background(&self) -> &str {
+            ""
+        }
+    }
+
+    #[async_trait]
+    impl StorageConnector for MemoryStorage {
+        async fn is_logged_in(&self, _session: &serde_json::Value) -> ConnectorResult<bool> {
+            Ok(true)
+        }
+        async fn get_oauth_url(
+            &self,
+            _session: &serde_json::Value,
+        ) -> ConnectorResult<Option<String>> {
+            Ok(None)
+        }
+        async fn set_token(
+            &self,
+            _session: &mut serde_json::Value,
+            _token: &serde_json::Value,
+        ) -> ConnectorResult<()> {
+            Ok(())
+        }
+        async fn logout(&self, _session: &mut serde_json::Value) -> ConnectorResult<()> {
+            Ok(())
+        }
+        async fn get_user(&self, _session: &serde_json::Value) -> ConnectorResult<ConnectorUser> {
+            unimplemented!()
+        }
+        fn get_options(&self, _form_data: &serde_json::Value) -> ConnectorOptions {
+            unimplemented!()
+        }
+        async fn list_websites(
+            &self,
+            _session: &serde_json::Value,
+        ) -> ConnectorResult<Vec<WebsiteMeta>> {
+            unimplemented!()
+        }
+        async fn read_website(
+            &self,
+            _session: &serde_json::Value,
+            _website_id: &WebsiteId,
+        ) -> ConnectorResult<WebsiteData> {
+            Ok(self.data.lock().unwrap().clone())
+        }
+        async fn create_website(
+            &self,
+            _session: &serde_json::Value,
+            _meta: &WebsiteMetaFileContent,
+        ) -> ConnectorResult<WebsiteId> {
+            unimplemented!()
+        }
+        async fn update_website(
+            &self,
+            _session: &serde_json::Value,
+            _website_id: &WebsiteId,
+            data: &WebsiteData,
+        ) -> ConnectorResult<()> {
+            *self.data.lock().unwrap() = data.clone();
+            Ok(())
+        }
+        async fn update_website_if_match(
+            &self,
+            _session: &serde_json::Value,
+            _website_id: &WebsiteId,
+            data: &WebsiteData,
+            if_match: Option<&str>,
+        ) -> ConnectorResult<String> {
+            let mut stored = self.data.lock().unwrap();
+            let mut concurrent_saves = self.concurrent_saves.lock().unwrap();
+            if *concurrent_saves > 0 {
+                *concurrent_saves -= 1;
+                let id = format!("other-{}", concurrent_saves);
+                stored.pages.push(json!({ "id": id }));
+            }
+            if let Some(expected) = if_match {
+                if stored.revision() != expected {
+                    return Err(ConnectorError::Conflict(stored.revision()));
+                }
+            }
+            *stored = data.clone();
+            Ok(stored.revision())
+        }
+        async fn delete_website(
+            &self,
+            _session: &serde_json::Value,
+            _website_id: &WebsiteId,
+        ) -> ConnectorResult<()> {
+            unimplemented!()
+        }
+        async fn duplicate_website(
+            &self,
+            _session: &serde_json::Value,
+            _website_id: &WebsiteId,
+        ) -> ConnectorResult<WebsiteId> {
+            unimplemented!()
+        }
+        async fn write_assets(
+            &self,
+            _session: &serde_json::Value,
+            _website_id: &WebsiteId,
+            _files: Vec<ConnectorFile>,
+        ) -> ConnectorResult<Vec<String>> {
+            unimplemented!()
+        }
+        async fn read_asset(
+            &self,
+            _session: &serde_json::Value,
+            _website_id: &WebsiteId,
+            _file_name: &str,
+        ) -> ConnectorResult<Vec<u8>> {
+            unimplemented!()
+        }
+        async fn get_website_meta(
+            &self,
+            _session: &serde_json::Value,
+            _website_id: &WebsiteId,
+        ) -> ConnectorResult<WebsiteMeta> {
+            unimplemented!()
+        }
+        async fn set_website_meta(
+            &self,
+            _session: &serde_json::Value,
+            _website_id: &WebsiteId,
+            _meta: &WebsiteMetaFileContent,
+        ) -> ConnectorResult<()> {
+            unimplemented!()
+        }
+    }
+
+    /// The IDs of pages
+    fn ids(pages: &[serde_json::Value]) -> Vec<&str> {
+        pages.iter().filter_map(page_id_of).collect()
+    }
+
+    #[tokio::test]
+    async fn read_write_and_delete_a_page() {
+        let storage = MemoryStorage::new(vec![json!({ "id": "home", "name": "Home" })], 0);
+        let session = json!({});
+        let website_id = "site".to_string();
+        let page = json!({ "id": "about", "name": "About" });
+
+        let revision = storage
+            .write_page(&session, &website_id, &page, None)
+            .await
+            .unwrap();
+        assert_eq!(revision, page_revision(&page));
+        assert_eq!(
+            storage
+                .read_page(&session, &website_id, "about")
+                .await
+                .unwrap(),
+            page
+        );
+
+        // Replaced in place
+        let renamed = json!({ "id": "home", "name": "Welcome" });
+        storage
+            .write_page(&session, &website_id, &renamed, None)
+            .await
+            .unwrap();
+        assert_eq!(storage.pages(), [renamed, page]);
+
+        storage
+            .delete_page(&session, &website_id, "home")
+            .await
+            .unwrap();
+        assert_eq!(ids(&storage.pages()), ["about"]);
+        assert!(matches!(
+            storage.read_page(&session, &website_id, "home").await,
+            Err(ConnectorError::NotFound(_))
+        ));
+        assert!(matches!(
+            storage.delete_page(&session, &website_id, "home").await,
+            Err(ConnectorError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn write_page_checks_the_page_revision() {
+        let home = json!({ "id": "home", "name": "Home" });
+        let storage = MemoryStorage::new(vec![home.clone()], 0);
+        let session = json!({});
+        let website_id = "site".to_string();
+        let edited = json!({ "id": "home", "name": "Edited" });
+
+        assert_eq!(
+            storage
+                .write_page(&session, &website_id, &edited, Some("stale"))
+                .await
+                .unwrap_err()
+                .to_string(),
+            ConnectorError::Conflict(page_revision(&home)).to_string()
+        );
+        assert!(matches!(
+            storage
+                .write_page(&session, &website_id, &json!({ "id": "new" }), Some("x"))
+                .await,
+            Err(ConnectorError::NotFound(_))
+        ));
+        assert!(matches!(
+            storage
+                .write_page(&session, &website_id, &json!({ "name": "No ID" }), None)
+                .await,
+            Err(ConnectorError::InvalidInput(_))
+        ));
+
+        storage
+            .write_page(&session, &website_id, &edited, Some(&page_revision(&home)))
+            .await
+            .unwrap();
+        assert_eq!(storage.pages(), [edited]);
+    }
+
+    #[tokio::test]
+    async fn page_saves_are_retried_after_concurrent_saves() {
+        let storage = MemoryStorage::new(vec![json!({ "id": "home" })], PAGE_SAVE_ATTEMPTS - 1);
+        let session = json!({});
+        let website_id = "site".to_string();
+
+        storage
+            .write_page(&session, &website_id, &json!({ "id": "about" }), None)
+            .await
+            .unwrap();
+
+        // The pages saved in the meantime are kept
+        assert_eq!(
+            ids(&storage.pages()),
+            ["home", "other-1", "other-0", "about"]
+        );
+    }
+
+    #[tokio::test]
+    async fn page_saves_fail_after_too_many_concurrent_saves() {
+        let home = json!({ "id": "home" });
+        let storage = MemoryStorage::new(vec![home.clone()], PAGE_SAVE_ATTEMPTS);
+        let session = json!({});
+        let website_id = "site".to_string();
+
+        let result = storage
+            .write_page(
+                &session,
+                &website_id,
+                &json!({ "id": "home", "name": "Home" }),
+                None,
+            )
+            .await;
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            ConnectorError::Conflict(page_revision(&home)).to_string()
+        );
+
+        *storage.concurrent_saves.lock().unwrap() = PAGE_SAVE_ATTEMPTS;
+        assert!(matches!(
+            storage.delete_page(&session, &website_id, "home").await,
+            Err(ConnectorError::Conflict(_))
+        ));
+        assert_eq!(ids(&storage.pages())[0], "home");
+    }
+}
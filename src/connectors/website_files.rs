@@ -0,0 +1,839 @@
+/*
+ * Silex website builder, free/libre no-code tool for makers.
+ * Copyright (c) 2023 lexoyo and Silex Labs foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or any later version.
+ */
+
+//! Website file layout
+//!
+//! Storage connectors which persist a website as files (FsStorage, GitlabStorage)
+//! share the same layout: a `website.json` holding page references, and one
+//! JSON file per page in the pages folder.
+//!
+//! The page references are the page index: each one holds the `file` of its page,
+//! named after the page when it is created and kept when it is renamed. References
+//! saved before the index have no `file`, their page is in `{slug(name)}-{id}.json`.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::future::Future;
+
+use crate::error::{ConnectorError, ConnectorResult};
+use crate::models::{constants, IntegrityReport, PageIssue, WebsiteData, CURRENT_SCHEMA_VERSION};
+use crate::services::{migrate_website_data, validate_website_data};
+
+/// Serialize data to JSON with sorted keys for stable output
+pub(crate) fn serialize_json<T: serde::Serialize>(data: &T) -> ConnectorResult<String> {
+    // Serialize to Value first, then to string with sorted keys
+    let value = serde_json::to_value(data)?;
+    let sorted = sort_json_keys(&value);
+    Ok(serde_json::to_string_pretty(&sorted)?)
+}
+
+/// Get the pages folder path from website data
+pub(crate) fn get_pages_folder(data: &WebsiteData) -> &str {
+    if data.pages_folder.is_empty() {
+        constants::LEGACY_WEBSITE_PAGES_FOLDER
+    } else {
+        &data.pages_folder
+    }
+}
+
+/// Get the pages folder path from a parsed website.json
+pub(crate) fn get_pages_folder_of(parsed: &serde_json::Value) -> &str {
+    parsed
+        .get("pagesFolder")
+        .and_then(|v| v.as_str())
+        .unwrap_or(constants::LEGACY_WEBSITE_PAGES_FOLDER)
+}
+
+/// Get a slug from a page name (for file naming)
+pub(crate) fn get_page_slug(name: &str) -> String {
+    name.to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect::<String>()
+        .trim_matches('-')
+        .to_string()
+}
+
+/// Get the file name of a page, e.g. `home-abc123.json`
+pub(crate) fn get_page_file_name(name: &str, id: &str) -> String {
+    format!("{}-{}.json", get_page_slug(name), id)
+}
+
+/// File names of the pages of a website, by page ID
+pub(crate) type PageIndex = HashMap<String, String>;
+
+/// Get the file name of a page reference
+///
+/// References without `file`, from before the page index, get the file
+/// name derived from the page name and ID.
+pub(crate) fn page_file_name(page_ref: &serde_json::Value) -> Option<String> {
+    let id = page_ref.get("id")?.as_str()?;
+    if let Some(file) = page_ref.get("file").and_then(|f| f.as_str()) {
+        // The index is read from storage, it must not point out of the pages folder
+        if is_page_file_name(file) {
+            return Some(file.to_string());
+        }
+        tracing::warn!("Ignoring invalid page file name '{}' of page {}", file, id);
+    }
+    let name = page_ref
+        .get("name")
+        .and_then(|n| n.as_str())
+        .unwrap_or("page");
+    Some(get_page_file_name(name, id))
+}
+
+/// Get the page index of a parsed website.json
+pub(crate) fn page_index(parsed: &serde_json::Value) -> PageIndex {
+    parsed
+        .get("pages")
+        .and_then(|p| p.as_array())
+        .map(|pages| {
+            pages
+                .iter()
+                .filter(|p| p.get("isFile").and_then(|f| f.as_bool()).unwrap_or(false))
+                .filter_map(|p| Some((p.get("id")?.as_str()?.to_string(), page_file_name(p)?)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Whether all the page references of a parsed website.json are in the page index
+pub(crate) fn has_page_index(parsed: &serde_json::Value) -> bool {
+    parsed
+        .get("pages")
+        .and_then(|p| p.as_array())
+        .map(|pages| {
+            pages
+                .iter()
+                .filter(|p| p.get("isFile").is_some())
+                .all(|p| p.get("file").is_some())
+        })
+        .unwrap_or(true)
+}
+
+/// Pick the file name of a page which is not in the page index yet
+///
+/// The name is derived from the page name and ID, and must not be used by another page.
+pub(crate) fn new_page_file_name(
+    page: &serde_json::Value,
+    used: &HashSet<String>,
+) -> Option<String> {
+    let id = page.get("id")?.as_str()?;
+    let name = page.get("name").and_then(|n| n.as_str()).unwrap_or("page");
+    let mut file_name = get_page_file_name(name, id);
+    let mut count = 1;
+    while used.contains(&file_name) {
+        count += 1;
+        file_name = format!("{}-{}-{}.json", get_page_slug(name), id, count);
+    }
+    Some(file_name)
+}
+
+/// Make the reference to a page file which website.json holds
+pub(crate) fn page_ref(page: &serde_json::Value, file_name: &str) -> serde_json::Value {
+    serde_json::json!({
+        "name": page.get("name").and_then(|n| n.as_str()).unwrap_or("page"),
+        "id": page.get("id"),
+        "isFile": true,
+        "file": file_name
+    })
+}
+
+/// Whether a name can be the name of a page file in the pages folder
+fn is_page_file_name(name: &str) -> bool {
+    name.ends_with(".json") && !name.starts_with('.') && !name.contains(['/', '\\'])
+}
+
+/// Whether a parsed website.json references page files, rather than embedding all its pages
+///
+/// Pages without ID stay embedded next to the references, e.g. the empty page of a new website.
+pub(crate) fn has_page_files(parsed: &serde_json::Value) -> bool {
+    parsed
+        .get("pages")
+        .and_then(|p| p.as_array())
+        .map(|pages| pages.iter().any(|page| page.get("isFile").is_some()))
+        .unwrap_or(false)
+}
+
+/// Split website data into separate files (website.json + individual pages)
+///
+/// Pages keep their file of the stored `index`, new pages get a new file.
+/// Returned paths are relative to the website root.
+/// Fails with `ConnectorError::InvalidInput` when the data is malformed.
+pub(crate) fn split_website_data(
+    data: &WebsiteData,
+    index: &PageIndex,
+) -> ConnectorResult<Vec<(String, String)>> {
+    validate_website_data(data)?;
+
+    let mut files = Vec::new();
+    let pages_folder = get_pages_folder(data);
+
+    // Files of the pages which are kept, new pages must not use them
+    let mut used_files: HashSet<String> = data
+        .pages
+        .iter()
+        .filter_map(|page| index.get(page.get("id")?.as_str()?))
+        .cloned()
+        .collect();
+
+    // Process each page
+    let mut page_refs = Vec::new();
+    for page in &data.pages {
+        // Skip empty pages (like the {} from EMPTY_PAGES in tests)
+        let Some(page_id) = page.get("id").and_then(|v| v.as_str()) else {
+            page_refs.push(page.clone());
+            continue;
+        };
+
+        let file_name = match index.get(page_id) {
+            Some(file_name) => file_name.clone(),
+            None => {
+                let file_name = new_page_file_name(page, &used_files).unwrap_or_default();
+                used_files.insert(file_name.clone());
+                file_name
+            }
+        };
+        let file_path = format!("{}/{}", pages_folder, file_name);
+
+        // Write the page file
+        let page_content = serialize_json(page)?;
+        files.push((file_path, page_content));
+
+        // Create a reference to the page file
+        page_refs.push(page_ref(page, &file_name));
+    }
+
+    // Create the main website.json with page references instead of full pages
+    let website_data_with_refs = serde_json::json!({
+        "schemaVersion": CURRENT_SCHEMA_VERSION,
+        "pages": page_refs,
+        "pagesFolder": pages_folder,
+        "assets": data.assets,
+        "styles": data.styles,
+        "settings": data.settings,
+        "fonts": data.fonts,
+        "symbols": data.symbols,
+        "publication": data.publication,
+    });
+
+    let website_content = serialize_json(&website_data_with_refs)?;
+    files.push((constants::WEBSITE_DATA_FILE.to_string(), website_content));
+
+    Ok(files)
+}
+
+/// Merge website data from main file and page files
+///
+/// `read_page` is called with each page file path, relative to the website root.
+/// Fails with `ConnectorError::Corrupted` when a page can not be loaded, so that
+/// the editor never opens, then saves, a website with blank pages.
+pub(crate) async fn merge_website_data<F, Fut>(
+    website_content: &str,
+    read_page: F,
+) -> ConnectorResult<WebsiteData>
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = ConnectorResult<String>>,
+{
+    let (data, report) = load_website_data(website_content, read_page).await?;
+    if report.is_corrupted() {
+        return Err(ConnectorError::Corrupted(Box::new(report)));
+    }
+    Ok(data)
+}
+
+/// Load website data from main file and page files, reporting the pages which can not be loaded
+///
+/// A page which can not be loaded is kept as its reference. Only the missing and
+/// invalid pages of the report are filled.
+pub(crate) async fn load_website_data<F, Fut>(
+    website_content: &str,
+    mut read_page: F,
+) -> ConnectorResult<(WebsiteData, IntegrityReport)>
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = ConnectorResult<String>>,
+{
+    let mut parsed: serde_json::Value = serde_json::from_str(website_content)?;
+    let mut report = IntegrityReport::default();
+
+    // Get pages folder
+    let pages_folder = get_pages_folder_of(&parsed).to_string();
+
+    // Check if we have page references to load
+    let pages = match parsed.get("pages") {
+        Some(serde_json::Value::Array(pages)) if !pages.is_empty() => pages.clone(),
+        _ => return Ok((from_stored(parsed)?, report)),
+    };
+
+    // Check if pages are already embedded (no isFile field)
+    if !has_page_files(&parsed) {
+        return Ok((from_stored(parsed)?, report));
+    }
+
+    // Load pages from separate files
+    let mut loaded_pages = Vec::new();
+    for page_ref in pages {
+        let is_file = page_ref
+            .get("isFile")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+
+        if is_file {
+            let page_name = page_ref
+                .get("name")
+                .and_then(|v| v.as_str())
+                .unwrap_or("page");
+            let page_id = page_ref.get("id").and_then(|v| v.as_str()).unwrap_or("");
+
+            let file_name =
+                page_file_name(&page_ref).unwrap_or_else(|| get_page_file_name(page_name, page_id));
+            let file_path = format!("{}/{}", pages_folder, file_name);
+            let issue = |error: Option<String>| PageIssue {
+                id: page_id.to_string(),
+                name: page_name.to_string(),
+                file: file_path.clone(),
+                error,
+            };
+
+            match read_page(file_path.clone()).await {
+                Ok(content) => match serde_json::from_str::<serde_json::Value>(&content) {
+                    Ok(page) => loaded_pages.push(page),
+                    Err(e) => {
+                        tracing::warn!("Invalid page file {}: {}", file_path, e);
+                        report.invalid_pages.push(issue(Some(e.to_string())));
+                        loaded_pages.push(page_ref);
+                    }
+                },
+                Err(ConnectorError::NotFound(_)) => {
+                    tracing::warn!("Missing page file {}", file_path);
+                    report.missing_pages.push(issue(None));
+                    loaded_pages.push(page_ref);
+                }
+                Err(e) => return Err(e),
+            }
+        } else {
+            loaded_pages.push(page_ref);
+        }
+    }
+
+    // Replace pages with loaded content
+    parsed["pages"] = serde_json::Value::Array(loaded_pages);
+
+    Ok((from_stored(parsed)?, report))
+}
+
+/// Deserialize stored website data, migrating it to the current schema version
+pub(crate) fn from_stored(value: serde_json::Value) -> ConnectorResult<WebsiteData> {
+    Ok(serde_json::from_value(migrate_website_data(value)?)?)
+}
+
+/// Get the file names of the pages referenced by a parsed website.json
+pub(crate) fn referenced_page_files(parsed: &serde_json::Value) -> HashSet<String> {
+    parsed
+        .get("pages")
+        .and_then(|p| p.as_array())
+        .map(|pages| {
+            pages
+                .iter()
+                .filter(|p| p.get("isFile").and_then(|f| f.as_bool()).unwrap_or(false))
+                .filter_map(page_file_name)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Get the path of an asset, relative to the assets folder, from its URL
+///
+/// Handles the stored form `/assets/image.png` and the displayed form
+/// `/api/website/assets/image.png?websiteId=X`, with or without origin.
+/// Other URLs have no path.
+pub(crate) fn asset_path(url: &str) -> Option<String> {
+    const API_PREFIX: &str = "/api/website/assets/";
+
+    let url = url.split(['?', '#']).next()?;
+    let path = match url.find(API_PREFIX) {
+        Some(pos) => &url[pos + API_PREFIX.len()..],
+        None => url.trim_start_matches('/').strip_prefix("assets/")?,
+    };
+    let path = percent_decode(path);
+
+    // Never leave the assets folder
+    if path.is_empty() || path.split('/').any(|part| part.is_empty() || part == "..") {
+        return None;
+    }
+    Some(path)
+}
+
+/// Get the paths of all the assets referenced by website data
+///
+/// Looks for asset URLs in every string, so that assets used in styles
+/// (e.g. background images) are found along with the assets list.
+pub(crate) fn referenced_asset_paths(data: &WebsiteData) -> BTreeSet<String> {
+    value_asset_paths(&serde_json::to_value(data).unwrap_or_default())
+}
+
+/// Get the paths of the assets referenced by a part of the website data, e.g. a page
+pub(crate) fn value_asset_paths(value: &serde_json::Value) -> BTreeSet<String> {
+    let mut paths = BTreeSet::new();
+    collect_asset_paths(value, &mut paths);
+    paths
+}
+
+/// Get the asset paths, relative to the assets folder, which website data does not use
+///
+/// An asset is used when one of its URLs is found in the data (see
+/// `referenced_asset_paths`), or when its path appears anywhere in it, so that
+/// URLs of an unexpected form keep their asset.
+pub(crate) fn unused_asset_paths(
+    data: &WebsiteData,
+    paths: impl IntoIterator<Item = String>,
+) -> ConnectorResult<Vec<String>> {
+    let referenced = referenced_asset_paths(data);
+    let serialized = serde_json::to_string(data)?;
+    Ok(paths
+        .into_iter()
+        .filter(|path| {
+            !referenced.contains(path)
+                && !serialized.contains(path.as_str())
+                && !serialized.contains(&path.replace(' ', "%20"))
+        })
+        .collect())
+}
+
+/// Collect the asset URLs found in the strings of a JSON value
+fn collect_asset_paths(value: &serde_json::Value, paths: &mut BTreeSet<String>) {
+    match value {
+        serde_json::Value::String(text) => {
+            for (start, _) in text.match_indices("/assets/") {
+                // The URL starts after a quote, a parenthesis or a space
+                let begin = text[..start]
+                    .rfind(|c: char| c == '"' || c == '\'' || c == '(' || c.is_whitespace())
+                    .map(|i| i + 1)
+                    .unwrap_or(0);
+                let end = text[start..]
+                    .find(|c: char| c == '"' || c == '\'' || c == ')' || c.is_whitespace())
+                    .map(|i| start + i)
+                    .unwrap_or(text.len());
+                if let Some(path) = asset_path(&text[begin..end]) {
+                    paths.insert(path);
+                }
+            }
+        }
+        serde_json::Value::Array(values) => {
+            values.iter().for_each(|v| collect_asset_paths(v, paths));
+        }
+        serde_json::Value::Object(map) => {
+            map.values().for_each(|v| collect_asset_paths(v, paths));
+        }
+        _ => {}
+    }
+}
+
+/// Point the asset URLs of a website to a copy of it
+///
+/// Displayed asset URLs hold the website and connector which store the asset
+/// (`/api/website/assets/image.png?websiteId=X&connectorId=Y`). In every string of
+/// `value`, the URLs of `from_website_id` get `to_website_id` and `to_connector_id`.
+/// URLs of other websites and stored URLs (`/assets/image.png`) are kept.
+pub(crate) fn rewrite_asset_urls(
+    value: &mut serde_json::Value,
+    from_website_id: &str,
+    to_website_id: &str,
+    to_connector_id: &str,
+) {
+    match value {
+        serde_json::Value::String(text) => {
+            if let Some(rewritten) =
+                rewrite_text_asset_urls(text, from_website_id, to_website_id, to_connector_id)
+            {
+                *text = rewritten;
+            }
+        }
+        serde_json::Value::Array(values) => values
+            .iter_mut()
+            .for_each(|v| rewrite_asset_urls(v, from_website_id, to_website_id, to_connector_id)),
+        serde_json::Value::Object(map) => map
+            .values_mut()
+            .for_each(|v| rewrite_asset_urls(v, from_website_id, to_website_id, to_connector_id)),
+        _ => {}
+    }
+}
+
+/// Rewrite the asset URLs of a string, None when it has none to rewrite
+fn rewrite_text_asset_urls(
+    text: &str,
+    from_website_id: &str,
+    to_website_id: &str,
+    to_connector_id: &str,
+) -> Option<String> {
+    const API_PREFIX: &str = "/api/website/assets/";
+
+    let mut rewritten = String::with_capacity(text.len());
+    let mut rest = text;
+    let mut changed = false;
+    while let Some(pos) = rest.find(API_PREFIX) {
+        // The URL ends before a quote, a parenthesis or a space
+        let start = pos + API_PREFIX.len();
+        let end = rest[start..]
+            .find(|c: char| matches!(c, '"' | '\'' | ')' | '<' | '>' | '\\') || c.is_whitespace())
+            .map(|i| start + i)
+            .unwrap_or(rest.len());
+        rewritten.push_str(&rest[..start]);
+
+        let url = &rest[start..end];
+        let (url, fragment) = url.split_at(url.find('#').unwrap_or(url.len()));
+        match url.split_once('?') {
+            Some((path, query)) => {
+                let new_query =
+                    rewrite_asset_query(query, from_website_id, to_website_id, to_connector_id);
+                changed |= new_query.is_some();
+                rewritten.push_str(path);
+                rewritten.push('?');
+                rewritten.push_str(new_query.as_deref().unwrap_or(query));
+            }
+            None => rewritten.push_str(url),
+        }
+        rewritten.push_str(fragment);
+        rest = &rest[end..];
+    }
+    rewritten.push_str(rest);
+
+    changed.then_some(rewritten)
+}
+
+/// Rewrite the `websiteId` and `connectorId` of an asset URL query string
+///
+/// Returns None when the query is not for `from_website_id`. Parameters may be
+/// separated by `&` or by `&amp;` in HTML. The `connectorId` is added when missing,
+/// as the copy may not be in the default storage connector.
+fn rewrite_asset_query(
+    query: &str,
+    from_website_id: &str,
+    to_website_id: &str,
+    to_connector_id: &str,
+) -> Option<String> {
+    let params: Vec<(&str, &str)> = query
+        .split('&')
+        .map(|param| match param.strip_prefix("amp;") {
+            Some(param) => ("amp;", param),
+            None => ("", param),
+        })
+        .collect();
+    let is_from = params
+        .iter()
+        .any(|(_, param)| param.split_once('=') == Some(("websiteId", from_website_id)));
+    if !is_from {
+        return None;
+    }
+
+    let separator = if query.contains("&amp;") {
+        "&amp;"
+    } else {
+        "&"
+    };
+    let has_connector = params
+        .iter()
+        .any(|(_, param)| param.starts_with("connectorId="));
+    let params: Vec<String> = params
+        .into_iter()
+        .map(|(entity, param)| match param.split_once('=') {
+            Some(("websiteId", _)) => format!("{}websiteId={}", entity, to_website_id),
+            Some(("connectorId", _)) => format!("{}connectorId={}", entity, to_connector_id),
+            _ => format!("{}{}", entity, param),
+        })
+        .collect();
+    let mut query = params.join("&");
+    if !has_connector {
+        query.push_str(separator);
+        query.push_str("connectorId=");
+        query.push_str(to_connector_id);
+    }
+    Some(query)
+}
+
+/// Encode a path for a URL, as `%XX` sequences except the unreserved characters and `/`
+pub(crate) fn percent_encode_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~' | b'/') {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+/// Decode the `%XX` sequences of a URL path
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = text
+            .get(i + 1..i + 3)
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Sort JSON object keys recursively for stable serialization
+pub(crate) fn sort_json_keys(value: &serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut sorted: serde_json::Map<String, serde_json::Value> = serde_json::Map::new();
+            let mut keys: Vec<_> = map.keys().collect();
+            keys.sort();
+            for key in keys {
+                sorted.insert(key.clone(), sort_json_keys(&map[key]));
+            }
+            serde_json::Value::Object(sorted)
+        }
+        serde_json::Value::Array(arr) => {
+            serde_json::Value::Array(arr.iter().map(sort_json_keys).collect())
+        }
+        _ => value.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Website data with pages
+    fn website(pages: Vec<serde_json::Value>) -> WebsiteData {
+        WebsiteData {
+            pages,
+            ..Default::default()
+        }
+    }
+
+    /// Load website data from split files
+    async fn load(
+        files: &HashMap<String, String>,
+    ) -> ConnectorResult<(WebsiteData, IntegrityReport)> {
+        let website = &files[constants::WEBSITE_DATA_FILE];
+        load_website_data(website, |path| async move {
+            files
+                .get(&path)
+                .cloned()
+                .ok_or(ConnectorError::NotFound(path))
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn split_and_merge_round_trip() {
+        let data = website(vec![
+            json!({ "id": "home", "name": "Home", "frames": [{ "component": "a" }] }),
+            json!({ "id": "about", "name": "About us" }),
+        ]);
+
+        let files: HashMap<_, _> = split_website_data(&data, &PageIndex::new())
+            .unwrap()
+            .into_iter()
+            .collect();
+
+        assert!(files.contains_key("pages/home-home.json"));
+        assert!(files.contains_key("pages/about-us-about.json"));
+        let (loaded, report) = load(&files).await.unwrap();
+        assert!(!report.is_corrupted());
+        assert_eq!(loaded.pages, data.pages);
+    }
+
+    #[test]
+    fn split_keeps_the_file_of_renamed_pages() {
+        let index = PageIndex::from([("home".to_string(), "home-home.json".to_string())]);
+        let data = website(vec![
+            json!({ "id": "home", "name": "Welcome" }),
+            json!({ "id": "home-2", "name": "Home" }),
+        ]);
+
+        let files = split_website_data(&data, &index).unwrap();
+        let paths: Vec<_> = files.iter().map(|(path, _)| path.as_str()).collect();
+
+        assert_eq!(
+            paths,
+            [
+                "pages/home-home.json",
+                "pages/home-home-2.json",
+                constants::WEBSITE_DATA_FILE
+            ]
+        );
+        let website: serde_json::Value = serde_json::from_str(&files[2].1).unwrap();
+        assert_eq!(
+            website["pages"][0],
+            json!({ "id": "home", "name": "Welcome", "isFile": true, "file": "home-home.json" })
+        );
+        assert_eq!(website["schemaVersion"], CURRENT_SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn new_page_file_names_do_not_collide() {
+        let page = json!({ "id": "x", "name": "A" });
+        let used = HashSet::from(["a-x.json".to_string(), "a-x-2.json".to_string()]);
+
+        assert_eq!(new_page_file_name(&page, &used).unwrap(), "a-x-3.json");
+        assert_eq!(new_page_file_name(&json!({ "name": "A" }), &used), None);
+    }
+
+    #[test]
+    fn split_rejects_invalid_data() {
+        let data = website(vec![json!({ "id": "../x" })]);
+        assert!(matches!(
+            split_website_data(&data, &PageIndex::new()),
+            Err(ConnectorError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn page_file_names_stay_in_the_pages_folder() {
+        assert_eq!(
+            page_file_name(&json!({ "id": "a", "name": "A", "file": "../../website.json" })),
+            Some("a-a.json".to_string())
+        );
+        assert_eq!(
+            page_file_name(&json!({ "id": "a", "file": "kept.json" })),
+            Some("kept.json".to_string())
+        );
+        // References from before the page index
+        assert_eq!(
+            page_file_name(&json!({ "id": "a", "name": "My Page" })),
+            Some("my-page-a.json".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn load_reports_missing_and_invalid_pages() {
+        let files = HashMap::from([
+            (
+                constants::WEBSITE_DATA_FILE.to_string(),
+                json!({
+                    "schemaVersion": CURRENT_SCHEMA_VERSION,
+                    "pagesFolder": "pages",
+                    "pages": [
+                        { "id": "a", "name": "A", "isFile": true, "file": "a.json" },
+                        { "id": "b", "name": "B", "isFile": true, "file": "b.json" },
+                        { "id": "c", "name": "C", "isFile": true, "file": "c.json" },
+                    ],
+                })
+                .to_string(),
+            ),
+            ("pages/a.json".to_string(), json!({ "id": "a" }).to_string()),
+            ("pages/c.json".to_string(), "{ not json".to_string()),
+        ]);
+
+        let (data, report) = load(&files).await.unwrap();
+
+        assert!(report.is_corrupted());
+        assert_eq!(report.missing_pages.len(), 1);
+        assert_eq!(report.missing_pages[0].id, "b");
+        assert_eq!(report.missing_pages[0].file, "pages/b.json");
+        assert_eq!(report.invalid_pages.len(), 1);
+        assert_eq!(report.invalid_pages[0].id, "c");
+        assert!(report.invalid_pages[0].error.is_some());
+        // Pages which can not be loaded are kept as their reference
+        assert_eq!(data.pages[0], json!({ "id": "a" }));
+        assert_eq!(data.pages[1]["file"], "b.json");
+
+        let files = &files;
+        let website = &files[constants::WEBSITE_DATA_FILE];
+        let merged = merge_website_data(website, |path| async move {
+            files
+                .get(&path)
+                .cloned()
+                .ok_or(ConnectorError::NotFound(path))
+        })
+        .await;
+        assert!(matches!(merged, Err(ConnectorError::Corrupted(_))));
+    }
+
+    #[tokio::test]
+    async fn load_migrates_embedded_pages() {
+        let files = HashMap::from([(
+            constants::WEBSITE_DATA_FILE.to_string(),
+            json!({ "pages": [{ "id": "a" }], "styles": null }).to_string(),
+        )]);
+
+        let (data, report) = load(&files).await.unwrap();
+
+        assert!(!report.is_corrupted());
+        assert_eq!(data.pages, [json!({ "id": "a" })]);
+        assert_eq!(data.pages_folder, constants::LEGACY_WEBSITE_PAGES_FOLDER);
+        assert_eq!(data.schema_version, CURRENT_SCHEMA_VERSION);
+    }
+
+    #[tokio::test]
+    async fn load_fails_on_read_errors() {
+        let website = json!({
+            "pages": [{ "id": "a", "isFile": true, "file": "a.json" }],
+        })
+        .to_string();
+
+        let result = load_website_data(&website, |_| async {
+            Err::<String, _>(ConnectorError::Remote("down".to_string()))
+        })
+        .await;
+        assert!(matches!(result, Err(ConnectorError::Remote(_))));
+    }
+
+    #[test]
+    fn asset_path_of_stored_and_displayed_urls() {
+        assert_eq!(asset_path("/assets/a.png").as_deref(), Some("a.png"));
+        assert_eq!(
+            asset_path("assets/img/a%20b.png#x").as_deref(),
+            Some("img/a b.png")
+        );
+        assert_eq!(
+            asset_path("https://silex.test/api/website/assets/img/a.png?websiteId=1").as_deref(),
+            Some("img/a.png")
+        );
+        assert_eq!(asset_path("/assets/../website.json"), None);
+        assert_eq!(asset_path("/assets/"), None);
+        assert_eq!(asset_path("https://example.test/a.png"), None);
+    }
+
+    #[test]
+    fn referenced_assets_in_every_string() {
+        let data = WebsiteData {
+            assets: vec![json!({ "src": "/api/website/assets/a.png?websiteId=1" })],
+            styles: vec![json!({ "style": { "background-image": "url('/assets/b.png')" } })],
+            pages: vec![json!({ "id": "p", "html": "<img src=\"/assets/c.png\"> /assets/d.png" })],
+            ..Default::default()
+        };
+
+        let paths: Vec<_> = referenced_asset_paths(&data).into_iter().collect();
+        assert_eq!(paths, ["a.png", "b.png", "c.png", "d.png"]);
+    }
+
+    #[test]
+    fn unused_assets_are_not_found_in_the_data() {
+        let data = WebsiteData {
+            assets: vec![json!({ "src": "/assets/used.png" })],
+            // A URL of an unexpected form still keeps its asset
+            settings: json!({ "favicon": "//cdn.test/assets/icon.png", "og": "my file.png" }),
+            ..Default::default()
+        };
+        let paths = ["used.png", "icon.png", "my file.png", "unused.png"].map(String::from);
+
+        assert_eq!(unused_asset_paths(&data, paths).unwrap(), ["unused.png"]);
+    }
+}
@@ -12,12 +12,14 @@
 //! This module defines all error types used throughout the server.
 //! Errors are designed to be informative and map cleanly to HTTP status codes.
 
-use axum::http::StatusCode;
+use axum::http::{header, StatusCode};
 use axum::response::{IntoResponse, Response};
 use axum::Json;
 use serde_json::json;
 use thiserror::Error;
 
+use crate::models::IntegrityReport;
+
 /// Errors that can occur in connector operations
 ///
 /// Each variant maps to a specific HTTP status code for API responses.
@@ -42,6 +44,38 @@ pub enum ConnectorError {
     /// JSON parsing/serialization failed (HTTP 500)
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
+
+    /// The stored data changed since it was read (HTTP 409)
+    ///
+    /// Holds the current revision, so the client can reload and retry.
+    #[error("Conflict: the website was modified by someone else (current revision: {0})")]
+    Conflict(String),
+
+    /// The connector does not support this operation (HTTP 501)
+    #[error("Not supported: {0}")]
+    NotSupported(String),
+
+    /// An uploaded file or request is over the size limit (HTTP 413)
+    #[error("Payload too large: {0}")]
+    PayloadTooLarge(String),
+
+    /// An uploaded file's type is not allowed (HTTP 415)
+    #[error("Unsupported media type: {0}")]
+    UnsupportedMediaType(String),
+
+    /// A remote service (e.g. the GitLab API) failed (HTTP 502)
+    #[error("Remote service error: {0}")]
+    Remote(String),
+
+    /// The stored website has pages which can not be loaded (HTTP 500)
+    ///
+    /// Holds the report of the problems, the website needs to be repaired.
+    #[error(
+        "Website data is corrupted: {} missing and {} invalid page files",
+        .0.missing_pages.len(),
+        .0.invalid_pages.len()
+    )]
+    Corrupted(Box<IntegrityReport>),
 }
 
 impl ConnectorError {
@@ -53,6 +87,12 @@ impl ConnectorError {
             ConnectorError::InvalidInput(_) => StatusCode::BAD_REQUEST,
             ConnectorError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
             ConnectorError::Json(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ConnectorError::Conflict(_) => StatusCode::CONFLICT,
+            ConnectorError::NotSupported(_) => StatusCode::NOT_IMPLEMENTED,
+            ConnectorError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ConnectorError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ConnectorError::Remote(_) => StatusCode::BAD_GATEWAY,
+            ConnectorError::Corrupted(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
 }
@@ -71,12 +111,24 @@ impl IntoResponse for ConnectorError {
             tracing::error!("Server error: {}", message);
         }
 
-        let body = Json(json!({
+        let mut body = json!({
             "error": true,
             "message": message
-        }));
+        });
+
+        // Let the client know which revision it conflicts with
+        if let ConnectorError::Conflict(revision) = &self {
+            body["currentRevision"] = json!(revision);
+            let etag = format!("\"{}\"", revision);
+            return (status, [(header::ETAG, etag)], Json(body)).into_response();
+        }
+
+        // Let the client show what is wrong
+        if let ConnectorError::Corrupted(report) = &self {
+            body["integrity"] = json!(report);
+        }
 
-        (status, body).into_response()
+        (status, Json(body)).into_response()
     }
 }
 
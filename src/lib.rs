@@ -21,6 +21,7 @@ pub mod services;
 
 use std::sync::Arc;
 
+use axum::http::header;
 use axum::Router;
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::trace::TraceLayer;
@@ -28,10 +29,17 @@ use tower_sessions::{MemoryStore, SessionManagerLayer};
 
 // Re-export commonly used types for convenience
 pub use config::Config;
-pub use connectors::{ConnectorRegistry, FsHosting, FsStorage, HostingConnector, StorageConnector};
+pub use connectors::{
+    ConnectorRegistry, FsHosting, FsStorage, GitlabHosting, GitlabOptions, GitlabStorage,
+    HostingConnector, StorageConnector,
+};
 pub use error::ConnectorError;
 pub use models::{ConnectorType, WebsiteData, WebsiteMeta};
-pub use services::{configure_static_files, JobManager, StaticConfig};
+pub use services::{
+    configure_static_files, JobManager, StaticConfig, TemplateLibrary, ThumbnailCache,
+};
+
+use services::collect_all_unused_assets;
 
 /// Build the full application router, ready to be served.
 ///
@@ -51,10 +59,19 @@ pub async fn build_app(config: Config) -> (Router, u16) {
         static_routes: config.static_routes.clone(),
     };
 
+    let templates = config
+        .templates_path
+        .clone()
+        .map(|path| Arc::new(TemplateLibrary::new(path, config.assets_folder.clone())));
+
+    let thumbnails = Arc::new(ThumbnailCache::new(config.thumbnails_path()));
+
     let state = routes::AppState {
         config: Arc::new(config),
         registry: Arc::new(registry),
         job_manager: JobManager::new(),
+        templates,
+        thumbnails,
     };
 
     let app = Router::new()
@@ -70,17 +87,74 @@ pub async fn build_app(config: Config) -> (Router, u16) {
             CorsLayer::new()
                 .allow_origin(Any)
                 .allow_methods(Any)
-                .allow_headers(Any),
+                .allow_headers(Any)
+                // Let the editor read revisions for optimistic concurrency control
+                .expose_headers([header::ETAG]),
         );
 
     (app, port)
 }
 
+/// Interval between two purges of the trash and of the unused asset blobs
+const TRASH_PURGE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// Build the filesystem storage connector from config
+pub fn build_fs_storage(config: &Config) -> FsStorage {
+    FsStorage::new(config.data_path.clone(), config.assets_folder.clone())
+        .with_history(
+            config.history_max_versions,
+            chrono::Duration::days(config.history_max_age_days),
+        )
+        .with_trash(chrono::Duration::days(config.trash_retention_days))
+        .with_asset_deduplication(config.deduplicate_assets)
+}
+
+/// Purge the expired websites of the filesystem storage's trash, now and periodically
+///
+/// The asset blobs which are not used anymore are purged next.
+fn spawn_trash_purge(fs_storage: Arc<FsStorage>, purge_blobs: bool) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(TRASH_PURGE_INTERVAL);
+        loop {
+            interval.tick().await;
+            match fs_storage.purge_expired_trash().await {
+                Ok(0) => {}
+                Ok(purged) => tracing::info!("Purged {} expired websites from the trash", purged),
+                Err(e) => tracing::warn!("Failed to purge the trash: {}", e),
+            }
+            if !purge_blobs {
+                continue;
+            }
+            match fs_storage.purge_unused_blobs().await {
+                Ok(0) => {}
+                Ok(purged) => tracing::info!("Purged {} unused asset blobs", purged),
+                Err(e) => tracing::warn!("Failed to purge the asset blobs: {}", e),
+            }
+        }
+    });
+}
+
+/// Delete the unused assets of the filesystem storage's websites periodically
+fn spawn_assets_gc(fs_storage: Arc<FsStorage>, interval_hours: u64) {
+    tokio::spawn(async move {
+        let period = std::time::Duration::from_secs(interval_hours * 60 * 60);
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            match collect_all_unused_assets(fs_storage.as_ref(), &serde_json::json!({})).await {
+                Ok(0) => {}
+                Ok(deleted) => tracing::info!("Deleted {} unused assets", deleted),
+                Err(e) => tracing::warn!("Failed to delete the unused assets: {}", e),
+            }
+        }
+    });
+}
+
 /// Initialize storage and hosting connectors from config
 pub async fn init_connectors(config: &Config) -> ConnectorRegistry {
     let mut registry = ConnectorRegistry::new();
 
-    let fs_storage = FsStorage::new(config.data_path.clone(), config.assets_folder.clone());
+    let fs_storage = build_fs_storage(config);
     if let Err(e) = fs_storage.init(&config.default_website_id).await {
         tracing::warn!("Failed to initialize FsStorage: {}", e);
     }
@@ -90,8 +164,45 @@ pub async fn init_connectors(config: &Config) -> ConnectorRegistry {
         tracing::warn!("Failed to initialize FsHosting: {}", e);
     }
 
-    registry.register_storage(Arc::new(fs_storage));
+    let fs_storage = Arc::new(fs_storage);
+    if config.trash_retention_days > 0 || config.deduplicate_assets {
+        spawn_trash_purge(fs_storage.clone(), config.deduplicate_assets);
+    }
+    if config.assets_gc_interval_hours > 0 {
+        spawn_assets_gc(fs_storage.clone(), config.assets_gc_interval_hours);
+    }
+
+    registry.register_storage(fs_storage);
     registry.register_hosting(Arc::new(fs_hosting));
 
+    // GitLab connectors are enabled when an OAuth application is configured
+    if let (Some(client_id), Some(client_secret)) =
+        (&config.gitlab_client_id, &config.gitlab_client_secret)
+    {
+        let options = GitlabOptions {
+            domain: config.gitlab_domain.clone(),
+            client_id: client_id.clone(),
+            client_secret: client_secret.clone(),
+            server_url: config.url.clone(),
+            display_name: config.gitlab_display_name.clone(),
+        };
+        tracing::info!("GitLab connectors enabled for {}", options.domain);
+
+        registry.register_storage(Arc::new(GitlabStorage::new(
+            options.clone(),
+            config.assets_folder.clone(),
+        )));
+        registry.register_hosting(Arc::new(GitlabHosting::new(options)));
+    }
+
+    for command in &config.plugins {
+        if let Err(e) = registry
+            .register_plugin(command, config.plugin_timeout())
+            .await
+        {
+            tracing::warn!("Failed to start connector plugin '{}': {}", command, e);
+        }
+    }
+
     registry
 }
@@ -8,13 +8,18 @@
  */
 
 use std::net::SocketAddr;
+use std::sync::Arc;
 
 use tokio::net::TcpListener;
 use tower::Layer;
 use tower_http::normalize_path::NormalizePathLayer;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-use silex_server::Config;
+use serde::Serialize;
+use silex_server::error::ConnectorResult;
+use silex_server::models::JobStatus;
+use silex_server::services::{migrate_all_websites, transfer_website};
+use silex_server::{Config, ConnectorRegistry, JobManager, StorageConnector};
 
 #[tokio::main]
 async fn main() {
@@ -29,6 +34,13 @@ async fn main() {
 
     // Load configuration
     let config = Config::from_env();
+
+    // Maintenance commands run on the filesystem storage, then exit
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        std::process::exit(run_command(&config, &args).await);
+    }
+
     tracing::info!("Starting Silex server on {}", config.server_url());
 
     // Build the application
@@ -40,5 +52,111 @@ async fn main() {
     tracing::info!("Listening on {}", addr);
 
     let app = NormalizePathLayer::trim_trailing_slash().layer(app);
-    axum::serve(listener, axum::ServiceExt::<axum::extract::Request>::into_make_service(app)).await.unwrap();
+    axum::serve(
+        listener,
+        axum::ServiceExt::<axum::extract::Request>::into_make_service(app),
+    )
+    .await
+    .unwrap();
+}
+
+/// Run a maintenance command and return the exit code
+///
+/// - `check <websiteId>` - Print the integrity report of a website
+/// - `repair <websiteId>` - Rebuild a website's data from its page files
+/// - `migrate` - Migrate all websites to the current schema version
+/// - `transfer <websiteId> <fromConnectorId> <toConnectorId> [--move]` - Copy or move
+///   a website between the filesystem storage and the connector plugins
+async fn run_command(config: &Config, args: &[String]) -> i32 {
+    let storage = silex_server::build_fs_storage(config);
+    let session = serde_json::json!({});
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    match args.as_slice() {
+        ["check", website_id] => print_result(
+            storage
+                .check_integrity(&session, &website_id.to_string())
+                .await,
+            |report| !report.is_corrupted(),
+        ),
+        ["repair", website_id] => print_result(
+            storage
+                .repair_website(&session, &website_id.to_string())
+                .await,
+            |report| !report.is_corrupted(),
+        ),
+        ["migrate"] => print_result(migrate_all_websites(&storage, &session).await, |report| {
+            report.failed.is_empty()
+        }),
+        ["transfer", website_id, from, to, options @ ..]
+            if options.is_empty() || options == ["--move"] =>
+        {
+            // Connectors which need no user session
+            let mut registry = ConnectorRegistry::new();
+            registry.register_storage(Arc::new(storage));
+            for command in &config.plugins {
+                if let Err(e) = registry
+                    .register_plugin(command, config.plugin_timeout())
+                    .await
+                {
+                    eprintln!("Failed to start connector plugin '{}': {}", command, e);
+                }
+            }
+            let (Some(from), Some(to)) = (
+                registry.get_storage_connector(from),
+                registry.get_storage_connector(to),
+            ) else {
+                eprintln!("Unknown storage connector");
+                return 2;
+            };
+
+            let job_manager = JobManager::new();
+            let mut job = job_manager.start_job(format!("Transfer of website {}", website_id));
+            let result = transfer_website(
+                from.as_ref(),
+                to.as_ref(),
+                &session,
+                &website_id.to_string(),
+                !options.is_empty(),
+                &mut job,
+                &job_manager,
+            )
+            .await;
+            match result {
+                Ok(new_website_id) => {
+                    job.success(format!("Website copied with ID: {}", new_website_id))
+                }
+                Err(e) => job.fail(format!("Transfer failed: {}", e)),
+            }
+            print_result(Ok(job), |job| job.base.status == JobStatus::Success)
+        }
+        _ => {
+            eprintln!(
+                "Usage: silex-server [check <websiteId> | repair <websiteId> | migrate \
+                 | transfer <websiteId> <fromConnectorId> <toConnectorId> [--move]]"
+            );
+            2
+        }
+    }
+}
+
+/// Print the result of a command as JSON and return the exit code
+fn print_result<T: Serialize>(result: ConnectorResult<T>, success: impl Fn(&T) -> bool) -> i32 {
+    match result {
+        Ok(value) => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&value).unwrap_or_default()
+            );
+            if success(&value) {
+                0
+            } else {
+                1
+            }
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
 }
@@ -0,0 +1,127 @@
+/*
+ * Silex website builder, free/libre no-code tool for makers.
+ * Copyright (c) 2023 lexoyo and Silex Labs foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or any later version.
+ */
+
+//! Data models for the differences between two website versions
+
+use serde::{Deserialize, Serialize};
+
+/// Structural differences between two versions of a website's data
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebsiteDiff {
+    /// Changes in the pages
+    pub pages: PagesDiff,
+
+    /// Changes in the CSS rules
+    pub styles: StylesDiff,
+
+    /// Changes in the assets list
+    pub assets: AssetsDiff,
+
+    /// Changed website settings, by key
+    pub settings: Vec<ValueChange>,
+}
+
+/// A page, identified by its ID
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PageSummary {
+    /// Page ID
+    pub id: String,
+
+    /// Page name
+    pub name: String,
+}
+
+/// A page whose name changed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PageRename {
+    /// Page ID
+    pub id: String,
+
+    /// Name before
+    pub from: String,
+
+    /// Name after
+    pub to: String,
+}
+
+/// Changes in the pages
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PagesDiff {
+    /// Pages only in the new version
+    pub added: Vec<PageSummary>,
+
+    /// Pages only in the old version
+    pub removed: Vec<PageSummary>,
+
+    /// Pages whose name changed
+    pub renamed: Vec<PageRename>,
+
+    /// Pages whose content changed (a renamed page may also be modified)
+    pub modified: Vec<PageSummary>,
+}
+
+/// A CSS rule whose declarations changed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StyleChange {
+    /// The rule without its declarations (selectors, state, media query)
+    pub rule: serde_json::Value,
+
+    /// Declarations before
+    pub from: serde_json::Value,
+
+    /// Declarations after
+    pub to: serde_json::Value,
+}
+
+/// Changes in the CSS rules
+///
+/// Rules are identified by everything but their declarations
+/// (selectors, state, media query).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StylesDiff {
+    /// Rules only in the new version
+    pub added: Vec<serde_json::Value>,
+
+    /// Rules only in the old version
+    pub removed: Vec<serde_json::Value>,
+
+    /// Rules whose declarations changed
+    pub changed: Vec<StyleChange>,
+}
+
+/// Changes in the assets list, identified by their `src`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetsDiff {
+    /// Assets only in the new version
+    pub added: Vec<String>,
+
+    /// Assets only in the old version
+    pub removed: Vec<String>,
+}
+
+/// A changed value, absent on one side when added or removed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValueChange {
+    /// Key of the value
+    pub key: String,
+
+    /// Value before
+    pub from: Option<serde_json::Value>,
+
+    /// Value after
+    pub to: Option<serde_json::Value>,
+}
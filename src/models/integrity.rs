@@ -0,0 +1,58 @@
+/*
+ * Silex website builder, free/libre no-code tool for makers.
+ * Copyright (c) 2023 lexoyo and Silex Labs foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or any later version.
+ */
+
+//! Data models for the integrity check of a stored website
+
+use serde::{Deserialize, Serialize};
+
+/// A page file which could not be loaded
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PageIssue {
+    /// Page ID, from the reference in website.json
+    pub id: String,
+
+    /// Page name, from the reference in website.json
+    pub name: String,
+
+    /// Path of the page file, relative to the website root
+    pub file: String,
+
+    /// Why the file could not be loaded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Problems found in the files of a stored website
+///
+/// Missing and invalid pages make the website unusable: opening it would
+/// show blank pages, and saving it would lose their content.
+/// Orphan files are only a waste of space.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntegrityReport {
+    /// Pages referenced by website.json whose file does not exist
+    pub missing_pages: Vec<PageIssue>,
+
+    /// Pages referenced by website.json whose file is not valid JSON
+    pub invalid_pages: Vec<PageIssue>,
+
+    /// Files of the pages folder which website.json does not reference
+    pub orphan_pages: Vec<String>,
+
+    /// Files of the assets folder which the website data does not reference
+    pub orphan_assets: Vec<String>,
+}
+
+impl IntegrityReport {
+    /// Whether pages can not be loaded
+    pub fn is_corrupted(&self) -> bool {
+        !self.missing_pages.is_empty() || !self.invalid_pages.is_empty()
+    }
+}
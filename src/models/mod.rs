@@ -12,9 +12,15 @@
 //! These types match the TypeScript types to ensure API compatibility.
 
 mod connector;
+mod diff;
+mod integrity;
 mod job;
+mod template;
 mod website;
 
 pub use connector::*;
+pub use diff::*;
+pub use integrity::*;
 pub use job::*;
+pub use template::*;
 pub use website::*;
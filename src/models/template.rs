@@ -0,0 +1,41 @@
+/*
+ * Silex website builder, free/libre no-code tool for makers.
+ * Copyright (c) 2023 lexoyo and Silex Labs foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or any later version.
+ */
+
+//! Data models for website templates
+
+use serde::{Deserialize, Serialize};
+
+/// Where a template is stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TemplateSource {
+    /// The server's template directory
+    Library,
+
+    /// A website of the storage connector, marked as a template
+    Website,
+}
+
+/// A template new websites can be created from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Template {
+    /// ID to pass as `templateId` when creating a website
+    pub template_id: String,
+
+    /// Human-readable template name
+    pub name: String,
+
+    /// Preview image URL
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_url: Option<String>,
+
+    /// Where the template is stored
+    pub source: TemplateSource,
+}
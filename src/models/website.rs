@@ -11,15 +11,19 @@
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use tokio::io::{AsyncRead, AsyncSeek};
 
 /// Unique identifier for a website
 pub type WebsiteId = String;
 
 /// Content stored in meta.json file
 ///
-/// This is the metadata saved alongside the website data.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// This is the metadata saved alongside the website data. The creation and
+/// modification fields are maintained by the storage connectors, values sent
+/// by the frontend are ignored.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct WebsiteMetaFileContent {
     /// Human-readable website name
@@ -32,6 +36,47 @@ pub struct WebsiteMetaFileContent {
     /// Per-connector settings for this website
     #[serde(default)]
     pub connector_user_settings: HashMap<String, serde_json::Value>,
+
+    /// Whether new websites can be created from this one (see `services::templates`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_template: bool,
+
+    /// When the website was created
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+
+    /// When the website was last modified
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime<Utc>>,
+
+    /// User who created the website
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<String>,
+
+    /// User who last modified the website
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_by: Option<String>,
+}
+
+impl WebsiteMetaFileContent {
+    /// Revision of this metadata, used for optimistic concurrency control
+    ///
+    /// The creation and modification fields are left out, so that saving the
+    /// website data does not make a concurrent metadata save fail.
+    pub fn revision(&self) -> String {
+        revision_of(&self.without_history())
+    }
+
+    /// Copy of this metadata without the creation and modification fields
+    pub fn without_history(&self) -> Self {
+        WebsiteMetaFileContent {
+            created_at: None,
+            updated_at: None,
+            created_by: None,
+            updated_by: None,
+            ..self.clone()
+        }
+    }
 }
 
 /// Website metadata returned to the frontend
@@ -55,6 +100,10 @@ pub struct WebsiteMeta {
     #[serde(default)]
     pub connector_user_settings: HashMap<String, serde_json::Value>,
 
+    /// Whether new websites can be created from this one
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub is_template: bool,
+
     /// When the website was created
     #[serde(skip_serializing_if = "Option::is_none")]
     pub created_at: Option<DateTime<Utc>>,
@@ -63,6 +112,14 @@ pub struct WebsiteMeta {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub updated_at: Option<DateTime<Utc>>,
 
+    /// User who created the website
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<String>,
+
+    /// User who last modified the website
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_by: Option<String>,
+
     /// Path/URL to source files (maps to GitLab's repoUrl)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub repo_url: Option<String>,
@@ -70,10 +127,17 @@ pub struct WebsiteMeta {
     /// Path/URL to published site (maps to GitLab's pagesUrl)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub pages_url: Option<String>,
+
+    /// Revision of the metadata, to send back in `If-Match` when saving
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<String>,
 }
 
 impl WebsiteMeta {
     /// Create WebsiteMeta from file content and additional fields
+    ///
+    /// The times stored in the file content take precedence over `created_at`
+    /// and `updated_at`, which are those of the storage.
     pub fn from_file_content(
         website_id: WebsiteId,
         content: WebsiteMetaFileContent,
@@ -85,14 +149,37 @@ impl WebsiteMeta {
             name: content.name,
             image_url: content.image_url,
             connector_user_settings: content.connector_user_settings,
-            created_at,
-            updated_at,
+            is_template: content.is_template,
+            created_at: content.created_at.or(created_at),
+            updated_at: content.updated_at.or(updated_at),
+            created_by: content.created_by,
+            updated_by: content.updated_by,
             repo_url: None,
             pages_url: None,
+            revision: None,
+        }
+    }
+
+    /// Get the part of the metadata which is stored in meta.json
+    pub fn to_file_content(&self) -> WebsiteMetaFileContent {
+        WebsiteMetaFileContent {
+            name: self.name.clone(),
+            image_url: self.image_url.clone(),
+            connector_user_settings: self.connector_user_settings.clone(),
+            is_template: self.is_template,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+            created_by: self.created_by.clone(),
+            updated_by: self.updated_by.clone(),
         }
     }
 }
 
+/// Version of the website data schema written by this server
+///
+/// See `services::schema` for the migrations from previous versions.
+pub const CURRENT_SCHEMA_VERSION: u32 = 2;
+
 /// Full website data including pages, styles, and assets
 ///
 /// This is the main data structure stored in website.json.
@@ -101,6 +188,13 @@ impl WebsiteMeta {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct WebsiteData {
+    /// Version of the schema of this data
+    ///
+    /// Data sent by the editor has the current version. Stored data without
+    /// version is migrated when read (see `services::schema`).
+    #[serde(default = "current_schema_version")]
+    pub schema_version: u32,
+
     /// Array of page objects (GrapesJS Page format)
     #[serde(default)]
     pub pages: Vec<serde_json::Value>,
@@ -138,10 +232,15 @@ fn default_pages_folder() -> String {
     "pages".to_string()
 }
 
+fn current_schema_version() -> u32 {
+    CURRENT_SCHEMA_VERSION
+}
+
 impl Default for WebsiteData {
     fn default() -> Self {
         // Match EMPTY_WEBSITE from TypeScript
         WebsiteData {
+            schema_version: CURRENT_SCHEMA_VERSION,
             pages: vec![serde_json::json!({})], // GrapesJS expects this to create an empty page
             pages_folder: "pages".to_string(),
             assets: Vec::new(),
@@ -154,6 +253,176 @@ impl Default for WebsiteData {
     }
 }
 
+impl WebsiteData {
+    /// Revision of this data, used for optimistic concurrency control
+    ///
+    /// Two saves of the same content have the same revision,
+    /// whichever connector stores them.
+    pub fn revision(&self) -> String {
+        revision_of(self)
+    }
+
+    /// Get a page by its ID
+    pub fn page(&self, page_id: &str) -> Option<&serde_json::Value> {
+        self.pages
+            .iter()
+            .find(|page| page_id_of(page) == Some(page_id))
+    }
+}
+
+/// Get the ID of a page
+pub fn page_id_of(page: &serde_json::Value) -> Option<&str> {
+    page.get("id").and_then(|id| id.as_str())
+}
+
+/// Revision of a page, used for optimistic concurrency control of page saves
+pub fn page_revision(page: &serde_json::Value) -> String {
+    revision_of(page)
+}
+
+/// Compute a revision as the SHA-256 of the JSON serialization
+///
+/// The value goes through `serde_json::Value` first, whose objects sort their keys,
+/// so the serialization is stable even for `HashMap` fields.
+fn revision_of<T: Serialize>(value: &T) -> String {
+    let json = serde_json::to_value(value)
+        .and_then(|value| serde_json::to_vec(&value))
+        .unwrap_or_default();
+    Sha256::digest(&json)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// A snapshot of a website's data in its history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebsiteVersion {
+    /// Unique identifier of this version within the website
+    pub version_id: String,
+
+    /// When this version was saved
+    pub created_at: DateTime<Utc>,
+
+    /// Revision of the website data (see `WebsiteData::revision`)
+    pub revision: String,
+
+    /// Size of the snapshot in bytes
+    pub size: u64,
+}
+
+/// A deleted website kept in the trash
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashedWebsite {
+    /// ID of the trash entry, to restore or purge it
+    pub trash_id: String,
+
+    /// ID the website had, and gets back when restored
+    pub website_id: WebsiteId,
+
+    /// Human-readable website name
+    pub name: String,
+
+    /// When the website was deleted
+    pub deleted_at: DateTime<Utc>,
+
+    /// When the website will be purged from the trash
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Result of the migration of all the websites of a storage connector
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationReport {
+    /// Websites which were migrated to the current schema version
+    pub migrated: Vec<WebsiteId>,
+
+    /// Websites which already had the current schema version
+    pub up_to_date: Vec<WebsiteId>,
+
+    /// Websites which could not be migrated
+    pub failed: Vec<MigrationFailure>,
+}
+
+/// A website which could not be migrated
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationFailure {
+    /// ID of the website
+    pub website_id: WebsiteId,
+
+    /// Why the migration failed
+    pub error: String,
+}
+
+/// An asset file of a website
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetInfo {
+    /// Path in the assets folder, with a leading slash as returned by `write_assets`
+    pub path: String,
+
+    /// Size of the file in bytes
+    pub size: u64,
+
+    /// MIME type, guessed from the file extension
+    pub content_type: String,
+
+    /// When the file was last modified
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modified_at: Option<DateTime<Utc>>,
+}
+
+/// Assets which a website's data does not use
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnusedAssetsReport {
+    /// Unused assets, except the recent ones which the editor may not have saved yet
+    pub assets: Vec<AssetInfo>,
+
+    /// Total size of the unused assets in bytes
+    pub total_size: u64,
+
+    /// Whether the unused assets were deleted
+    pub deleted: bool,
+}
+
+/// An uploaded image, with the copies made of it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageInfo {
+    /// Asset URL of the image, as in the `data` of the upload response
+    pub src: String,
+
+    /// Width in pixels, after downscaling
+    pub width: u32,
+
+    /// Height in pixels, after downscaling
+    pub height: u32,
+
+    /// Smaller and WebP copies of the image, for `srcset`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variants: Vec<ImageVariant>,
+}
+
+/// A copy of an uploaded image, stored next to it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageVariant {
+    /// Asset URL of the copy
+    pub src: String,
+
+    /// Width in pixels
+    pub width: u32,
+
+    /// Height in pixels
+    pub height: u32,
+
+    /// MIME type of the copy
+    pub content_type: String,
+}
+
 /// A file to be written to storage or hosting
 #[derive(Debug, Clone)]
 pub struct ConnectorFile {
@@ -164,6 +433,43 @@ pub struct ConnectorFile {
     pub content: Vec<u8>,
 }
 
+/// Content of an asset file, which can be read from any position
+pub trait AssetReader: AsyncRead + AsyncSeek + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncSeek + Send + Unpin> AssetReader for T {}
+
+/// An asset file opened for reading, to be streamed
+pub struct AssetStream {
+    /// Size of the file in bytes
+    pub size: u64,
+
+    /// When the file was last modified
+    pub modified_at: Option<DateTime<Utc>>,
+
+    /// Entity tag of the content, which changes with it (without quotes)
+    pub etag: String,
+
+    /// Content of the file
+    pub reader: Box<dyn AssetReader>,
+}
+
+impl AssetStream {
+    /// Make a stream of an asset read in memory, tagged with the hash of its content
+    pub fn from_content(content: Vec<u8>, modified_at: Option<DateTime<Utc>>) -> Self {
+        let etag = Sha256::digest(&content)
+            .iter()
+            .take(16)
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        AssetStream {
+            size: content.len() as u64,
+            modified_at,
+            etag,
+            reader: Box::new(std::io::Cursor::new(content)),
+        }
+    }
+}
+
 /// Constants matching TypeScript constants.ts
 pub mod constants {
     /// Main website data file
@@ -0,0 +1,392 @@
+/*
+ * Silex website builder, free/libre no-code tool for makers.
+ * Copyright (c) 2023 lexoyo and Silex Labs foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or any later version.
+ */
+
+//! HTTP delivery of asset files
+//!
+//! Assets are streamed with validators, so that browsers and the editor only
+//! download them again when they changed:
+//! - `ETag` and `Last-Modified`, checked against `If-None-Match` and
+//!   `If-Modified-Since` (304 Not Modified)
+//! - `Cache-Control: no-cache`, as asset paths are not versioned: a cached
+//!   asset is revalidated before being used
+//!
+//! Single byte ranges are supported (`Range: bytes=0-1023`, 206 Partial
+//! Content), so that videos can be seeked. `If-Range` is honored, and
+//! requests for several ranges get the whole file.
+//!
+//! Browsers are told not to guess the type of the assets (`nosniff`), and SVG
+//! images, which can hold scripts, are sandboxed when opened directly.
+
+use axum::body::Body;
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, Utc};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+
+use crate::error::ConnectorResult;
+use crate::models::AssetStream;
+
+/// Cache policy of the assets: cached, but revalidated before use
+const CACHE_CONTROL: &str = "no-cache";
+
+/// Policy of the SVG images: no script, no form, no same origin access
+const SVG_CONTENT_SECURITY_POLICY: &str = "sandbox";
+
+/// Part of an asset to send
+#[derive(Debug, PartialEq, Eq)]
+enum ByteRange {
+    /// The whole file
+    Full,
+
+    /// From the first to the last byte, included
+    Partial(u64, u64),
+
+    /// The range starts after the end of the file
+    Unsatisfiable,
+}
+
+/// Build the response to an asset request
+///
+/// Answers 304 when the client's copy is current, 206 for a byte range,
+/// 416 for a range out of the file, and 200 with the whole file otherwise.
+pub(super) async fn asset_response(
+    headers: &HeaderMap,
+    mut asset: AssetStream,
+    content_type: String,
+) -> ConnectorResult<Response> {
+    let mut response_headers = validator_headers(&asset.etag, asset.modified_at);
+    response_headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+
+    if is_not_modified(headers, &asset.etag, asset.modified_at) {
+        return Ok((StatusCode::NOT_MODIFIED, response_headers).into_response());
+    }
+
+    response_headers.insert(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+    if content_type == "image/svg+xml" {
+        response_headers.insert(
+            header::CONTENT_SECURITY_POLICY,
+            HeaderValue::from_static(SVG_CONTENT_SECURITY_POLICY),
+        );
+    }
+    if let Ok(content_type) = HeaderValue::from_str(&content_type) {
+        response_headers.insert(header::CONTENT_TYPE, content_type);
+    }
+
+    let (status, start, length) = match byte_range(headers, &asset) {
+        ByteRange::Full => (StatusCode::OK, 0, asset.size),
+        ByteRange::Partial(start, end) => {
+            let content_range = format!("bytes {}-{}/{}", start, end, asset.size);
+            response_headers.insert(header::CONTENT_RANGE, header_value(&content_range));
+            (StatusCode::PARTIAL_CONTENT, start, end - start + 1)
+        }
+        ByteRange::Unsatisfiable => {
+            let content_range = format!("bytes */{}", asset.size);
+            response_headers.insert(header::CONTENT_RANGE, header_value(&content_range));
+            return Ok((StatusCode::RANGE_NOT_SATISFIABLE, response_headers).into_response());
+        }
+    };
+
+    if start > 0 {
+        asset.reader.seek(std::io::SeekFrom::Start(start)).await?;
+    }
+    response_headers.insert(header::CONTENT_LENGTH, header_value(&length.to_string()));
+    let body = Body::from_stream(ReaderStream::new(asset.reader.take(length)));
+
+    Ok((status, response_headers, body).into_response())
+}
+
+/// Whether the client's copy of an asset is current, according to the
+/// conditional headers of the request
+///
+/// `If-None-Match` takes precedence over `If-Modified-Since`.
+pub(super) fn is_not_modified(
+    headers: &HeaderMap,
+    etag: &str,
+    modified_at: Option<DateTime<Utc>>,
+) -> bool {
+    if let Some(if_none_match) = header_str(headers, header::IF_NONE_MATCH) {
+        return if_none_match
+            .split(',')
+            .map(str::trim)
+            .any(|tag| tag == "*" || unquote(tag.trim_start_matches("W/")) == etag);
+    }
+
+    match (
+        header_str(headers, header::IF_MODIFIED_SINCE).and_then(parse_http_date),
+        modified_at,
+    ) {
+        (Some(since), Some(modified_at)) => modified_at.timestamp() <= since.timestamp(),
+        _ => false,
+    }
+}
+
+/// Build the `ETag`, `Last-Modified` and `Cache-Control` headers
+pub(super) fn validator_headers(etag: &str, modified_at: Option<DateTime<Utc>>) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(header::ETAG, header_value(&format!("\"{}\"", etag)));
+    if let Some(modified_at) = modified_at {
+        headers.insert(
+            header::LAST_MODIFIED,
+            header_value(&format_http_date(modified_at)),
+        );
+    }
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static(CACHE_CONTROL),
+    );
+    headers
+}
+
+/// Get the part of an asset asked for by the `Range` header
+///
+/// Ranges which can not be parsed are ignored, as the HTTP specification asks.
+fn byte_range(headers: &HeaderMap, asset: &AssetStream) -> ByteRange {
+    let Some(range) = header_str(headers, header::RANGE) else {
+        return ByteRange::Full;
+    };
+
+    // The range is only valid for the version of the file the client has
+    if let Some(if_range) = header_str(headers, header::IF_RANGE) {
+        let current = if if_range.starts_with('"') {
+            unquote(if_range) == asset.etag
+        } else {
+            match (parse_http_date(if_range), asset.modified_at) {
+                (Some(date), Some(modified_at)) => date.timestamp() == modified_at.timestamp(),
+                _ => false,
+            }
+        };
+        if !current {
+            return ByteRange::Full;
+        }
+    }
+
+    let Some(spec) = range.trim().strip_prefix("bytes=") else {
+        return ByteRange::Full;
+    };
+    // Several ranges would need a multipart response, the whole file is fine too
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return ByteRange::Full;
+    };
+
+    let size = asset.size;
+    match (start.parse::<u64>(), end.parse::<u64>()) {
+        // Last bytes of the file
+        (Err(_), Ok(suffix)) if start.is_empty() => {
+            if suffix == 0 || size == 0 {
+                ByteRange::Unsatisfiable
+            } else {
+                ByteRange::Partial(size.saturating_sub(suffix), size - 1)
+            }
+        }
+        (Ok(start), Err(_)) if end.is_empty() => {
+            if start >= size {
+                ByteRange::Unsatisfiable
+            } else {
+                ByteRange::Partial(start, size - 1)
+            }
+        }
+        (Ok(start), Ok(end)) if start <= end => {
+            if start >= size {
+                ByteRange::Unsatisfiable
+            } else {
+                ByteRange::Partial(start, end.min(size - 1))
+            }
+        }
+        _ => ByteRange::Full,
+    }
+}
+
+/// Get a header as a string
+fn header_str(headers: &HeaderMap, name: header::HeaderName) -> Option<&str> {
+    headers.get(name)?.to_str().ok()
+}
+
+/// Build a header value from a string known to be valid
+fn header_value(value: &str) -> HeaderValue {
+    HeaderValue::from_str(value).unwrap_or_else(|_| HeaderValue::from_static(""))
+}
+
+/// Remove the quotes around an entity tag
+fn unquote(tag: &str) -> &str {
+    tag.trim().trim_matches('"')
+}
+
+/// Format a date for HTTP headers, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`
+fn format_http_date(date: DateTime<Utc>) -> String {
+    date.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// Parse a date of an HTTP header
+fn parse_http_date(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc2822(value.trim())
+        .ok()
+        .map(|date| date.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// An asset of 10 bytes, modified at a known time
+    fn asset() -> AssetStream {
+        let modified_at = Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap();
+        AssetStream::from_content(b"0123456789".to_vec(), Some(modified_at))
+    }
+
+    /// Request headers
+    fn headers(pairs: &[(header::HeaderName, &str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.clone(), HeaderValue::from_str(value).unwrap()))
+            .collect()
+    }
+
+    /// The byte range of the asset for a `Range` header
+    fn range(value: &str) -> ByteRange {
+        byte_range(&headers(&[(header::RANGE, value)]), &asset())
+    }
+
+    #[test]
+    fn byte_range_forms() {
+        assert_eq!(byte_range(&HeaderMap::new(), &asset()), ByteRange::Full);
+        assert_eq!(range("bytes=2-5"), ByteRange::Partial(2, 5));
+        assert_eq!(range("bytes=4-"), ByteRange::Partial(4, 9));
+        assert_eq!(range("bytes=-3"), ByteRange::Partial(7, 9));
+        // Ends after the end of the file are clamped
+        assert_eq!(range("bytes=8-100"), ByteRange::Partial(8, 9));
+        assert_eq!(range("bytes=-100"), ByteRange::Partial(0, 9));
+    }
+
+    #[test]
+    fn byte_range_unsatisfiable() {
+        assert_eq!(range("bytes=10-"), ByteRange::Unsatisfiable);
+        assert_eq!(range("bytes=10-20"), ByteRange::Unsatisfiable);
+        assert_eq!(range("bytes=-0"), ByteRange::Unsatisfiable);
+    }
+
+    #[test]
+    fn byte_range_ignores_invalid_and_multiple_ranges() {
+        assert_eq!(range("bytes=5-2"), ByteRange::Full);
+        assert_eq!(range("bytes=a-b"), ByteRange::Full);
+        assert_eq!(range("items=0-1"), ByteRange::Full);
+        assert_eq!(range("bytes=0-1,4-5"), ByteRange::Full);
+        assert_eq!(range("bytes=-"), ByteRange::Full);
+    }
+
+    #[test]
+    fn byte_range_honors_if_range() {
+        let asset = asset();
+        let etag = format!("\"{}\"", asset.etag);
+        let date = format_http_date(asset.modified_at.unwrap());
+
+        let with_if_range = |if_range: &str| {
+            byte_range(
+                &headers(&[(header::RANGE, "bytes=0-1"), (header::IF_RANGE, if_range)]),
+                &asset,
+            )
+        };
+        assert_eq!(with_if_range(&etag), ByteRange::Partial(0, 1));
+        assert_eq!(with_if_range(&date), ByteRange::Partial(0, 1));
+        assert_eq!(with_if_range("\"other\""), ByteRange::Full);
+        assert_eq!(
+            with_if_range("Mon, 01 Jan 2024 00:00:00 GMT"),
+            ByteRange::Full
+        );
+    }
+
+    #[test]
+    fn not_modified_by_etag_or_date() {
+        let asset = asset();
+        let modified_at = asset.modified_at;
+        let not_modified = |pairs: &[(header::HeaderName, &str)]| {
+            is_not_modified(&headers(pairs), &asset.etag, modified_at)
+        };
+        let etag = format!("\"{}\"", asset.etag);
+
+        assert!(!not_modified(&[]));
+        assert!(not_modified(&[(header::IF_NONE_MATCH, &etag)]));
+        assert!(not_modified(&[(
+            header::IF_NONE_MATCH,
+            &format!("W/{}", etag)
+        )]));
+        assert!(not_modified(&[(header::IF_NONE_MATCH, "\"a\", *")]));
+        assert!(!not_modified(&[(header::IF_NONE_MATCH, "\"a\"")]));
+        assert!(not_modified(&[(
+            header::IF_MODIFIED_SINCE,
+            "Tue, 02 Jan 2024 03:04:05 GMT"
+        )]));
+        assert!(!not_modified(&[(
+            header::IF_MODIFIED_SINCE,
+            "Tue, 02 Jan 2024 03:04:04 GMT"
+        )]));
+        // If-None-Match takes precedence
+        assert!(!not_modified(&[
+            (header::IF_NONE_MATCH, "\"a\""),
+            (header::IF_MODIFIED_SINCE, "Tue, 02 Jan 2024 03:04:05 GMT"),
+        ]));
+    }
+
+    #[tokio::test]
+    async fn partial_content_response() {
+        let response = asset_response(
+            &headers(&[(header::RANGE, "bytes=3-5")]),
+            asset(),
+            "text/plain".to_string(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes 3-5/10");
+        assert_eq!(response.headers()[header::CONTENT_LENGTH], "3");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"345");
+    }
+
+    #[tokio::test]
+    async fn svg_response_is_sandboxed() {
+        let response = asset_response(&HeaderMap::new(), asset(), "image/svg+xml".to_string())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_SECURITY_POLICY],
+            "sandbox"
+        );
+        assert_eq!(
+            response.headers()[header::X_CONTENT_TYPE_OPTIONS],
+            "nosniff"
+        );
+    }
+
+    #[tokio::test]
+    async fn unsatisfiable_range_response() {
+        let response = asset_response(
+            &headers(&[(header::RANGE, "bytes=20-")]),
+            asset(),
+            "text/plain".to_string(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(response.headers()[header::CONTENT_RANGE], "bytes */10");
+    }
+}
@@ -25,11 +25,19 @@ use axum::{Json, Router};
 use serde::{Deserialize, Serialize};
 use tower_sessions::Session;
 
-use crate::connectors::{hosting_to_connector_data, to_connector_data};
+use crate::connectors::{
+    clear_session_user, hosting_to_connector_data, set_session_user, to_connector_data,
+};
 use crate::error::{ConnectorError, ConnectorResult};
 use crate::models::{ConnectorData, ConnectorOptions, ConnectorType, ConnectorUser};
 use crate::routes::AppState;
 
+/// Key of the OAuth state in the session
+///
+/// The login sends a random state to the OAuth provider, and the callback only
+/// accepts the one it gets back, so that nobody can log a user into their account.
+const OAUTH_STATE_KEY: &str = "oauthState";
+
 /// Build connector routes
 pub fn routes() -> Router<AppState> {
     Router::new()
@@ -209,7 +217,7 @@ async fn login(
 
             // Check for OAuth URL
             if let Some(oauth_url) = connector.get_oauth_url(&session_data).await? {
-                return Ok(Redirect::to(&oauth_url).into_response());
+                return start_oauth(&session, &oauth_url).await;
             }
 
             // For FsStorage and similar, no login needed - redirect to callback
@@ -230,7 +238,7 @@ async fn login(
 
             // Check for OAuth URL
             if let Some(oauth_url) = connector.get_oauth_url(&session_data).await? {
-                return Ok(Redirect::to(&oauth_url).into_response());
+                return start_oauth(&session, &oauth_url).await;
             }
 
             // For FsHosting and similar, no login needed - redirect to callback
@@ -264,6 +272,16 @@ async fn login_callback(
 
     let connector_id = query.connector_id.as_deref().unwrap_or("");
     let mut session_data = get_session_data(&session).await;
+    let invalid_state = || {
+        Html(get_end_auth_html(
+            "Invalid login state, please log in again",
+            true,
+            connector_id,
+            query.connector_type,
+            None,
+            None,
+        ))
+    };
 
     // Process the callback based on connector type
     let options = match query.connector_type {
@@ -277,11 +295,19 @@ async fn login_callback(
 
             // Store token if not already logged in
             if !connector.is_logged_in(&session_data).await? {
+                let is_oauth = connector.get_oauth_url(&session_data).await?.is_some();
+                if is_oauth && !check_oauth_state(&session, query.state.as_deref()).await {
+                    return Ok(invalid_state());
+                }
                 let token = serde_json::json!({
                     "code": query.code,
                     "state": query.state,
                 });
                 connector.set_token(&mut session_data, &token).await?;
+                // The user is the author of the changes made in this session
+                if let Ok(user) = connector.get_user(&session_data).await {
+                    set_session_user(&mut session_data, connector.connector_id(), &user.name);
+                }
                 save_session_data(&session, &session_data).await;
             }
 
@@ -297,11 +323,19 @@ async fn login_callback(
 
             // Store token if not already logged in
             if !connector.is_logged_in(&session_data).await? {
+                let is_oauth = connector.get_oauth_url(&session_data).await?.is_some();
+                if is_oauth && !check_oauth_state(&session, query.state.as_deref()).await {
+                    return Ok(invalid_state());
+                }
                 let token = serde_json::json!({
                     "code": query.code,
                     "state": query.state,
                 });
                 connector.set_token(&mut session_data, &token).await?;
+                // The user is the author of the changes made in this session
+                if let Ok(user) = connector.get_user(&session_data).await {
+                    set_session_user(&mut session_data, connector.connector_id(), &user.name);
+                }
                 save_session_data(&session, &session_data).await;
             }
 
@@ -343,6 +377,7 @@ async fn logout(
                 .get_storage_connector_or_default(query.connector_id.as_deref())
             {
                 connector.logout(&mut session_data).await?;
+                clear_session_user(&mut session_data, connector.connector_id());
                 save_session_data(&session, &session_data).await;
             }
         }
@@ -352,6 +387,7 @@ async fn logout(
                 .get_hosting_connector_or_default(query.connector_id.as_deref())
             {
                 connector.logout(&mut session_data).await?;
+                clear_session_user(&mut session_data, connector.connector_id());
                 save_session_data(&session, &session_data).await;
             }
         }
@@ -382,6 +418,31 @@ async fn save_session_data(session: &Session, data: &serde_json::Value) {
     let _ = session.insert("data", data.clone()).await;
 }
 
+/// Redirect to an OAuth URL, with a new state kept in the session
+async fn start_oauth(session: &Session, oauth_url: &str) -> ConnectorResult<Response> {
+    let state = uuid::Uuid::new_v4().simple().to_string();
+    session
+        .insert(OAUTH_STATE_KEY, &state)
+        .await
+        .map_err(|e| ConnectorError::Remote(format!("Could not save the session: {}", e)))?;
+
+    let separator = if oauth_url.contains('?') { '&' } else { '?' };
+    let url = format!("{}{}state={}", oauth_url, separator, state);
+    Ok(Redirect::to(&url).into_response())
+}
+
+/// Check the state received by an OAuth callback against the one of the login
+///
+/// The state is used once: a callback without a pending login is rejected.
+async fn check_oauth_state(session: &Session, state: Option<&str>) -> bool {
+    let expected = session
+        .remove::<String>(OAUTH_STATE_KEY)
+        .await
+        .ok()
+        .flatten();
+    matches!((expected, state), (Some(expected), Some(state)) if expected == state)
+}
+
 /// Generate the HTML page shown after authentication
 ///
 /// This page sends a postMessage to the parent window and closes the popup.
@@ -12,6 +12,7 @@
 //! This module defines all HTTP endpoints for the Silex API.
 //! Routes are organized into submodules by functionality.
 
+mod asset_delivery;
 mod connector;
 mod publication;
 mod website;
@@ -23,7 +24,7 @@ use axum::Router;
 
 use crate::config::Config;
 use crate::connectors::ConnectorRegistry;
-use crate::services::JobManager;
+use crate::services::{JobManager, TemplateLibrary, ThumbnailCache};
 
 /// Shared application state
 ///
@@ -38,6 +39,12 @@ pub struct AppState {
 
     /// Job manager for tracking async operations (shared across requests)
     pub job_manager: JobManager,
+
+    /// Template library, when a templates directory is configured
+    pub templates: Option<Arc<TemplateLibrary>>,
+
+    /// Cache of the asset thumbnails and website preview images
+    pub thumbnails: Arc<ThumbnailCache>,
 }
 
 impl AppState {
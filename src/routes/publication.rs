@@ -23,9 +23,7 @@ use tower_sessions::Session;
 
 use crate::connectors::{HostingConnector, StorageConnector};
 use crate::error::{ConnectorError, ConnectorResult};
-use crate::models::{
-    ConnectorFile, JobId, PublicationJobData, WebsiteId,
-};
+use crate::models::{ConnectorFile, JobId, PublicationJobData, WebsiteId};
 use crate::routes::AppState;
 
 /// Build publication routes
@@ -115,7 +113,8 @@ async fn publish(
     let hosting_connector = get_hosting_connector(&state, &session_data, &query.hosting_id).await?;
 
     // Get the storage connector (needed to read assets referenced by src)
-    let storage_connector = get_storage_connector(&state, &session_data, query.storage_id.as_deref()).await?;
+    let storage_connector =
+        get_storage_connector(&state, &session_data, query.storage_id.as_deref()).await?;
 
     // Convert client-side files to connector files
     // Files can have content directly, or a src path to read from storage
@@ -137,9 +136,10 @@ async fn publish(
                 content,
             });
         } else {
-            return Err(ConnectorError::InvalidInput(
-                format!("File '{}' has neither content nor src", f.path),
-            ));
+            return Err(ConnectorError::InvalidInput(format!(
+                "File '{}' has neither content nor src",
+                f.path
+            )));
         }
     }
 
@@ -199,10 +199,7 @@ async fn get_storage_connector(
         .registry
         .get_storage_connector_or_default(connector_id)
         .ok_or_else(|| {
-            ConnectorError::NotFound(format!(
-                "Storage connector not found: {:?}",
-                connector_id
-            ))
+            ConnectorError::NotFound(format!("Storage connector not found: {:?}", connector_id))
         })?;
 
     if !connector.is_logged_in(session_data).await? {
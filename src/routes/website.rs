@@ -14,27 +14,74 @@
 //! Routes:
 //! - GET /api/website/?websiteId=X - Read website (or list if no websiteId)
 //! - POST /api/website/?websiteId=X - Update website
-//! - PUT /api/website/ - Create new website
-//! - DELETE /api/website/?websiteId=X - Delete website
+//! - PUT /api/website/?templateId=X - Create new website (from a template if `templateId` is set)
+//! - DELETE /api/website/?websiteId=X - Delete website (moved to the trash)
 //! - POST /api/website/duplicate?websiteId=X - Duplicate website
+//! - GET /api/website/page?websiteId=X&pageId=Y - Read a page
+//! - PUT /api/website/page?websiteId=X&pageId=Y - Save a page (added if it does not exist)
+//! - DELETE /api/website/page?websiteId=X&pageId=Y - Delete a page
 //! - GET /api/website/meta?websiteId=X - Get metadata
 //! - POST /api/website/meta?websiteId=X - Update metadata
-//! - GET /api/website/assets/:path?websiteId=X - Read asset
+//! - GET /api/website/assets/:path?websiteId=X&w=200 - Read asset (a thumbnail if `w` is set)
 //! - POST /api/website/assets?websiteId=X - Upload assets
+//! - GET /api/website/assets?websiteId=X - List assets
+//! - DELETE /api/website/assets/:path?websiteId=X - Delete an asset
+//! - PATCH /api/website/assets/:path?websiteId=X&to=Y - Move or rename an asset
+//! - GET /api/website/unused-assets?websiteId=X - List the assets the website does not use
+//! - DELETE /api/website/unused-assets?websiteId=X - Delete the assets the website does not use
+//! - GET /api/website/versions?websiteId=X - List saved versions
+//! - GET /api/website/version?websiteId=X&versionId=Y - Read a saved version
+//! - POST /api/website/version/restore?websiteId=X&versionId=Y - Restore a saved version
+//! - GET /api/website/diff?websiteId=X&from=Y&to=Z - Compare two versions (`to` defaults to current)
+//! - GET /api/website/integrity?websiteId=X - Check the stored files
+//! - POST /api/website/repair?websiteId=X - Rebuild the website data from its page files
+//! - POST /api/website/migrate - Migrate all websites to the current schema version
+//! - GET /api/website/export?websiteId=X - Download the website as a zip bundle
+//! - POST /api/website/import?websiteId=X - Create a website from a zip bundle (`websiteId` is optional)
+//! - POST /api/website/transfer?websiteId=X&toConnectorId=Y - Copy or move a website to another connector
+//! - GET /api/website/templates - List the templates new websites can be created from
+//! - GET /api/website/trash - List the deleted websites
+//! - POST /api/website/trash/restore?trashId=X - Restore a deleted website
+//! - DELETE /api/website/trash?trashId=X - Purge a deleted website (all of them if no trashId)
+//!
+//! Website data, pages and metadata are returned with an `ETag` header holding their revision.
+//! Updates accept an `If-Match` header and fail with 409 Conflict when the stored
+//! revision changed in between.
+//!
+//! Reading a website whose page files can not be loaded fails with 500 and an
+//! `integrity` report, instead of opening it with blank pages.
+//! A website is marked as a template by saving its metadata with `isTemplate: true`.
+//!
+//! Saving malformed website data fails with 400 and the path of the faulty value,
+//! e.g. `styles[3].style must be an object`.
 
-use axum::body::Bytes;
-use axum::extract::{Multipart, Path, Query, State};
-use axum::http::header;
-use axum::response::IntoResponse;
-use axum::routing::{delete, get, post, put};
+use std::collections::{HashMap, HashSet};
+
+use axum::body::{Body, Bytes};
+use axum::extract::multipart::Field;
+use axum::extract::{DefaultBodyLimit, Multipart, Path, Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get, patch, post, put};
 use axum::{Json, Router};
 use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tower_sessions::Session;
 
+use crate::connectors::website_files;
 use crate::connectors::StorageConnector;
 use crate::error::{ConnectorError, ConnectorResult};
-use crate::models::{ConnectorFile, WebsiteData, WebsiteId, WebsiteMeta, WebsiteMetaFileContent};
-use crate::routes::AppState;
+use crate::models::{
+    page_revision, AssetInfo, AssetStream, ConnectorFile, ImageInfo, ImageVariant, IntegrityReport,
+    MigrationReport, PublicationJobData, Template, TrashedWebsite, UnusedAssetsReport, WebsiteData,
+    WebsiteDiff, WebsiteId, WebsiteMeta, WebsiteMetaFileContent, WebsiteVersion,
+};
+use crate::routes::{asset_delivery, AppState};
+use crate::services::{
+    collect_unused_assets, create_from_template, diff_websites, export_website, import_website,
+    is_processed_image, list_templates, migrate_all_websites, process_asset, sanitize_file_name,
+    start_transfer, ImageOptions, ThumbnailCache, UploadLimits, MAX_BUNDLE_SIZE, SNIFF_LENGTH,
+};
 
 /// Build website routes
 pub fn routes() -> Router<AppState> {
@@ -44,10 +91,38 @@ pub fn routes() -> Router<AppState> {
         .route("/", put(create_website))
         .route("/", delete(delete_website))
         .route("/duplicate", post(duplicate_website))
+        .route("/page", get(read_page))
+        .route("/page", put(write_page))
+        .route("/page", delete(delete_page))
         .route("/meta", get(get_meta))
         .route("/meta", post(set_meta))
         .route("/assets/{*path}", get(read_asset))
-        .route("/assets", post(write_assets))
+        .route("/assets/{*path}", delete(delete_asset))
+        .route("/assets/{*path}", patch(move_asset))
+        .route("/assets", get(list_assets))
+        .route(
+            "/assets",
+            post(write_assets).layer(DefaultBodyLimit::disable()),
+        )
+        .route("/unused-assets", get(get_unused_assets))
+        .route("/unused-assets", delete(delete_unused_assets))
+        .route("/versions", get(list_versions))
+        .route("/version", get(read_version))
+        .route("/version/restore", post(restore_version))
+        .route("/diff", get(diff_versions))
+        .route("/integrity", get(check_integrity))
+        .route("/repair", post(repair_website))
+        .route("/migrate", post(migrate_websites))
+        .route("/export", get(export_bundle))
+        .route(
+            "/import",
+            post(import_bundle).layer(DefaultBodyLimit::max(MAX_BUNDLE_SIZE)),
+        )
+        .route("/transfer", post(transfer))
+        .route("/templates", get(get_templates))
+        .route("/trash", get(list_trash))
+        .route("/trash", delete(purge_trash))
+        .route("/trash/restore", post(restore_website))
 }
 
 // ==================
@@ -68,10 +143,20 @@ pub struct WebsiteWriteQuery {
     pub connector_id: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PageQuery {
+    pub website_id: WebsiteId,
+    pub page_id: String,
+    pub connector_id: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CreateQuery {
     pub connector_id: Option<String>,
+    /// Template to create the website from
+    pub template_id: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -79,6 +164,64 @@ pub struct CreateQuery {
 pub struct AssetReadQuery {
     pub website_id: WebsiteId,
     pub connector_id: Option<String>,
+    /// Width of the thumbnail to return instead of the image
+    pub w: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetMoveQuery {
+    pub website_id: WebsiteId,
+    /// New path of the asset in the assets folder
+    pub to: String,
+    pub connector_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionQuery {
+    pub website_id: WebsiteId,
+    pub version_id: String,
+    pub connector_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffQuery {
+    pub website_id: WebsiteId,
+    /// Version to compare from
+    pub from: String,
+    /// Version to compare to, the current website data if not set
+    pub to: Option<String>,
+    pub connector_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferQuery {
+    pub website_id: WebsiteId,
+    /// Connector to copy from, the default one if not set
+    pub connector_id: Option<String>,
+    /// Connector to copy to
+    pub to_connector_id: String,
+    /// Delete the website from the source connector once copied
+    #[serde(default, rename = "move")]
+    pub delete_source: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashQuery {
+    pub trash_id: String,
+    pub connector_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashPurgeQuery {
+    /// Entry to purge, the whole trash if not set
+    pub trash_id: Option<String>,
+    pub connector_id: Option<String>,
 }
 
 // ==================
@@ -100,6 +243,10 @@ pub struct CreateResponse {
 #[derive(Debug, Serialize)]
 pub struct AssetsResponse {
     pub data: Vec<String>,
+
+    /// Dimensions and variants of the uploaded images
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<ImageInfo>,
 }
 
 // ==================
@@ -116,17 +263,23 @@ async fn read_or_list_website(
     Query(query): Query<WebsiteReadQuery>,
 ) -> ConnectorResult<impl IntoResponse> {
     let session_data = get_session_data(&session).await;
-    let connector = get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
+    let connector =
+        get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
 
     match query.website_id {
         Some(website_id) => {
             // Read specific website
             let data = connector.read_website(&session_data, &website_id).await?;
-            Ok(Json(serde_json::to_value(data)?).into_response())
+            let revision = data.revision();
+            Ok(([etag(&revision)], Json(serde_json::to_value(data)?)).into_response())
         }
         None => {
-            // List all websites
-            let websites = connector.list_websites(&session_data).await?;
+            // List all websites, with a preview image for those which have none
+            let mut websites = connector.list_websites(&session_data).await?;
+            state
+                .thumbnails
+                .fill_preview_images(connector.as_ref(), &session_data, &mut websites)
+                .await;
             Ok(Json(websites).into_response())
         }
     }
@@ -135,27 +288,41 @@ async fn read_or_list_website(
 /// Update an existing website
 ///
 /// POST /api/website/?websiteId=X
+///
+/// With an `If-Match` header, fails with 409 if the website changed since it was read.
 async fn update_website(
     State(state): State<AppState>,
     session: Session,
     Query(query): Query<WebsiteWriteQuery>,
+    headers: HeaderMap,
     Json(data): Json<WebsiteData>,
-) -> ConnectorResult<Json<MessageResponse>> {
+) -> ConnectorResult<impl IntoResponse> {
     let session_data = get_session_data(&session).await;
-    let connector = get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
+    let connector =
+        get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
 
-    connector
-        .update_website(&session_data, &query.website_id, &data)
+    let revision = connector
+        .update_website_if_match(
+            &session_data,
+            &query.website_id,
+            &data,
+            if_match(&headers).as_deref(),
+        )
         .await?;
 
-    Ok(Json(MessageResponse {
-        message: "Website saved".to_string(),
-    }))
+    Ok((
+        [etag(&revision)],
+        Json(MessageResponse {
+            message: "Website saved".to_string(),
+        }),
+    ))
 }
 
 /// Create a new website
 ///
-/// PUT /api/website/
+/// PUT /api/website/?templateId=X
+///
+/// Starts with an empty page, or with a copy of the template when `templateId` is set.
 async fn create_website(
     State(state): State<AppState>,
     session: Session,
@@ -163,9 +330,22 @@ async fn create_website(
     Json(meta): Json<WebsiteMetaFileContent>,
 ) -> ConnectorResult<Json<CreateResponse>> {
     let session_data = get_session_data(&session).await;
-    let connector = get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
+    let connector =
+        get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
 
-    let website_id = connector.create_website(&session_data, &meta).await?;
+    let website_id = match &query.template_id {
+        Some(template_id) => {
+            create_from_template(
+                connector.as_ref(),
+                &session_data,
+                state.templates.as_deref(),
+                &meta,
+                template_id,
+            )
+            .await?
+        }
+        None => connector.create_website(&session_data, &meta).await?,
+    };
 
     Ok(Json(CreateResponse {
         message: format!("Website created with ID: {}", website_id),
@@ -182,7 +362,8 @@ async fn delete_website(
     Query(query): Query<WebsiteWriteQuery>,
 ) -> ConnectorResult<Json<MessageResponse>> {
     let session_data = get_session_data(&session).await;
-    let connector = get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
+    let connector =
+        get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
 
     connector
         .delete_website(&session_data, &query.website_id)
@@ -202,7 +383,8 @@ async fn duplicate_website(
     Query(query): Query<WebsiteWriteQuery>,
 ) -> ConnectorResult<Json<MessageResponse>> {
     let session_data = get_session_data(&session).await;
-    let connector = get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
+    let connector =
+        get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
 
     let new_id = connector
         .duplicate_website(&session_data, &query.website_id)
@@ -213,6 +395,94 @@ async fn duplicate_website(
     }))
 }
 
+/// Read one page of a website
+///
+/// GET /api/website/page?websiteId=X&pageId=Y
+async fn read_page(
+    State(state): State<AppState>,
+    session: Session,
+    Query(query): Query<PageQuery>,
+) -> ConnectorResult<impl IntoResponse> {
+    let session_data = get_session_data(&session).await;
+    let connector =
+        get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
+
+    let page = connector
+        .read_page(&session_data, &query.website_id, &query.page_id)
+        .await?;
+    let revision = page_revision(&page);
+
+    Ok(([etag(&revision)], Json(page)))
+}
+
+/// Save one page of a website, without sending the whole website data
+///
+/// PUT /api/website/page?websiteId=X&pageId=Y
+///
+/// The page is added when the website has no page with this ID. The body's `id`
+/// defaults to `pageId`. With an `If-Match` header, fails with 409 if the page
+/// changed since it was read.
+async fn write_page(
+    State(state): State<AppState>,
+    session: Session,
+    Query(query): Query<PageQuery>,
+    headers: HeaderMap,
+    Json(mut page): Json<serde_json::Value>,
+) -> ConnectorResult<impl IntoResponse> {
+    let session_data = get_session_data(&session).await;
+    let connector =
+        get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
+
+    if let Some(fields) = page.as_object_mut() {
+        let id = fields
+            .entry("id")
+            .or_insert_with(|| serde_json::Value::String(query.page_id.clone()));
+        if id.as_str() != Some(query.page_id.as_str()) {
+            return Err(ConnectorError::InvalidInput(format!(
+                "page.id does not match pageId '{}'",
+                query.page_id
+            )));
+        }
+    }
+
+    let revision = connector
+        .write_page(
+            &session_data,
+            &query.website_id,
+            &page,
+            if_match(&headers).as_deref(),
+        )
+        .await?;
+
+    Ok((
+        [etag(&revision)],
+        Json(MessageResponse {
+            message: "Page saved".to_string(),
+        }),
+    ))
+}
+
+/// Delete one page of a website
+///
+/// DELETE /api/website/page?websiteId=X&pageId=Y
+async fn delete_page(
+    State(state): State<AppState>,
+    session: Session,
+    Query(query): Query<PageQuery>,
+) -> ConnectorResult<Json<MessageResponse>> {
+    let session_data = get_session_data(&session).await;
+    let connector =
+        get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
+
+    connector
+        .delete_page(&session_data, &query.website_id, &query.page_id)
+        .await?;
+
+    Ok(Json(MessageResponse {
+        message: "Page deleted".to_string(),
+    }))
+}
+
 /// Get website metadata
 ///
 /// GET /api/website/meta?websiteId=X
@@ -220,52 +490,75 @@ async fn get_meta(
     State(state): State<AppState>,
     session: Session,
     Query(query): Query<WebsiteWriteQuery>,
-) -> ConnectorResult<Json<WebsiteMeta>> {
+) -> ConnectorResult<impl IntoResponse> {
     let session_data = get_session_data(&session).await;
-    let connector = get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
+    let connector =
+        get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
 
-    let meta = connector
+    let mut meta: WebsiteMeta = connector
         .get_website_meta(&session_data, &query.website_id)
         .await?;
 
-    Ok(Json(meta))
+    let revision = meta.to_file_content().revision();
+    meta.revision = Some(revision.clone());
+
+    Ok(([etag(&revision)], Json(meta)))
 }
 
 /// Update website metadata
 ///
 /// POST /api/website/meta?websiteId=X
+///
+/// With an `If-Match` header, fails with 409 if the metadata changed since it was read.
 async fn set_meta(
     State(state): State<AppState>,
     session: Session,
     Query(query): Query<WebsiteWriteQuery>,
+    headers: HeaderMap,
     Json(meta): Json<WebsiteMetaFileContent>,
-) -> ConnectorResult<Json<MessageResponse>> {
+) -> ConnectorResult<impl IntoResponse> {
     let session_data = get_session_data(&session).await;
-    let connector = get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
+    let connector =
+        get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
 
-    connector
-        .set_website_meta(&session_data, &query.website_id, &meta)
+    let revision = connector
+        .set_website_meta_if_match(
+            &session_data,
+            &query.website_id,
+            &meta,
+            if_match(&headers).as_deref(),
+        )
         .await?;
 
-    Ok(Json(MessageResponse {
-        message: "Website meta saved".to_string(),
-    }))
+    Ok((
+        [etag(&revision)],
+        Json(MessageResponse {
+            message: "Website meta saved".to_string(),
+        }),
+    ))
 }
 
 /// Read an asset file
 ///
-/// GET /api/website/assets/:path?websiteId=X
+/// GET /api/website/assets/:path?websiteId=X&w=200
+///
+/// With `w`, images wider than `w` pixels are returned as a thumbnail of that width.
+/// `w` is ignored for other files and for images over `MAX_THUMBNAIL_SOURCE_SIZE`.
+/// The file is streamed, with `ETag`/`Last-Modified` validators and `Range` support
+/// (see `asset_delivery`).
 async fn read_asset(
     State(state): State<AppState>,
     session: Session,
     Path(path): Path<String>,
     Query(query): Query<AssetReadQuery>,
-) -> ConnectorResult<impl IntoResponse> {
+    headers: HeaderMap,
+) -> ConnectorResult<Response> {
     let session_data = get_session_data(&session).await;
-    let connector = get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
+    let connector =
+        get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
 
-    let content = connector
-        .read_asset(&session_data, &query.website_id, &path)
+    let mut asset = connector
+        .read_asset_stream(&session_data, &query.website_id, &path)
         .await?;
 
     // Determine content type from file extension
@@ -273,10 +566,65 @@ async fn read_asset(
         .first_or_octet_stream()
         .to_string();
 
-    Ok((
-        [(header::CONTENT_TYPE, content_type)],
-        Bytes::from(content),
-    ))
+    // Thumbnails are made from the whole image, and tagged after it.
+    // Other files and large images are streamed as they are.
+    let width = query
+        .w
+        .filter(|_| ThumbnailCache::is_thumbnail_source(&path, asset.size));
+    if let Some(width) = width {
+        let etag = format!("{}-w{}", asset.etag, width);
+        if asset_delivery::is_not_modified(&headers, &etag, asset.modified_at) {
+            let validators = asset_delivery::validator_headers(&etag, asset.modified_at);
+            return Ok((StatusCode::NOT_MODIFIED, validators).into_response());
+        }
+        let mut content = Vec::with_capacity(asset.size as usize);
+        asset.reader.read_to_end(&mut content).await?;
+        if let Some(thumbnail) = state.thumbnails.thumbnail(&path, &content, width).await? {
+            content = thumbnail;
+        }
+        asset = AssetStream {
+            etag,
+            ..AssetStream::from_content(content, asset.modified_at)
+        };
+    }
+
+    asset_delivery::asset_response(&headers, asset, content_type).await
+}
+
+/// Size of the buffer between an upload and the connector writing it
+const UPLOAD_PIPE_SIZE: usize = 64 * 1024;
+
+/// Where an uploaded file was written
+enum UploadedAsset {
+    /// Path of the file in the batch written at the end of the request
+    Batched(String),
+
+    /// Stored path of the file, streamed to the connector
+    Streamed(String),
+}
+
+/// Size of an uploaded file being received, checked against the limits
+struct UploadProgress<'a> {
+    file_name: &'a str,
+    limits: &'a UploadLimits,
+    file_size: usize,
+    request_size: &'a mut usize,
+}
+
+impl UploadProgress<'_> {
+    /// Read the next chunk of the file, failing as soon as a limit is reached
+    async fn next_chunk(&mut self, field: &mut Field<'_>) -> ConnectorResult<Option<Bytes>> {
+        let chunk = field.chunk().await.map_err(|e| {
+            ConnectorError::InvalidInput(format!("Failed to read file data: {}", e))
+        })?;
+        if let Some(chunk) = &chunk {
+            self.file_size += chunk.len();
+            *self.request_size += chunk.len();
+            self.limits
+                .check_size(self.file_name, self.file_size, *self.request_size)?;
+        }
+        Ok(chunk)
+    }
 }
 
 /// Upload asset files
@@ -284,6 +632,13 @@ async fn read_asset(
 /// POST /api/website/assets?websiteId=X
 ///
 /// Accepts multipart form data with files[] field.
+/// Images and small files are written together at the end, other files are
+/// streamed to the connector as they are received.
+/// Files over the size limits fail with 413, files of a type which is not allowed
+/// or does not match their extension fail with 415. File names are sanitized.
+/// A failed request stores nothing, the files already streamed are deleted.
+/// Images are processed as configured (downscaling, metadata removal, variants),
+/// their dimensions and variants are returned in `images`.
 async fn write_assets(
     State(state): State<AppState>,
     session: Session,
@@ -291,58 +646,659 @@ async fn write_assets(
     mut multipart: Multipart,
 ) -> ConnectorResult<Json<AssetsResponse>> {
     let session_data = get_session_data(&session).await;
-    let connector = get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
-
-    let mut files = Vec::new();
-
-    // Process multipart form data
-    while let Some(field) = multipart.next_field().await.map_err(|e| {
-        ConnectorError::InvalidInput(format!("Failed to read multipart field: {}", e))
-    })? {
-        let file_name = field
-            .file_name()
-            .map(String::from)
-            .unwrap_or_else(|| "unknown".to_string());
-
-        let content = field
-            .bytes()
-            .await
-            .map_err(|e| ConnectorError::InvalidInput(format!("Failed to read file data: {}", e)))?;
-
-        // Clean up the path (remove /assets/ prefix if present)
-        let path = file_name.replace("/assets/", "/");
-        let path = if !path.starts_with('/') {
-            format!("/{}", path)
+    let connector =
+        get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
+
+    let limits = UploadLimits::from_config(&state.config);
+    let image_options = ImageOptions::from_config(&state.config);
+    let mut files: Vec<ConnectorFile> = Vec::new();
+    let mut uploaded = Vec::new();
+    let mut images: Vec<ImageInfo> = Vec::new();
+    let mut request_size = 0;
+    // Paths of the image copies in the batch
+    let mut variant_paths = HashSet::new();
+
+    // Files are received, then the images and small files are written
+    let received = async {
+        // Process multipart form data
+        while let Some(mut field) = multipart.next_field().await.map_err(|e| {
+            ConnectorError::InvalidInput(format!("Failed to read multipart field: {}", e))
+        })? {
+            let file_name = field
+                .file_name()
+                .map(String::from)
+                .unwrap_or_else(|| "unknown".to_string());
+
+            // Clean up the path (remove /assets/ prefix if present, and unsafe characters)
+            let path = sanitize_file_name(
+                file_name
+                    .trim_start_matches('/')
+                    .trim_start_matches("assets/"),
+            );
+
+            // Read the beginning of the file to check its type
+            let mut upload = UploadProgress {
+                file_name: &file_name,
+                limits: &limits,
+                file_size: 0,
+                request_size: &mut request_size,
+            };
+            let mut content = Vec::new();
+            let mut complete = false;
+            while content.len() < SNIFF_LENGTH {
+                match upload.next_chunk(&mut field).await? {
+                    Some(chunk) => content.extend_from_slice(&chunk),
+                    None => {
+                        complete = true;
+                        break;
+                    }
+                }
+            }
+            limits.check_type(&path, &content)?;
+
+            if complete || is_processed_image(&path) {
+                // Images are processed in memory, they come with their variants, the original first
+                while let Some(chunk) = upload.next_chunk(&mut field).await? {
+                    content.extend_from_slice(&chunk);
+                }
+                // An image copy of this upload is replaced by the file
+                if variant_paths.remove(&path) {
+                    files.retain(|file| file.path != path);
+                    for image in &mut images {
+                        image.variants.retain(|variant| variant.src != path);
+                    }
+                }
+
+                let file = ConnectorFile {
+                    path: path.clone(),
+                    content,
+                };
+                let (mut processed, mut image) = process_asset(file, &image_options).await?;
+
+                // Copies never replace an asset, even a copy of a previous upload of the image
+                let mut skipped = HashSet::new();
+                for variant in processed.iter().skip(1) {
+                    let taken = files.iter().any(|file| file.path == variant.path)
+                        || asset_exists(
+                            connector.as_ref(),
+                            &session_data,
+                            &query.website_id,
+                            &variant.path,
+                        )
+                        .await?;
+                    if taken {
+                        tracing::warn!("Asset {} exists, the image copy is skipped", variant.path);
+                        skipped.insert(variant.path.clone());
+                    }
+                }
+                processed.retain(|file| !skipped.contains(&file.path));
+                if let Some(image) = &mut image {
+                    image
+                        .variants
+                        .retain(|variant| !skipped.contains(&variant.src));
+                }
+
+                variant_paths.extend(processed.iter().skip(1).map(|file| file.path.clone()));
+                uploaded.push(UploadedAsset::Batched(path));
+                files.extend(processed);
+                images.extend(image);
+            } else {
+                // Other files are streamed to the connector as they are received
+                let (mut sender, receiver) = tokio::io::duplex(UPLOAD_PIPE_SIZE);
+                let receive = async {
+                    sender.write_all(&content).await?;
+                    while let Some(chunk) = upload.next_chunk(&mut field).await? {
+                        sender.write_all(&chunk).await?;
+                    }
+                    sender.shutdown().await?;
+                    Ok::<_, ConnectorError>(())
+                };
+                // A failed upload cancels the write
+                let write = connector.write_asset_stream(
+                    &session_data,
+                    &query.website_id,
+                    &path,
+                    Box::new(receiver),
+                );
+                let ((), stored_path) = tokio::try_join!(receive, write)?;
+                uploaded.push(UploadedAsset::Streamed(stored_path));
+            }
+        }
+
+        let file_paths: Vec<String> = files.iter().map(|file| file.path.clone()).collect();
+        let paths = if files.is_empty() {
+            Vec::new()
         } else {
-            path
+            connector
+                .write_assets(&session_data, &query.website_id, files)
+                .await?
         };
-
-        files.push(ConnectorFile {
-            path,
-            content: content.to_vec(),
-        });
+        Ok((file_paths, paths))
     }
-
-    // Write the files
-    let paths = connector
-        .write_assets(&session_data, &query.website_id, files)
-        .await?;
+    .await;
+    // A failed request stores nothing: the files already streamed are deleted
+    let (file_paths, paths) = match received {
+        Ok(written) => written,
+        Err(e) => {
+            for asset in &uploaded {
+                if let UploadedAsset::Streamed(path) = asset {
+                    if let Err(e) = connector
+                        .delete_asset(&session_data, &query.website_id, path)
+                        .await
+                    {
+                        tracing::warn!("Failed to delete the uploaded asset {}: {}", path, e);
+                    }
+                }
+            }
+            return Err(e);
+        }
+    };
 
     // Build URLs for the uploaded assets
     // Use relative URLs (no origin) so the client-side displayedToStored() can parse them
     // Use the resolved connector's ID (not the query param, which may be empty)
     let resolved_connector_id = connector.connector_id();
-    let data: Vec<String> = paths
+    let asset_url = |path: &str| {
+        format!(
+            "/api/website/assets{}?websiteId={}&connectorId={}",
+            path, query.website_id, resolved_connector_id
+        )
+    };
+    // Batched paths are the uploaded ones, the connector tells where they were stored
+    let stored: HashMap<&str, &str> = file_paths
         .iter()
-        .map(|path| {
-            format!(
-                "/api/website/assets{}?websiteId={}&connectorId={}",
-                path, query.website_id, resolved_connector_id
-            )
+        .map(String::as_str)
+        .zip(paths.iter().map(String::as_str))
+        .collect();
+    let stored_url = |path: &str| asset_url(stored.get(path).copied().unwrap_or(path));
+    let data: Vec<String> = uploaded
+        .iter()
+        .map(|asset| match asset {
+            UploadedAsset::Batched(path) => stored_url(path),
+            UploadedAsset::Streamed(path) => asset_url(path),
         })
         .collect();
+    let images = images
+        .into_iter()
+        .map(|image| ImageInfo {
+            src: stored_url(&image.src),
+            variants: image
+                .variants
+                .into_iter()
+                .map(|variant| ImageVariant {
+                    src: stored_url(&variant.src),
+                    ..variant
+                })
+                .collect(),
+            ..image
+        })
+        .collect();
+
+    Ok(Json(AssetsResponse { data, images }))
+}
+
+/// Whether an asset file exists
+async fn asset_exists(
+    connector: &dyn StorageConnector,
+    session: &serde_json::Value,
+    website_id: &WebsiteId,
+    path: &str,
+) -> ConnectorResult<bool> {
+    match connector.read_asset_stream(session, website_id, path).await {
+        Ok(_) => Ok(true),
+        Err(ConnectorError::NotFound(_)) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// List the asset files of a website
+///
+/// GET /api/website/assets?websiteId=X
+async fn list_assets(
+    State(state): State<AppState>,
+    session: Session,
+    Query(query): Query<WebsiteWriteQuery>,
+) -> ConnectorResult<Json<Vec<AssetInfo>>> {
+    let session_data = get_session_data(&session).await;
+    let connector =
+        get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
 
-    Ok(Json(AssetsResponse { data }))
+    let assets = connector
+        .list_assets(&session_data, &query.website_id)
+        .await?;
+
+    Ok(Json(assets))
+}
+
+/// Delete an asset file
+///
+/// DELETE /api/website/assets/:path?websiteId=X
+async fn delete_asset(
+    State(state): State<AppState>,
+    session: Session,
+    Path(path): Path<String>,
+    Query(query): Query<AssetReadQuery>,
+) -> ConnectorResult<Json<MessageResponse>> {
+    let session_data = get_session_data(&session).await;
+    let connector =
+        get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
+
+    connector
+        .delete_asset(&session_data, &query.website_id, &path)
+        .await?;
+
+    Ok(Json(MessageResponse {
+        message: format!("Asset {} deleted", path),
+    }))
+}
+
+/// Move or rename an asset file
+///
+/// PATCH /api/website/assets/:path?websiteId=X&to=Y
+///
+/// The website data is not updated: the editor changes the references and saves.
+async fn move_asset(
+    State(state): State<AppState>,
+    session: Session,
+    Path(path): Path<String>,
+    Query(query): Query<AssetMoveQuery>,
+) -> ConnectorResult<Json<MessageResponse>> {
+    let session_data = get_session_data(&session).await;
+    let connector =
+        get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
+
+    connector
+        .move_asset(&session_data, &query.website_id, &path, &query.to)
+        .await?;
+
+    Ok(Json(MessageResponse {
+        message: format!("Asset {} moved to {}", path, query.to),
+    }))
+}
+
+/// List the assets which the website data does not use
+///
+/// GET /api/website/unused-assets?websiteId=X
+///
+/// Assets uploaded in the last hours are not listed, they may be used by unsaved changes.
+async fn get_unused_assets(
+    State(state): State<AppState>,
+    session: Session,
+    Query(query): Query<WebsiteWriteQuery>,
+) -> ConnectorResult<Json<UnusedAssetsReport>> {
+    let session_data = get_session_data(&session).await;
+    let connector =
+        get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
+
+    let report =
+        collect_unused_assets(connector.as_ref(), &session_data, &query.website_id, false).await?;
+
+    Ok(Json(report))
+}
+
+/// Delete the assets which the website data does not use
+///
+/// DELETE /api/website/unused-assets?websiteId=X
+///
+/// Returns the deleted assets.
+async fn delete_unused_assets(
+    State(state): State<AppState>,
+    session: Session,
+    Query(query): Query<WebsiteWriteQuery>,
+) -> ConnectorResult<Json<UnusedAssetsReport>> {
+    let session_data = get_session_data(&session).await;
+    let connector =
+        get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
+
+    let report =
+        collect_unused_assets(connector.as_ref(), &session_data, &query.website_id, true).await?;
+
+    Ok(Json(report))
+}
+
+/// List the saved versions of a website, newest first
+///
+/// GET /api/website/versions?websiteId=X
+async fn list_versions(
+    State(state): State<AppState>,
+    session: Session,
+    Query(query): Query<WebsiteWriteQuery>,
+) -> ConnectorResult<Json<Vec<WebsiteVersion>>> {
+    let session_data = get_session_data(&session).await;
+    let connector =
+        get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
+
+    let versions = connector
+        .list_versions(&session_data, &query.website_id)
+        .await?;
+
+    Ok(Json(versions))
+}
+
+/// Read the website data of a saved version
+///
+/// GET /api/website/version?websiteId=X&versionId=Y
+async fn read_version(
+    State(state): State<AppState>,
+    session: Session,
+    Query(query): Query<VersionQuery>,
+) -> ConnectorResult<Json<WebsiteData>> {
+    let session_data = get_session_data(&session).await;
+    let connector =
+        get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
+
+    let data = connector
+        .read_version(&session_data, &query.website_id, &query.version_id)
+        .await?;
+
+    Ok(Json(data))
+}
+
+/// Restore a saved version as the current website data
+///
+/// POST /api/website/version/restore?websiteId=X&versionId=Y
+async fn restore_version(
+    State(state): State<AppState>,
+    session: Session,
+    Query(query): Query<VersionQuery>,
+) -> ConnectorResult<Json<MessageResponse>> {
+    let session_data = get_session_data(&session).await;
+    let connector =
+        get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
+
+    connector
+        .restore_version(&session_data, &query.website_id, &query.version_id)
+        .await?;
+
+    Ok(Json(MessageResponse {
+        message: format!("Version {} restored", query.version_id),
+    }))
+}
+
+/// Compare two versions of a website
+///
+/// GET /api/website/diff?websiteId=X&from=Y&to=Z
+///
+/// Compares with the current website data when `to` is not set.
+async fn diff_versions(
+    State(state): State<AppState>,
+    session: Session,
+    Query(query): Query<DiffQuery>,
+) -> ConnectorResult<Json<WebsiteDiff>> {
+    let session_data = get_session_data(&session).await;
+    let connector =
+        get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
+
+    let from = connector
+        .read_version(&session_data, &query.website_id, &query.from)
+        .await?;
+    let to = match &query.to {
+        Some(version_id) => {
+            connector
+                .read_version(&session_data, &query.website_id, version_id)
+                .await?
+        }
+        None => {
+            connector
+                .read_website(&session_data, &query.website_id)
+                .await?
+        }
+    };
+
+    Ok(Json(diff_websites(&from, &to)))
+}
+
+/// Check the stored files of a website
+///
+/// GET /api/website/integrity?websiteId=X
+async fn check_integrity(
+    State(state): State<AppState>,
+    session: Session,
+    Query(query): Query<WebsiteWriteQuery>,
+) -> ConnectorResult<Json<IntegrityReport>> {
+    let session_data = get_session_data(&session).await;
+    let connector =
+        get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
+
+    let report = connector
+        .check_integrity(&session_data, &query.website_id)
+        .await?;
+
+    Ok(Json(report))
+}
+
+/// Rebuild the website data from its page files
+///
+/// POST /api/website/repair?websiteId=X
+///
+/// Returns the integrity report of the repaired website.
+async fn repair_website(
+    State(state): State<AppState>,
+    session: Session,
+    Query(query): Query<WebsiteWriteQuery>,
+) -> ConnectorResult<Json<IntegrityReport>> {
+    let session_data = get_session_data(&session).await;
+    let connector =
+        get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
+
+    let report = connector
+        .repair_website(&session_data, &query.website_id)
+        .await?;
+
+    Ok(Json(report))
+}
+
+/// Migrate all the websites of a storage connector to the current schema version
+///
+/// POST /api/website/migrate
+async fn migrate_websites(
+    State(state): State<AppState>,
+    session: Session,
+    Query(query): Query<CreateQuery>,
+) -> ConnectorResult<Json<MigrationReport>> {
+    let session_data = get_session_data(&session).await;
+    let connector =
+        get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
+
+    let report = migrate_all_websites(connector.as_ref(), &session_data).await?;
+
+    Ok(Json(report))
+}
+
+/// Download a website as a zip bundle
+///
+/// GET /api/website/export?websiteId=X
+///
+/// The bundle holds website.json, the page files, meta.json and the assets.
+async fn export_bundle(
+    State(state): State<AppState>,
+    session: Session,
+    Query(query): Query<WebsiteWriteQuery>,
+) -> ConnectorResult<impl IntoResponse> {
+    let session_data = get_session_data(&session).await;
+    let connector =
+        get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
+
+    let meta = connector
+        .get_website_meta(&session_data, &query.website_id)
+        .await?;
+    let bundle = export_website(connector.as_ref(), &session_data, &query.website_id).await?;
+
+    // Name the file after the website, header values must be ASCII
+    let name = match website_files::get_page_slug(&meta.name) {
+        slug if slug.is_empty() || !slug.is_ascii() => query.website_id.clone(),
+        slug => slug,
+    };
+    let disposition = format!("attachment; filename=\"{}.zip\"", name);
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/zip".to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        Body::from_stream(tokio_util::io::ReaderStream::new(bundle)),
+    ))
+}
+
+/// Create a website from a zip bundle
+///
+/// POST /api/website/import?websiteId=X
+///
+/// Accepts multipart form data with the bundle in its first file field.
+/// The website gets a new ID unless `websiteId` is set.
+async fn import_bundle(
+    State(state): State<AppState>,
+    session: Session,
+    Query(query): Query<WebsiteReadQuery>,
+    mut multipart: Multipart,
+) -> ConnectorResult<Json<CreateResponse>> {
+    let session_data = get_session_data(&session).await;
+    let connector =
+        get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
+
+    let field = multipart
+        .next_field()
+        .await
+        .map_err(|e| {
+            ConnectorError::InvalidInput(format!("Failed to read multipart field: {}", e))
+        })?
+        .ok_or_else(|| ConnectorError::InvalidInput("No bundle uploaded".to_string()))?;
+    let content = field
+        .bytes()
+        .await
+        .map_err(|e| ConnectorError::InvalidInput(format!("Failed to read file data: {}", e)))?;
+
+    let website_id = import_website(
+        connector.as_ref(),
+        &session_data,
+        content.to_vec(),
+        query.website_id,
+    )
+    .await?;
+
+    Ok(Json(CreateResponse {
+        message: format!("Website imported with ID: {}", website_id),
+        website_id,
+    }))
+}
+
+/// Copy or move a website to another storage connector
+///
+/// POST /api/website/transfer?websiteId=X&connectorId=Y&toConnectorId=Z&move=true
+///
+/// Returns a job, to follow with GET /api/publication/status?jobId=X.
+/// Its final message holds the ID of the copy.
+async fn transfer(
+    State(state): State<AppState>,
+    session: Session,
+    Query(query): Query<TransferQuery>,
+) -> ConnectorResult<Json<PublicationJobData>> {
+    let session_data = get_session_data(&session).await;
+    let from = get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
+    let to = get_storage_connector(&state, &session_data, Some(&query.to_connector_id)).await?;
+
+    if from.connector_id() == to.connector_id() {
+        return Err(ConnectorError::InvalidInput(
+            "The source and target connectors are the same".to_string(),
+        ));
+    }
+
+    let job = start_transfer(
+        from,
+        to,
+        session_data,
+        query.website_id,
+        query.delete_source,
+        state.job_manager(),
+    );
+
+    Ok(Json(job))
+}
+
+/// List the templates new websites can be created from
+///
+/// GET /api/website/templates
+///
+/// The connector's websites marked as templates come first, then the template library.
+async fn get_templates(
+    State(state): State<AppState>,
+    session: Session,
+    Query(query): Query<CreateQuery>,
+) -> ConnectorResult<Json<Vec<Template>>> {
+    let session_data = get_session_data(&session).await;
+    let connector =
+        get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
+
+    let templates = list_templates(
+        connector.as_ref(),
+        &session_data,
+        state.templates.as_deref(),
+    )
+    .await?;
+
+    Ok(Json(templates))
+}
+
+/// List the deleted websites, newest first
+///
+/// GET /api/website/trash
+async fn list_trash(
+    State(state): State<AppState>,
+    session: Session,
+    Query(query): Query<CreateQuery>,
+) -> ConnectorResult<Json<Vec<TrashedWebsite>>> {
+    let session_data = get_session_data(&session).await;
+    let connector =
+        get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
+
+    let websites = connector.list_trash(&session_data).await?;
+
+    Ok(Json(websites))
+}
+
+/// Restore a deleted website with its former ID
+///
+/// POST /api/website/trash/restore?trashId=X
+async fn restore_website(
+    State(state): State<AppState>,
+    session: Session,
+    Query(query): Query<TrashQuery>,
+) -> ConnectorResult<Json<CreateResponse>> {
+    let session_data = get_session_data(&session).await;
+    let connector =
+        get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
+
+    let website_id = connector
+        .restore_website(&session_data, &query.trash_id)
+        .await?;
+
+    Ok(Json(CreateResponse {
+        message: format!("Website restored with ID: {}", website_id),
+        website_id,
+    }))
+}
+
+/// Permanently delete websites from the trash
+///
+/// DELETE /api/website/trash?trashId=X
+///
+/// Empties the whole trash when `trashId` is not set.
+async fn purge_trash(
+    State(state): State<AppState>,
+    session: Session,
+    Query(query): Query<TrashPurgeQuery>,
+) -> ConnectorResult<Json<MessageResponse>> {
+    let session_data = get_session_data(&session).await;
+    let connector =
+        get_storage_connector(&state, &session_data, query.connector_id.as_deref()).await?;
+
+    let purged = connector
+        .purge_trash(&session_data, query.trash_id.as_deref())
+        .await?;
+
+    Ok(Json(MessageResponse {
+        message: format!("{} websites purged from the trash", purged),
+    }))
 }
 
 // ==================
@@ -359,6 +1315,23 @@ async fn get_session_data(session: &Session) -> serde_json::Value {
         .unwrap_or_else(|| serde_json::json!({}))
 }
 
+/// Build an `ETag` header from a revision
+fn etag(revision: &str) -> (header::HeaderName, String) {
+    (header::ETAG, format!("\"{}\"", revision))
+}
+
+/// Get the expected revision from the `If-Match` header
+///
+/// Returns None when the header is absent or is `*` (any revision).
+fn if_match(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get(header::IF_MATCH)?.to_str().ok()?.trim();
+    if value == "*" {
+        return None;
+    }
+    let value = value.trim_start_matches("W/").trim_matches('"');
+    Some(value.to_string())
+}
+
 /// Get the storage connector, checking authentication
 async fn get_storage_connector(
     state: &AppState,
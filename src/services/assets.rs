@@ -0,0 +1,342 @@
+/*
+ * Silex website builder, free/libre no-code tool for makers.
+ * Copyright (c) 2023 lexoyo and Silex Labs foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or any later version.
+ */
+
+//! Garbage collection of unused assets
+//!
+//! Uploaded assets stay in storage when no page, style, setting or asset list
+//! entry uses them anymore. They are found by scanning the website data for
+//! asset URLs, in their stored and displayed forms (see `website_files`).
+//! Assets used by a version of the history or as the website preview image
+//! are kept, and so are the copies of a used image made at upload (`logo.png`
+//! keeps `logo-480w.png` and `logo.webp`, and the other way around).
+//!
+//! Recent assets are never collected: the editor uploads an asset before
+//! the website data which uses it is saved. Websites whose data can not be
+//! read are skipped, so that their assets do not look unused.
+
+use std::collections::HashSet;
+
+use chrono::{Duration, Utc};
+
+use crate::connectors::website_files;
+use crate::connectors::StorageConnector;
+use crate::error::{ConnectorError, ConnectorResult};
+use crate::models::{UnusedAssetsReport, WebsiteData, WebsiteId};
+
+/// Assets modified less than this number of hours ago are kept, even if unused
+pub const UNUSED_ASSET_MIN_AGE_HOURS: i64 = 24;
+
+/// Get the paths of all the asset files of a website, without leading slash
+///
+/// Used to copy a website with its assets: assets which the data does not
+/// reference may still be used, e.g. by the editor's asset manager or by old
+/// versions. Connectors which can not list their assets fall back to the
+/// assets referenced by `fallback`, or fail with NotSupported without it.
+pub(crate) async fn website_asset_paths(
+    connector: &dyn StorageConnector,
+    session: &serde_json::Value,
+    website_id: &WebsiteId,
+    fallback: Option<&WebsiteData>,
+) -> ConnectorResult<Vec<String>> {
+    match connector.list_assets(session, website_id).await {
+        Ok(assets) => Ok(assets
+            .into_iter()
+            .map(|asset| asset.path.trim_start_matches('/').to_string())
+            .collect()),
+        Err(ConnectorError::NotSupported(message)) => match fallback {
+            Some(data) => Ok(website_files::referenced_asset_paths(data)
+                .into_iter()
+                .collect()),
+            None => Err(ConnectorError::NotSupported(message)),
+        },
+        Err(e) => Err(e),
+    }
+}
+
+/// Find the assets which a website's data does not use, and delete them if asked
+pub async fn collect_unused_assets(
+    connector: &dyn StorageConnector,
+    session: &serde_json::Value,
+    website_id: &WebsiteId,
+    delete: bool,
+) -> ConnectorResult<UnusedAssetsReport> {
+    let data = connector.read_website(session, website_id).await?;
+    let assets = connector.list_assets(session, website_id).await?;
+    let paths: Vec<String> = assets
+        .iter()
+        .map(|asset| asset.path.trim_start_matches('/').to_string())
+        .collect();
+
+    // Unused by the current data, then by each version of the history
+    let mut unused = website_files::unused_asset_paths(&data, paths.iter().cloned())?;
+    let versions = match connector.list_versions(session, website_id).await {
+        Ok(versions) => versions,
+        Err(ConnectorError::NotSupported(_)) => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    for version in versions {
+        if unused.is_empty() {
+            break;
+        }
+        let version_data = match connector
+            .read_version(session, website_id, &version.version_id)
+            .await
+        {
+            Ok(version_data) => version_data,
+            // Purged since it was listed
+            Err(ConnectorError::NotFound(_)) => continue,
+            Err(e) => return Err(e),
+        };
+        unused = website_files::unused_asset_paths(&version_data, unused)?;
+    }
+
+    // The preview image
+    let meta = connector.get_website_meta(session, website_id).await?;
+    if let Some(preview) = meta
+        .image_url
+        .as_deref()
+        .and_then(website_files::asset_path)
+    {
+        unused.retain(|path| *path != preview);
+    }
+
+    // Copies of the used images
+    let unused: HashSet<String> = unused.into_iter().collect();
+    let used_images: HashSet<&str> = paths
+        .iter()
+        .filter(|path| !unused.contains(*path))
+        .map(|path| image_family(path))
+        .collect();
+    let unused: HashSet<String> = unused
+        .into_iter()
+        .filter(|path| !used_images.contains(image_family(path)))
+        .collect();
+
+    // Assets without modification time can not be told apart from new ones
+    let min_date = Utc::now() - Duration::hours(UNUSED_ASSET_MIN_AGE_HOURS);
+    let assets: Vec<_> = assets
+        .into_iter()
+        .filter(|asset| unused.contains(asset.path.trim_start_matches('/')))
+        .filter(|asset| {
+            asset
+                .modified_at
+                .map(|date| date < min_date)
+                .unwrap_or(false)
+        })
+        .collect();
+
+    if delete {
+        for asset in &assets {
+            match connector
+                .delete_asset(session, website_id, &asset.path)
+                .await
+            {
+                Ok(()) | Err(ConnectorError::NotFound(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    Ok(UnusedAssetsReport {
+        total_size: assets.iter().map(|asset| asset.size).sum(),
+        assets,
+        deleted: delete,
+    })
+}
+
+/// Get the path of an image without its extension nor its width suffix
+///
+/// The copies made at upload (see `process_asset`) share the family of their
+/// original: `logo.png`, `logo.webp` and `logo-480w.png` are all `logo`.
+fn image_family(path: &str) -> &str {
+    let stem = match path.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() && !extension.contains('/') => stem,
+        _ => path,
+    };
+    match stem.rsplit_once('-') {
+        Some((original, width))
+            if !original.is_empty()
+                && width.len() > 1
+                && width.ends_with('w')
+                && width[..width.len() - 1].bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            original
+        }
+        _ => stem,
+    }
+}
+
+/// Delete the unused assets of all the websites of a storage connector
+///
+/// A website which fails does not stop the others.
+/// Returns the number of assets deleted.
+pub async fn collect_all_unused_assets(
+    connector: &dyn StorageConnector,
+    session: &serde_json::Value,
+) -> ConnectorResult<usize> {
+    let mut deleted = 0;
+
+    for website in connector.list_websites(session).await? {
+        match collect_unused_assets(connector, session, &website.website_id, true).await {
+            Ok(report) => deleted += report.assets.len(),
+            Err(e) => {
+                tracing::warn!(
+                    "Could not collect the unused assets of website {}: {}",
+                    website.website_id,
+                    e
+                );
+            }
+        }
+    }
+
+    Ok(deleted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectors::FsStorage;
+    use crate::models::{ConnectorFile, WebsiteMetaFileContent};
+    use serde_json::json;
+
+    /// A website of a storage in a temporary folder, with assets
+    async fn website(assets: &[&str]) -> (tempfile::TempDir, FsStorage, WebsiteId) {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = FsStorage::new(dir.path().to_path_buf(), "assets".to_string());
+        let session = json!({});
+        let meta = WebsiteMetaFileContent {
+            name: "Site".to_string(),
+            ..Default::default()
+        };
+        let website_id = storage.create_website(&session, &meta).await.unwrap();
+        let files = assets
+            .iter()
+            .map(|path| ConnectorFile {
+                path: format!("/{}", path),
+                content: path.as_bytes().to_vec(),
+            })
+            .collect();
+        storage
+            .write_assets(&session, &website_id, files)
+            .await
+            .unwrap();
+        (dir, storage, website_id)
+    }
+
+    /// Make assets look older than the minimum age
+    fn age(dir: &tempfile::TempDir, website_id: &str, assets: &[&str]) {
+        let old = std::time::SystemTime::now()
+            - std::time::Duration::from_secs(3600 * (UNUSED_ASSET_MIN_AGE_HOURS as u64 + 1));
+        for path in assets {
+            let path = dir.path().join(website_id).join("assets").join(path);
+            std::fs::File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(old)
+                .unwrap();
+        }
+    }
+
+    /// Website data using assets in its pages
+    fn data(assets: &[&str]) -> WebsiteData {
+        let html: Vec<_> = assets
+            .iter()
+            .map(|path| format!("<img src=\"/assets/{}\">", path))
+            .collect();
+        WebsiteData {
+            pages: vec![json!({ "id": "home", "html": html.join("") })],
+            ..Default::default()
+        }
+    }
+
+    /// Paths of the unused assets of a website
+    async fn unused(storage: &FsStorage, website_id: &WebsiteId, delete: bool) -> Vec<String> {
+        let report = collect_unused_assets(storage, &json!({}), website_id, delete)
+            .await
+            .unwrap();
+        let mut paths: Vec<_> = report.assets.into_iter().map(|a| a.path).collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn image_family_groups_upload_copies() {
+        assert_eq!(image_family("logo.png"), "logo");
+        assert_eq!(image_family("logo.webp"), "logo");
+        assert_eq!(image_family("logo-480w.png"), "logo");
+        assert_eq!(image_family("img/logo-1200w.webp"), "img/logo");
+        assert_eq!(image_family("my-logo.png"), "my-logo");
+        assert_eq!(image_family("logo-w.png"), "logo-w");
+        assert_eq!(image_family("logo-12.png"), "logo-12");
+        assert_eq!(image_family(".hidden"), ".hidden");
+        assert_eq!(image_family("dir.v2/readme"), "dir.v2/readme");
+    }
+
+    #[tokio::test]
+    async fn recent_assets_are_never_collected() {
+        let (dir, storage, website_id) = website(&["old.png", "new.png"]).await;
+        age(&dir, &website_id, &["old.png"]);
+
+        assert_eq!(unused(&storage, &website_id, false).await, ["/old.png"]);
+    }
+
+    #[tokio::test]
+    async fn used_assets_are_kept() {
+        let assets = [
+            "used.png",
+            "logo.png",
+            "logo-480w.png",
+            "logo.webp",
+            "old-version.png",
+            "cover.png",
+            "unused.png",
+        ];
+        let (dir, storage, website_id) = website(&assets).await;
+        age(&dir, &website_id, &assets);
+        let session = json!({});
+
+        // Used by a version of the history only
+        storage
+            .update_website(&session, &website_id, &data(&["old-version.png"]))
+            .await
+            .unwrap();
+        storage
+            .update_website(&session, &website_id, &data(&["used.png", "logo.png"]))
+            .await
+            .unwrap();
+        // The preview image
+        let meta = WebsiteMetaFileContent {
+            name: "Site".to_string(),
+            image_url: Some("/assets/cover.png".to_string()),
+            ..Default::default()
+        };
+        storage
+            .set_website_meta(&session, &website_id, &meta)
+            .await
+            .unwrap();
+
+        assert_eq!(unused(&storage, &website_id, false).await, ["/unused.png"]);
+    }
+
+    #[tokio::test]
+    async fn unused_assets_are_deleted_when_asked() {
+        let (dir, storage, website_id) = website(&["unused.png"]).await;
+        age(&dir, &website_id, &["unused.png"]);
+
+        assert_eq!(unused(&storage, &website_id, false).await, ["/unused.png"]);
+        assert_eq!(unused(&storage, &website_id, true).await, ["/unused.png"]);
+        assert!(unused(&storage, &website_id, false).await.is_empty());
+        assert!(!dir
+            .path()
+            .join(&website_id)
+            .join("assets/unused.png")
+            .exists());
+    }
+}
@@ -0,0 +1,551 @@
+/*
+ * Silex website builder, free/libre no-code tool for makers.
+ * Copyright (c) 2023 lexoyo and Silex Labs foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or any later version.
+ */
+
+//! Website bundles
+//!
+//! A bundle is a zip file holding a whole website, portable between servers
+//! and storage connectors:
+//! - website.json and the page files, in the layout of `website_files`
+//! - meta.json
+//! - assets/ with the asset files of the website
+//!
+//! Bundles are written and read through the `StorageConnector` trait,
+//! so a website can move from one connector to another.
+
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::connectors::website_files;
+use crate::connectors::{check_website_id, StorageConnector};
+use crate::error::{ConnectorError, ConnectorResult};
+use crate::models::{constants, ConnectorFile, WebsiteData, WebsiteId, WebsiteMetaFileContent};
+use crate::services::assets::website_asset_paths;
+
+/// Folder of the assets in a bundle, whatever the storage's assets folder
+pub const BUNDLE_ASSETS_FOLDER: &str = "assets";
+
+/// Maximum size of a bundle, compressed or not (512 MB)
+pub const MAX_BUNDLE_SIZE: usize = 512 * 1024 * 1024;
+
+/// Number of files read ahead of the zip writer
+const EXPORT_QUEUE_SIZE: usize = 4;
+
+/// Convert a zip error
+fn zip_error(e: zip::result::ZipError) -> ConnectorError {
+    match e {
+        zip::result::ZipError::Io(e) => ConnectorError::Io(e),
+        e => ConnectorError::InvalidInput(format!("Invalid zip file: {}", e)),
+    }
+}
+
+/// Export a website as a zip bundle
+///
+/// The bundle is written to an anonymous temporary file, which is returned
+/// ready to be streamed. Assets which are referenced but missing are skipped.
+pub async fn export_website(
+    connector: &dyn StorageConnector,
+    session: &serde_json::Value,
+    website_id: &WebsiteId,
+) -> ConnectorResult<tokio::fs::File> {
+    let data = connector.read_website(session, website_id).await?;
+    let meta = connector
+        .get_website_meta(session, website_id)
+        .await?
+        .to_file_content();
+
+    // Files are read here and written to the zip by a blocking task
+    let (sender, mut receiver) = tokio::sync::mpsc::channel::<(String, Vec<u8>)>(EXPORT_QUEUE_SIZE);
+    let writer = tokio::task::spawn_blocking(move || -> ConnectorResult<std::fs::File> {
+        let mut zip = ZipWriter::new(tempfile::tempfile()?);
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        while let Some((path, content)) = receiver.blocking_recv() {
+            zip.start_file(path, options).map_err(zip_error)?;
+            zip.write_all(&content)?;
+        }
+        let mut file = zip.finish().map_err(zip_error)?;
+        file.seek(SeekFrom::Start(0))?;
+        Ok(file)
+    });
+
+    let read = async move {
+        let mut files: Vec<(String, Vec<u8>)> =
+            website_files::split_website_data(&data, &Default::default())?
+                .into_iter()
+                .map(|(path, content)| (path, content.into_bytes()))
+                .collect();
+        files.push((
+            constants::WEBSITE_META_DATA_FILE.to_string(),
+            website_files::serialize_json(&meta)?.into_bytes(),
+        ));
+        for file in files {
+            if sender.send(file).await.is_err() {
+                // The writer failed, its error is returned below
+                return Ok(());
+            }
+        }
+
+        for path in website_asset_paths(connector, session, website_id, Some(&data)).await? {
+            let content = match connector.read_asset(session, website_id, &path).await {
+                Ok(content) => content,
+                Err(ConnectorError::NotFound(_)) => {
+                    tracing::warn!(
+                        "Asset {} of website {} not found, skipped",
+                        path,
+                        website_id
+                    );
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            let file = (format!("{}/{}", BUNDLE_ASSETS_FOLDER, path), content);
+            if sender.send(file).await.is_err() {
+                return Ok(());
+            }
+        }
+
+        Ok(())
+    };
+    let read: ConnectorResult<()> = read.await;
+
+    let file = writer.await.map_err(std::io::Error::other)??;
+    read?;
+
+    Ok(tokio::fs::File::from_std(file))
+}
+
+/// Content of a bundle, ready to be written to a storage connector
+struct Bundle {
+    /// Website data, with the pages loaded
+    data: WebsiteData,
+
+    /// Website metadata
+    meta: WebsiteMetaFileContent,
+
+    /// Asset files, with their path in the assets folder
+    assets: Vec<ConnectorFile>,
+}
+
+/// Files of a bundle by path, except the assets
+type BundleFiles = HashMap<String, Vec<u8>>;
+
+/// Read and validate a zip bundle
+fn read_bundle(content: &[u8]) -> ConnectorResult<(BundleFiles, Vec<ConnectorFile>)> {
+    let mut archive = ZipArchive::new(Cursor::new(content)).map_err(zip_error)?;
+
+    let mut files = HashMap::new();
+    let mut assets = Vec::new();
+    let mut total_size = 0;
+
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index).map_err(zip_error)?;
+        if entry.is_dir() {
+            continue;
+        }
+
+        // Reject paths which would leave the website folder
+        let name = entry.name().to_string();
+        let path = entry
+            .enclosed_name()
+            .and_then(|p| p.to_str().map(|p| p.replace('\\', "/")))
+            .filter(|p| !p.split('/').any(|part| part.is_empty() || part == ".."))
+            .ok_or_else(|| {
+                ConnectorError::InvalidInput(format!("Invalid path in bundle: {}", name))
+            })?;
+
+        // Protect against zip bombs, the announced size can not be trusted
+        let mut file_content = Vec::new();
+        let limit = (MAX_BUNDLE_SIZE - total_size) as u64 + 1;
+        entry.by_ref().take(limit).read_to_end(&mut file_content)?;
+        total_size += file_content.len();
+        if total_size > MAX_BUNDLE_SIZE {
+            return Err(ConnectorError::InvalidInput(format!(
+                "Bundle is larger than {} bytes once uncompressed",
+                MAX_BUNDLE_SIZE
+            )));
+        }
+
+        match path.strip_prefix(&format!("{}/", BUNDLE_ASSETS_FOLDER)) {
+            Some(asset_path) => assets.push(ConnectorFile {
+                path: format!("/{}", asset_path),
+                content: file_content,
+            }),
+            None => {
+                files.insert(path, file_content);
+            }
+        }
+    }
+
+    Ok((files, assets))
+}
+
+/// Load the website data and metadata of a bundle
+async fn load_bundle(content: Vec<u8>) -> ConnectorResult<Bundle> {
+    let (mut files, assets) = tokio::task::spawn_blocking(move || read_bundle(&content))
+        .await
+        .map_err(std::io::Error::other)??;
+
+    let text = |content: Vec<u8>, path: &str| {
+        String::from_utf8(content)
+            .map_err(|_| ConnectorError::InvalidInput(format!("{} is not valid UTF-8", path)))
+    };
+
+    let website_content = files.remove(constants::WEBSITE_DATA_FILE).ok_or_else(|| {
+        ConnectorError::InvalidInput(format!(
+            "Invalid bundle: {} is missing",
+            constants::WEBSITE_DATA_FILE
+        ))
+    })?;
+    let website_content = text(website_content, constants::WEBSITE_DATA_FILE)?;
+
+    // Pages are read from the bundle, they must all be there
+    let (data, report) = website_files::load_website_data(&website_content, |path| {
+        let page = match files.get(&path) {
+            Some(content) => text(content.clone(), &path),
+            None => Err(ConnectorError::NotFound(path)),
+        };
+        async move { page }
+    })
+    .await?;
+    if let Some(page) = report.missing_pages.first() {
+        return Err(ConnectorError::InvalidInput(format!(
+            "Invalid bundle: page file {} is missing",
+            page.file
+        )));
+    }
+    if let Some(page) = report.invalid_pages.first() {
+        return Err(ConnectorError::InvalidInput(format!(
+            "Invalid bundle: page file {} is not valid JSON",
+            page.file
+        )));
+    }
+
+    let meta = match files.remove(constants::WEBSITE_META_DATA_FILE) {
+        Some(content) => serde_json::from_slice(&content).map_err(|e| {
+            ConnectorError::InvalidInput(format!(
+                "Invalid bundle: {} is not valid: {}",
+                constants::WEBSITE_META_DATA_FILE,
+                e
+            ))
+        })?,
+        None => WebsiteMetaFileContent {
+            name: "Imported website".to_string(),
+            image_url: None,
+            connector_user_settings: Default::default(),
+            is_template: false,
+            ..Default::default()
+        },
+    };
+
+    Ok(Bundle { data, meta, assets })
+}
+
+/// Import a website from a zip bundle
+///
+/// The website gets a new ID, unless `website_id` is set and is not used yet.
+/// It is written with `create_website` (or `create_website_with_id`),
+/// `update_website` and `write_assets`, and purged if one of them fails.
+pub async fn import_website(
+    connector: &dyn StorageConnector,
+    session: &serde_json::Value,
+    content: Vec<u8>,
+    website_id: Option<WebsiteId>,
+) -> ConnectorResult<WebsiteId> {
+    let bundle = load_bundle(content).await?;
+
+    let website_id = match website_id {
+        Some(website_id) => {
+            check_website_id(&website_id)?;
+            connector
+                .create_website_with_id(session, &website_id, &bundle.meta)
+                .await?;
+            website_id
+        }
+        None => connector.create_website(session, &bundle.meta).await?,
+    };
+
+    let written = async {
+        connector
+            .update_website(session, &website_id, &bundle.data)
+            .await?;
+        if !bundle.assets.is_empty() {
+            connector
+                .write_assets(session, &website_id, bundle.assets)
+                .await?;
+        }
+        Ok::<_, ConnectorError>(())
+    };
+    if let Err(e) = written.await {
+        if let Err(cleanup) = connector.purge_website(session, &website_id).await {
+            tracing::warn!(
+                "Could not remove partially imported website {}: {}",
+                website_id,
+                cleanup
+            );
+        }
+        return Err(e);
+    }
+
+    Ok(website_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectors::FsStorage;
+    use serde_json::json;
+    use tokio::io::AsyncReadExt;
+
+    /// A storage in a temporary folder
+    fn storage() -> (tempfile::TempDir, FsStorage) {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = FsStorage::new(dir.path().to_path_buf(), "assets".to_string());
+        (dir, storage)
+    }
+
+    /// A website with a page and an asset
+    async fn website(storage: &FsStorage) -> WebsiteId {
+        let session = json!({});
+        let meta = WebsiteMetaFileContent {
+            name: "My site".to_string(),
+            ..Default::default()
+        };
+        let website_id = storage.create_website(&session, &meta).await.unwrap();
+        let data = WebsiteData {
+            pages: vec![
+                json!({ "id": "home", "name": "Home", "html": "<img src=\"/assets/img/a.png\">" }),
+            ],
+            ..Default::default()
+        };
+        storage
+            .update_website(&session, &website_id, &data)
+            .await
+            .unwrap();
+        storage
+            .write_assets(
+                &session,
+                &website_id,
+                vec![ConnectorFile {
+                    path: "/img/a.png".to_string(),
+                    content: b"png".to_vec(),
+                }],
+            )
+            .await
+            .unwrap();
+        website_id
+    }
+
+    /// Export a website and read the bundle back
+    async fn export(storage: &FsStorage, website_id: &WebsiteId) -> Vec<u8> {
+        let mut file = export_website(storage, &json!({}), website_id)
+            .await
+            .unwrap();
+        let mut content = Vec::new();
+        file.read_to_end(&mut content).await.unwrap();
+        content
+    }
+
+    #[tokio::test]
+    async fn export_holds_data_pages_meta_and_assets() {
+        let (_dir, storage) = storage();
+        let website_id = website(&storage).await;
+
+        let (files, assets) = read_bundle(&export(&storage, &website_id).await).unwrap();
+
+        let mut paths: Vec<_> = files.keys().map(String::as_str).collect();
+        paths.sort();
+        assert_eq!(paths, ["meta.json", "pages/home-home.json", "website.json"]);
+        let meta: serde_json::Value = serde_json::from_slice(&files["meta.json"]).unwrap();
+        assert_eq!(meta["name"], "My site");
+        assert_eq!(assets.len(), 1);
+        assert_eq!(assets[0].path, "/img/a.png");
+        assert_eq!(assets[0].content, b"png");
+    }
+
+    #[tokio::test]
+    async fn exported_website_imports_the_same() {
+        let (_dir, storage) = storage();
+        let website_id = website(&storage).await;
+        let bundle = export(&storage, &website_id).await;
+
+        let (_other_dir, other) = self::storage();
+        let imported = import_website(&other, &json!({}), bundle, None)
+            .await
+            .unwrap();
+
+        let session = json!({});
+        assert_eq!(
+            other.read_website(&session, &imported).await.unwrap().pages,
+            storage
+                .read_website(&session, &website_id)
+                .await
+                .unwrap()
+                .pages
+        );
+        assert_eq!(
+            other
+                .read_asset(&session, &imported, "/img/a.png")
+                .await
+                .unwrap(),
+            b"png"
+        );
+        assert_eq!(
+            other
+                .get_website_meta(&session, &imported)
+                .await
+                .unwrap()
+                .name,
+            "My site"
+        );
+    }
+
+    /// A zip file with files
+    fn zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (path, content) in files {
+            zip.start_file(*path, SimpleFileOptions::default()).unwrap();
+            zip.write_all(content).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    /// The website.json of a bundle with one page file
+    const WEBSITE: &[u8] = br#"{ "pagesFolder": "pages", "pages": [{ "id": "a", "name": "A", "isFile": true, "file": "a.json" }] }"#;
+
+    /// The error message of an invalid bundle
+    async fn invalid(bundle: Vec<u8>) -> String {
+        match load_bundle(bundle).await {
+            Err(ConnectorError::InvalidInput(message)) => message,
+            Err(e) => panic!("expected invalid input, got {}", e),
+            Ok(_) => panic!("expected invalid input"),
+        }
+    }
+
+    #[test]
+    fn read_bundle_rejects_paths_out_of_the_website() {
+        for path in ["../evil.json", "assets/../../evil.png", "/etc/evil", "a//b"] {
+            let result = read_bundle(&zip(&[(path, b"x")]));
+            assert!(
+                matches!(&result, Err(ConnectorError::InvalidInput(m)) if m.contains("Invalid path")),
+                "{} was accepted",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn read_bundle_separates_assets() {
+        let (files, assets) = read_bundle(&zip(&[
+            ("website.json", b"{}"),
+            ("assets/img/a.png", b"png"),
+            ("assets-other/b.png", b"b"),
+        ]))
+        .unwrap();
+
+        assert_eq!(files.len(), 2);
+        assert!(files.contains_key("assets-other/b.png"));
+        assert_eq!(assets.len(), 1);
+        assert_eq!(assets[0].path, "/img/a.png");
+    }
+
+    #[test]
+    fn read_bundle_rejects_other_files() {
+        assert!(matches!(
+            read_bundle(b"not a zip"),
+            Err(ConnectorError::InvalidInput(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn load_bundle_requires_the_website_and_its_pages() {
+        assert!(invalid(zip(&[("meta.json", b"{}")]))
+            .await
+            .contains("website.json is missing"));
+        assert!(invalid(zip(&[("website.json", WEBSITE)]))
+            .await
+            .contains("pages/a.json is missing"));
+        assert!(
+            invalid(zip(&[("website.json", WEBSITE), ("pages/a.json", b"{")]))
+                .await
+                .contains("pages/a.json is not valid JSON")
+        );
+        assert!(invalid(zip(&[("website.json", b"\xff")]))
+            .await
+            .contains("UTF-8"));
+        assert!(invalid(zip(&[
+            ("website.json", WEBSITE),
+            ("pages/a.json", b"{}"),
+            ("meta.json", b"[]"),
+        ]))
+        .await
+        .contains("meta.json is not valid"));
+    }
+
+    #[tokio::test]
+    async fn load_bundle_without_meta() {
+        let bundle = load_bundle(zip(&[
+            ("website.json", WEBSITE),
+            ("pages/a.json", br#"{ "id": "a" }"#),
+        ]))
+        .await
+        .unwrap();
+
+        assert_eq!(bundle.meta.name, "Imported website");
+        assert_eq!(bundle.data.pages, [json!({ "id": "a" })]);
+    }
+
+    #[tokio::test]
+    async fn import_with_an_unused_id() {
+        let (_dir, storage) = storage();
+        let bundle = || {
+            zip(&[
+                ("website.json", WEBSITE),
+                ("pages/a.json", br#"{ "id": "a" }"#),
+            ])
+        };
+        let session = json!({});
+
+        let website_id = import_website(&storage, &session, bundle(), Some("my-site".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(website_id, "my-site");
+        assert!(storage.read_website(&session, &website_id).await.is_ok());
+
+        // The ID is used now
+        assert!(
+            import_website(&storage, &session, bundle(), Some("my-site".to_string()))
+                .await
+                .is_err()
+        );
+        assert!(matches!(
+            import_website(&storage, &session, bundle(), Some("../x".to_string())).await,
+            Err(ConnectorError::InvalidInput(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn failed_import_is_purged() {
+        let (dir, storage) = storage();
+        // Loaded, but refused by the storage when written
+        let website = br#"{ "pagesFolder": "../pages", "pages": [] }"#;
+
+        let result = import_website(
+            &storage,
+            &json!({}),
+            zip(&[("website.json", website)]),
+            Some("broken".to_string()),
+        )
+        .await;
+
+        assert!(matches!(result, Err(ConnectorError::InvalidInput(_))));
+        assert!(!dir.path().join("broken").exists());
+    }
+}
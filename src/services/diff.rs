@@ -0,0 +1,359 @@
+/*
+ * Silex website builder, free/libre no-code tool for makers.
+ * Copyright (c) 2023 lexoyo and Silex Labs foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or any later version.
+ */
+
+//! Structural diff between two versions of a website
+//!
+//! Compares pages, styles, assets and settings, for review before publishing.
+//! JSON values are compared in their stable serialization (sorted keys),
+//! the same one used to store website data.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::connectors::website_files::sort_json_keys;
+use crate::models::{
+    AssetsDiff, PageRename, PageSummary, PagesDiff, StyleChange, StylesDiff, ValueChange,
+    WebsiteData, WebsiteDiff,
+};
+
+/// Compare two versions of a website's data
+pub fn diff_websites(from: &WebsiteData, to: &WebsiteData) -> WebsiteDiff {
+    WebsiteDiff {
+        pages: diff_pages(&from.pages, &to.pages),
+        styles: diff_styles(&from.styles, &to.styles),
+        assets: diff_assets(&from.assets, &to.assets),
+        settings: diff_object(&from.settings, &to.settings),
+    }
+}
+
+/// Stable string form of a JSON value, used as an identity or for comparison
+fn canonical(value: &serde_json::Value) -> String {
+    sort_json_keys(value).to_string()
+}
+
+/// Get a string field of a JSON object
+fn get_str<'a>(value: &'a serde_json::Value, field: &str) -> Option<&'a str> {
+    value.get(field).and_then(|v| v.as_str())
+}
+
+/// Compare pages by ID
+///
+/// Pages without ID (like the empty page of a new website) are ignored.
+fn diff_pages(from: &[serde_json::Value], to: &[serde_json::Value]) -> PagesDiff {
+    let summary = |page: &serde_json::Value| {
+        get_str(page, "id").map(|id| PageSummary {
+            id: id.to_string(),
+            name: get_str(page, "name").unwrap_or_default().to_string(),
+        })
+    };
+    let old_pages: HashMap<&str, &serde_json::Value> = from
+        .iter()
+        .filter_map(|p| get_str(p, "id").map(|id| (id, p)))
+        .collect();
+    let new_ids: Vec<&str> = to.iter().filter_map(|p| get_str(p, "id")).collect();
+
+    let mut diff = PagesDiff::default();
+
+    for page in to {
+        let Some(new) = summary(page) else {
+            continue;
+        };
+        let Some(old_page) = old_pages.get(new.id.as_str()) else {
+            diff.added.push(new);
+            continue;
+        };
+
+        let old_name = get_str(old_page, "name").unwrap_or_default();
+        if old_name != new.name {
+            diff.renamed.push(PageRename {
+                id: new.id.clone(),
+                from: old_name.to_string(),
+                to: new.name.clone(),
+            });
+        }
+
+        // Compare the content, without the name
+        let content = |page: &serde_json::Value| {
+            let mut page = page.clone();
+            if let Some(map) = page.as_object_mut() {
+                map.remove("name");
+            }
+            canonical(&page)
+        };
+        if content(old_page) != content(page) {
+            diff.modified.push(new);
+        }
+    }
+
+    diff.removed = from
+        .iter()
+        .filter_map(summary)
+        .filter(|old| !new_ids.contains(&old.id.as_str()))
+        .collect();
+
+    diff
+}
+
+/// Compare CSS rules, identified by everything but their declarations
+fn diff_styles(from: &[serde_json::Value], to: &[serde_json::Value]) -> StylesDiff {
+    // Split a rule into its identity and its declarations
+    let split = |style: &serde_json::Value| {
+        let mut rule = sort_json_keys(style);
+        let declarations = rule
+            .as_object_mut()
+            .and_then(|map| map.remove("style"))
+            .unwrap_or_else(|| serde_json::json!({}));
+        (canonical(&rule), rule, declarations)
+    };
+
+    let old_rules: BTreeMap<String, (serde_json::Value, serde_json::Value)> = from
+        .iter()
+        .map(split)
+        .map(|(key, rule, declarations)| (key, (rule, declarations)))
+        .collect();
+    let new_rules: BTreeMap<String, (serde_json::Value, serde_json::Value)> = to
+        .iter()
+        .map(split)
+        .map(|(key, rule, declarations)| (key, (rule, declarations)))
+        .collect();
+
+    let mut diff = StylesDiff::default();
+
+    for (key, (rule, declarations)) in &new_rules {
+        match old_rules.get(key) {
+            None => diff
+                .added
+                .push(sort_json_keys(&to_style(rule, declarations))),
+            Some((_, old_declarations)) => {
+                if canonical(old_declarations) != canonical(declarations) {
+                    diff.changed.push(StyleChange {
+                        rule: rule.clone(),
+                        from: old_declarations.clone(),
+                        to: declarations.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    for (key, (rule, declarations)) in &old_rules {
+        if !new_rules.contains_key(key) {
+            diff.removed
+                .push(sort_json_keys(&to_style(rule, declarations)));
+        }
+    }
+
+    diff
+}
+
+/// Put the declarations back into a rule
+fn to_style(rule: &serde_json::Value, declarations: &serde_json::Value) -> serde_json::Value {
+    let mut style = rule.clone();
+    if let Some(map) = style.as_object_mut() {
+        map.insert("style".to_string(), declarations.clone());
+    }
+    style
+}
+
+/// Compare assets, identified by their `src`
+fn diff_assets(from: &[serde_json::Value], to: &[serde_json::Value]) -> AssetsDiff {
+    let key = |asset: &serde_json::Value| match asset {
+        serde_json::Value::String(src) => src.clone(),
+        _ => get_str(asset, "src")
+            .map(String::from)
+            .unwrap_or_else(|| canonical(asset)),
+    };
+    let old_assets: Vec<String> = from.iter().map(key).collect();
+    let new_assets: Vec<String> = to.iter().map(key).collect();
+
+    AssetsDiff {
+        added: new_assets
+            .iter()
+            .filter(|a| !old_assets.contains(a))
+            .cloned()
+            .collect(),
+        removed: old_assets
+            .iter()
+            .filter(|a| !new_assets.contains(a))
+            .cloned()
+            .collect(),
+    }
+}
+
+/// Compare the top level keys of two JSON objects
+///
+/// Values which are not objects are compared as a whole, with an empty key.
+fn diff_object(from: &serde_json::Value, to: &serde_json::Value) -> Vec<ValueChange> {
+    let (Some(old_map), Some(new_map)) = (from.as_object(), to.as_object()) else {
+        if canonical(from) == canonical(to) {
+            return Vec::new();
+        }
+        return vec![ValueChange {
+            key: String::new(),
+            from: Some(from.clone()),
+            to: Some(to.clone()),
+        }];
+    };
+
+    let mut keys: Vec<&String> = old_map.keys().chain(new_map.keys()).collect();
+    keys.sort();
+    keys.dedup();
+
+    keys.into_iter()
+        .filter_map(|key| {
+            let old = old_map.get(key);
+            let new = new_map.get(key);
+            if old.map(canonical) == new.map(canonical) {
+                return None;
+            }
+            Some(ValueChange {
+                key: key.clone(),
+                from: old.cloned(),
+                to: new.cloned(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Website data from its JSON form
+    fn website(value: serde_json::Value) -> WebsiteData {
+        serde_json::from_value(value).unwrap()
+    }
+
+    /// The JSON form of the diff of two websites
+    fn diff(from: serde_json::Value, to: serde_json::Value) -> serde_json::Value {
+        serde_json::to_value(diff_websites(&website(from), &website(to))).unwrap()
+    }
+
+    #[test]
+    fn identical_websites_have_no_changes() {
+        let data = json!({
+            "pages": [{ "id": "home", "name": "Home", "frames": [] }],
+            "styles": [{ "selectors": ["#a"], "style": { "color": "red" } }],
+            "assets": ["/assets/a.png"],
+            "settings": { "title": "Site" },
+        });
+
+        let diff = diff_websites(&website(data.clone()), &website(data));
+        assert!(diff.pages.added.is_empty() && diff.pages.removed.is_empty());
+        assert!(diff.pages.modified.is_empty() && diff.pages.renamed.is_empty());
+        assert!(diff.styles.added.is_empty() && diff.styles.changed.is_empty());
+        assert!(diff.assets.added.is_empty() && diff.settings.is_empty());
+    }
+
+    #[test]
+    fn pages_are_compared_by_id() {
+        let diff = diff(
+            json!({ "pages": [
+                { "id": "home", "name": "Home", "frames": [1] },
+                { "id": "about", "name": "About" },
+                { "id": "old", "name": "Old" },
+                { "name": "Without ID" },
+            ] }),
+            json!({ "pages": [
+                { "id": "home", "name": "Home", "frames": [2] },
+                { "id": "about", "name": "About us" },
+                { "id": "new", "name": "New" },
+            ] }),
+        );
+
+        assert_eq!(
+            diff["pages"]["added"],
+            json!([{ "id": "new", "name": "New" }])
+        );
+        assert_eq!(
+            diff["pages"]["removed"],
+            json!([{ "id": "old", "name": "Old" }])
+        );
+        // A rename alone does not modify the content
+        assert_eq!(
+            diff["pages"]["modified"],
+            json!([{ "id": "home", "name": "Home" }])
+        );
+        assert_eq!(
+            diff["pages"]["renamed"],
+            json!([{ "id": "about", "from": "About", "to": "About us" }])
+        );
+    }
+
+    #[test]
+    fn styles_are_identified_by_their_rule() {
+        let diff = diff(
+            json!({ "styles": [
+                { "selectors": ["#a"], "style": { "color": "red" } },
+                { "selectors": ["#a"], "mediaText": "(max-width: 480px)", "style": { "color": "red" } },
+                { "selectors": ["#b"], "style": {} },
+            ] }),
+            json!({ "styles": [
+                { "style": { "color": "blue" }, "selectors": ["#a"] },
+                { "selectors": ["#a"], "mediaText": "(max-width: 480px)", "style": { "color": "red" } },
+                { "selectors": ["#c"], "style": { "margin": 0 } },
+            ] }),
+        );
+
+        assert_eq!(
+            diff["styles"]["changed"],
+            json!([{
+                "rule": { "selectors": ["#a"] },
+                "from": { "color": "red" },
+                "to": { "color": "blue" },
+            }])
+        );
+        assert_eq!(
+            diff["styles"]["added"],
+            json!([{ "selectors": ["#c"], "style": { "margin": 0 } }])
+        );
+        assert_eq!(
+            diff["styles"]["removed"],
+            json!([{ "selectors": ["#b"], "style": {} }])
+        );
+    }
+
+    #[test]
+    fn assets_are_identified_by_their_source() {
+        let diff = diff(
+            json!({ "assets": ["/assets/a.png", { "src": "/assets/b.png" }] }),
+            json!({ "assets": [{ "src": "/assets/a.png", "width": 10 }, "/assets/c.png"] }),
+        );
+
+        assert_eq!(diff["assets"]["added"], json!(["/assets/c.png"]));
+        assert_eq!(diff["assets"]["removed"], json!(["/assets/b.png"]));
+    }
+
+    #[test]
+    fn settings_are_compared_by_key() {
+        let diff = diff(
+            json!({ "settings": { "title": "Site", "lang": "en", "head": { "a": 1, "b": 2 } } }),
+            json!({ "settings": { "title": "My site", "head": { "b": 2, "a": 1 }, "favicon": "/f.ico" } }),
+        );
+
+        assert_eq!(
+            diff["settings"],
+            json!([
+                { "key": "favicon", "from": null, "to": "/f.ico" },
+                { "key": "lang", "from": "en", "to": null },
+                { "key": "title", "from": "Site", "to": "My site" },
+            ])
+        );
+    }
+
+    #[test]
+    fn settings_which_are_not_objects_are_compared_whole() {
+        let changes = diff_object(&json!(null), &json!({ "title": "Site" }));
+
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].key, "");
+        assert_eq!(changes[0].to, Some(json!({ "title": "Site" })));
+        assert!(diff_object(&json!(null), &json!(null)).is_empty());
+    }
+}
@@ -0,0 +1,561 @@
+/*
+ * Silex website builder, free/libre no-code tool for makers.
+ * Copyright (c) 2023 lexoyo and Silex Labs foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or any later version.
+ */
+
+//! Processing of uploaded images
+//!
+//! Images uploaded as assets are:
+//! - measured, so that the editor knows their dimensions
+//! - downscaled when larger than the configured maximum size
+//! - stripped of their metadata (EXIF, XMP, comments), which may hold the
+//!   position where a photo was taken. Files are only re-encoded when they
+//!   are rotated or resized, otherwise the metadata is cut out losslessly.
+//! - declined in smaller widths, and in WebP, next to the original:
+//!   `logo.png` gets `logo-480w.png`, `logo-480w.webp` and `logo.webp`
+//!
+//! The WebP encoder is lossless: WebP copies of lossy JPEG images would be
+//! larger than the JPEG files, so JPEG images only get copies in JPEG.
+//!
+//! JPEG, PNG and WebP images are processed. GIF images are only measured,
+//! to keep their animation. Other files are stored as they are.
+
+use std::io::Cursor;
+
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::imageops::FilterType;
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader};
+
+use crate::config::Config;
+use crate::error::{ConnectorError, ConnectorResult};
+use crate::models::{ConnectorFile, ImageInfo, ImageVariant};
+
+/// Quality of the re-encoded JPEG images
+const JPEG_QUALITY: u8 = 90;
+
+/// How uploaded images are processed
+#[derive(Debug, Clone, Default)]
+pub struct ImageOptions {
+    /// Maximum width and height, larger images are downscaled
+    pub max_size: Option<u32>,
+
+    /// Remove the metadata of the images
+    pub strip_metadata: bool,
+
+    /// Widths of the smaller copies of the images
+    pub variant_widths: Vec<u32>,
+
+    /// Also make WebP copies of the lossless images
+    pub webp_variants: bool,
+}
+
+impl ImageOptions {
+    /// Get the image options from config
+    pub fn from_config(config: &Config) -> Self {
+        ImageOptions {
+            max_size: config.image_max_size,
+            strip_metadata: config.image_strip_metadata,
+            variant_widths: config.image_variant_widths.clone(),
+            webp_variants: config.image_webp_variants,
+        }
+    }
+}
+
+/// Process an uploaded asset
+///
+/// Returns the files to write, the original first, and the image information
+/// with the asset paths of the original and the variants.
+/// Images which can not be decoded are stored as they are.
+pub async fn process_asset(
+    file: ConnectorFile,
+    options: &ImageOptions,
+) -> ConnectorResult<(Vec<ConnectorFile>, Option<ImageInfo>)> {
+    let Some(format) = image_format(&file.path) else {
+        return Ok((vec![file], None));
+    };
+
+    let options = options.clone();
+    tokio::task::spawn_blocking(move || {
+        let original = file.clone();
+        match process_image(file, format, &options) {
+            Ok(result) => result,
+            Err(e) => {
+                tracing::warn!("Could not process image {}: {}", original.path, e);
+                (vec![original], None)
+            }
+        }
+    })
+    .await
+    .map_err(|e| ConnectorError::Io(std::io::Error::other(e)))
+}
+
+/// Whether a file is an image which uploads process, from its path
+pub fn is_processed_image(path: &str) -> bool {
+    image_format(path).is_some()
+}
+
+/// Get the format of an image from its path, for the supported formats
+pub(crate) fn image_format(path: &str) -> Option<ImageFormat> {
+    ImageFormat::from_path(path).ok().filter(|format| {
+        matches!(
+            format,
+            ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::WebP | ImageFormat::Gif
+        )
+    })
+}
+
+/// Get the dimensions of an encoded image, as displayed
+///
+/// Only the headers are decoded, and the EXIF orientation is applied.
+pub(crate) fn image_dimensions(
+    content: &[u8],
+    format: ImageFormat,
+) -> image::ImageResult<(u32, u32)> {
+    let mut decoder = ImageReader::with_format(Cursor::new(content), format).into_decoder()?;
+    let (width, height) = decoder.dimensions();
+    let transposed = matches!(
+        decoder.orientation().unwrap_or(Orientation::NoTransforms),
+        Orientation::Rotate90
+            | Orientation::Rotate270
+            | Orientation::Rotate90FlipH
+            | Orientation::Rotate270FlipH
+    );
+    Ok(if transposed {
+        (height, width)
+    } else {
+        (width, height)
+    })
+}
+
+/// Make a smaller copy of an encoded image, in the same format
+///
+/// Returns None when the image is not wider than `width`. The copy is upright
+/// and has no metadata. Animated GIF images keep their first frame.
+pub(crate) fn resize_image(
+    content: &[u8],
+    format: ImageFormat,
+    width: u32,
+) -> image::ImageResult<Option<Vec<u8>>> {
+    let mut decoder = ImageReader::with_format(Cursor::new(content), format).into_decoder()?;
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+
+    if image.width() <= width {
+        return Ok(None);
+    }
+    let resized = image.resize(width, u32::MAX, FilterType::Lanczos3);
+    encode(&resized, format).map(Some)
+}
+
+/// Process an image, in a blocking task
+fn process_image(
+    mut file: ConnectorFile,
+    format: ImageFormat,
+    options: &ImageOptions,
+) -> image::ImageResult<(Vec<ConnectorFile>, Option<ImageInfo>)> {
+    let reader = ImageReader::with_format(Cursor::new(&file.content), format);
+
+    // Animated images would lose their animation
+    if format == ImageFormat::Gif {
+        let (width, height) = reader.into_dimensions()?;
+        let info = image_info(&file.path, width, height);
+        return Ok((vec![file], Some(info)));
+    }
+
+    let mut decoder = reader.into_decoder()?;
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+
+    let too_large = options
+        .max_size
+        .map(|max| image.width() > max || image.height() > max)
+        .unwrap_or(false);
+    if let Some(max) = options.max_size.filter(|_| too_large) {
+        image = image.resize(max, max, FilterType::Lanczos3);
+    }
+
+    // Rotating for good means re-encoding, as removing the orientation would show it wrong
+    let rotated = orientation != Orientation::NoTransforms;
+    if too_large || (rotated && options.strip_metadata) {
+        file.content = encode(&image, format)?;
+    } else if options.strip_metadata {
+        file.content = strip_metadata(&file.content, format).unwrap_or_else(|| {
+            tracing::warn!("Could not strip the metadata of {}, kept as is", file.path);
+            file.content.clone()
+        });
+    }
+
+    let mut info = image_info(&file.path, image.width(), image.height());
+    let (stem, extension) = split_extension(&file.path);
+    let (stem, extension) = (stem.to_string(), extension.to_string());
+    let mut files = vec![file];
+
+    // Lossless WebP copies are only worth it for lossless images
+    let webp_variants = options.webp_variants && format == ImageFormat::Png;
+
+    let mut widths = options.variant_widths.clone();
+    widths.sort_unstable();
+    widths.dedup();
+    for width in widths.into_iter().filter(|&w| w > 0 && w < image.width()) {
+        let resized = image.resize(width, u32::MAX, FilterType::Lanczos3);
+        let mut formats = vec![(format, extension.clone())];
+        if webp_variants {
+            formats.push((ImageFormat::WebP, "webp".to_string()));
+        }
+        for (variant_format, variant_extension) in formats {
+            let path = format!("{}-{}w.{}", stem, width, variant_extension);
+            info.variants.push(variant(&path, &resized, variant_format));
+            files.push(ConnectorFile {
+                path,
+                content: encode(&resized, variant_format)?,
+            });
+        }
+    }
+
+    if webp_variants {
+        let path = format!("{}.webp", stem);
+        info.variants
+            .push(variant(&path, &image, ImageFormat::WebP));
+        files.push(ConnectorFile {
+            path,
+            content: encode(&image, ImageFormat::WebP)?,
+        });
+    }
+
+    Ok((files, Some(info)))
+}
+
+/// Build the information of an image, without variants
+fn image_info(path: &str, width: u32, height: u32) -> ImageInfo {
+    ImageInfo {
+        src: path.to_string(),
+        width,
+        height,
+        variants: Vec::new(),
+    }
+}
+
+/// Build the information of an image variant
+fn variant(path: &str, image: &DynamicImage, format: ImageFormat) -> ImageVariant {
+    ImageVariant {
+        src: path.to_string(),
+        width: image.width(),
+        height: image.height(),
+        content_type: format.to_mime_type().to_string(),
+    }
+}
+
+/// Split a path into its stem and extension
+fn split_extension(path: &str) -> (&str, &str) {
+    match path.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() && !extension.contains('/') => {
+            (stem, extension)
+        }
+        _ => (path, ""),
+    }
+}
+
+/// Encode an image, WebP images are lossless
+fn encode(image: &DynamicImage, format: ImageFormat) -> image::ImageResult<Vec<u8>> {
+    let mut content = Vec::new();
+    match format {
+        ImageFormat::Jpeg => {
+            // JPEG has no transparency
+            let encoder = JpegEncoder::new_with_quality(&mut content, JPEG_QUALITY);
+            DynamicImage::ImageRgb8(image.to_rgb8()).write_with_encoder(encoder)?;
+        }
+        ImageFormat::WebP => {
+            let encoder = WebPEncoder::new_lossless(&mut content);
+            DynamicImage::ImageRgba8(image.to_rgba8()).write_with_encoder(encoder)?;
+        }
+        format => image.write_to(&mut Cursor::new(&mut content), format)?,
+    }
+    Ok(content)
+}
+
+// ==================
+// Metadata removal
+// Cuts the metadata out of the file, without decoding the image
+// ==================
+
+/// Remove the metadata of an encoded image
+///
+/// Returns None when the file structure is not the expected one.
+fn strip_metadata(content: &[u8], format: ImageFormat) -> Option<Vec<u8>> {
+    match format {
+        ImageFormat::Jpeg => strip_jpeg_metadata(content),
+        ImageFormat::Png => strip_png_metadata(content),
+        ImageFormat::WebP => strip_webp_metadata(content),
+        _ => None,
+    }
+}
+
+/// Remove the EXIF/XMP (APP1), Photoshop/IPTC (APP13) and comment segments of a JPEG file
+///
+/// The color profile (APP2) is kept.
+fn strip_jpeg_metadata(content: &[u8]) -> Option<Vec<u8>> {
+    const APP1: u8 = 0xE1;
+    const APP13: u8 = 0xED;
+    const COMMENT: u8 = 0xFE;
+    const START_OF_SCAN: u8 = 0xDA;
+
+    if !content.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    let mut stripped = content[..2].to_vec();
+    let mut i = 2;
+
+    loop {
+        if *content.get(i)? != 0xFF {
+            return None;
+        }
+        let marker = *content.get(i + 1)?;
+        // Fill bytes
+        if marker == 0xFF {
+            i += 1;
+            continue;
+        }
+        // The image data follows, with no more metadata
+        if marker == START_OF_SCAN {
+            stripped.extend_from_slice(&content[i..]);
+            return Some(stripped);
+        }
+        let length = u16::from_be_bytes([*content.get(i + 2)?, *content.get(i + 3)?]) as usize;
+        let end = i + 2 + length;
+        let segment = content.get(i..end)?;
+        if !matches!(marker, APP1 | APP13 | COMMENT) {
+            stripped.extend_from_slice(segment);
+        }
+        i = end;
+    }
+}
+
+/// Remove the EXIF, text and time chunks of a PNG file
+fn strip_png_metadata(content: &[u8]) -> Option<Vec<u8>> {
+    const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+    const METADATA_CHUNKS: [&[u8]; 5] = [b"eXIf", b"tEXt", b"iTXt", b"zTXt", b"tIME"];
+
+    if !content.starts_with(SIGNATURE) {
+        return None;
+    }
+    let mut stripped = SIGNATURE.to_vec();
+    let mut i = SIGNATURE.len();
+
+    while i < content.len() {
+        let length = u32::from_be_bytes(content.get(i..i + 4)?.try_into().ok()?) as usize;
+        let chunk_type = content.get(i + 4..i + 8)?;
+        // Length, type, data and CRC
+        let end = i + 12 + length;
+        let chunk = content.get(i..end)?;
+        if !METADATA_CHUNKS.contains(&chunk_type) {
+            stripped.extend_from_slice(chunk);
+        }
+        i = end;
+    }
+
+    Some(stripped)
+}
+
+/// Flag of the WebP VP8X chunk telling that the file has an EXIF chunk
+const VP8X_EXIF_FLAG: u8 = 0x08;
+
+/// Flag of the WebP VP8X chunk telling that the file has an XMP chunk
+const VP8X_XMP_FLAG: u8 = 0x04;
+
+/// Remove the EXIF and XMP chunks of a WebP file
+fn strip_webp_metadata(content: &[u8]) -> Option<Vec<u8>> {
+    if content.get(0..4)? != b"RIFF" || content.get(8..12)? != b"WEBP" {
+        return None;
+    }
+    let mut chunks = Vec::new();
+    let mut i = 12;
+
+    while i < content.len() {
+        let chunk_type = content.get(i..i + 4)?;
+        let length = u32::from_le_bytes(content.get(i + 4..i + 8)?.try_into().ok()?) as usize;
+        // Type, length and data, padded to an even size
+        let end = (i + 8 + length + (length & 1)).min(content.len());
+        let mut chunk = content.get(i..end)?.to_vec();
+        match chunk_type {
+            b"EXIF" | b"XMP " => {}
+            b"VP8X" => {
+                // The flags tell which chunks are present
+                *chunk.get_mut(8)? &= !(VP8X_EXIF_FLAG | VP8X_XMP_FLAG);
+                chunks.push(chunk);
+            }
+            _ => chunks.push(chunk),
+        }
+        i = end;
+    }
+
+    let body: Vec<u8> = chunks.concat();
+    let mut stripped = b"RIFF".to_vec();
+    stripped.extend_from_slice(&(body.len() as u32 + 4).to_le_bytes());
+    stripped.extend_from_slice(b"WEBP");
+    stripped.extend_from_slice(&body);
+    Some(stripped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A JPEG segment: marker and big-endian length, which counts itself
+    fn jpeg_segment(marker: u8, data: &[u8]) -> Vec<u8> {
+        let mut segment = vec![0xFF, marker];
+        segment.extend_from_slice(&(data.len() as u16 + 2).to_be_bytes());
+        segment.extend_from_slice(data);
+        segment
+    }
+
+    /// A PNG chunk: length, type, data and a CRC (not checked by the stripper)
+    fn png_chunk(chunk_type: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(chunk_type);
+        chunk.extend_from_slice(data);
+        chunk.extend_from_slice(&[0, 0, 0, 0]);
+        chunk
+    }
+
+    /// A WebP chunk: type, little-endian length and data padded to an even size
+    fn webp_chunk(chunk_type: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut chunk = chunk_type.to_vec();
+        chunk.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        chunk.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            chunk.push(0);
+        }
+        chunk
+    }
+
+    /// A WebP file made of chunks
+    fn webp_file(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let body = chunks.concat();
+        let mut file = b"RIFF".to_vec();
+        file.extend_from_slice(&(body.len() as u32 + 4).to_le_bytes());
+        file.extend_from_slice(b"WEBP");
+        file.extend_from_slice(&body);
+        file
+    }
+
+    #[test]
+    fn strip_jpeg_removes_exif_and_comments() {
+        let app0 = jpeg_segment(0xE0, b"JFIF\0\x01\x01");
+        let app2 = jpeg_segment(0xE2, b"ICC_PROFILE\0");
+        let scan = [0xFF, 0xDA, 0x00, 0x02, 0x12, 0x34, 0xFF, 0xD9];
+        let content = [
+            &[0xFF, 0xD8][..],
+            &app0,
+            &jpeg_segment(0xE1, b"Exif\0\0GPS"),
+            &jpeg_segment(0xFE, b"a comment"),
+            &jpeg_segment(0xED, b"Photoshop 3.0\0"),
+            &app2,
+            &scan,
+        ]
+        .concat();
+
+        let expected = [&[0xFF, 0xD8][..], &app0, &app2, &scan].concat();
+        assert_eq!(strip_jpeg_metadata(&content), Some(expected));
+    }
+
+    #[test]
+    fn strip_jpeg_skips_fill_bytes() {
+        let app0 = jpeg_segment(0xE0, b"JFIF\0");
+        let scan = [0xFF, 0xDA, 0x00, 0x02, 0x56];
+        let content = [
+            &[0xFF, 0xD8][..],
+            &[0xFF, 0xFF],
+            &app0,
+            &[0xFF, 0xFF, 0xFF],
+            &jpeg_segment(0xE1, b"Exif\0\0"),
+            &scan,
+        ]
+        .concat();
+
+        let expected = [&[0xFF, 0xD8][..], &app0, &scan].concat();
+        assert_eq!(strip_jpeg_metadata(&content), Some(expected));
+    }
+
+    #[test]
+    fn strip_jpeg_rejects_truncated_files() {
+        let segment = jpeg_segment(0xE1, b"Exif\0\0GPS");
+        let content = [&[0xFF, 0xD8][..], &segment].concat();
+
+        // No image data
+        assert_eq!(strip_jpeg_metadata(&content), None);
+        // Segment cut short
+        assert_eq!(strip_jpeg_metadata(&content[..content.len() - 2]), None);
+        // Marker cut short
+        assert_eq!(strip_jpeg_metadata(&[0xFF, 0xD8, 0xFF]), None);
+        // Not a JPEG file
+        assert_eq!(strip_jpeg_metadata(b"GIF89a"), None);
+    }
+
+    #[test]
+    fn strip_png_removes_text_and_exif_chunks() {
+        let header = png_chunk(b"IHDR", &[0; 13]);
+        let data = png_chunk(b"IDAT", &[1, 2, 3]);
+        let end = png_chunk(b"IEND", &[]);
+        let content = [
+            &b"\x89PNG\r\n\x1a\n"[..],
+            &header,
+            &png_chunk(b"tEXt", b"Author\0me"),
+            &png_chunk(b"eXIf", b"MM\0*"),
+            &data,
+            &png_chunk(b"tIME", &[0; 7]),
+            &end,
+        ]
+        .concat();
+
+        let expected = [&b"\x89PNG\r\n\x1a\n"[..], &header, &data, &end].concat();
+        assert_eq!(strip_png_metadata(&content), Some(expected));
+    }
+
+    #[test]
+    fn strip_png_rejects_truncated_files() {
+        let content = [&b"\x89PNG\r\n\x1a\n"[..], &png_chunk(b"IHDR", &[0; 13])].concat();
+
+        // Chunk data cut short
+        assert_eq!(strip_png_metadata(&content[..content.len() - 6]), None);
+        // Chunk header cut short
+        assert_eq!(strip_png_metadata(&content[..content.len() - 20]), None);
+        // Not a PNG file
+        assert_eq!(strip_png_metadata(b"\xFF\xD8\xFF"), None);
+    }
+
+    #[test]
+    fn strip_webp_removes_exif_and_xmp_and_clears_their_flags() {
+        const ALPHA_FLAG: u8 = 0x10;
+        let vp8x = |flags: u8| webp_chunk(b"VP8X", &[flags, 0, 0, 0, 1, 0, 0, 1, 0, 0]);
+        // Odd length, padded
+        let image = webp_chunk(b"VP8L", &[0x2F, 0, 0, 0, 0]);
+        let content = webp_file(&[
+            vp8x(ALPHA_FLAG | VP8X_EXIF_FLAG | VP8X_XMP_FLAG),
+            image.clone(),
+            webp_chunk(b"EXIF", b"MM\0*GPS"),
+            webp_chunk(b"XMP ", b"<x:xmpmeta/>"),
+        ]);
+
+        let expected = webp_file(&[vp8x(ALPHA_FLAG), image]);
+        assert_eq!(strip_webp_metadata(&content), Some(expected));
+    }
+
+    #[test]
+    fn strip_webp_rejects_truncated_files() {
+        let content = webp_file(&[webp_chunk(b"VP8L", &[0x2F, 0, 0, 0])]);
+
+        // Chunk header cut short
+        assert_eq!(strip_webp_metadata(&content[..content.len() - 6]), None);
+        // Not a WebP file
+        assert_eq!(strip_webp_metadata(b"RIFF\0\0\0\0WAVE"), None);
+        assert_eq!(strip_webp_metadata(b"RIFF"), None);
+    }
+}
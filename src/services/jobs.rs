@@ -85,7 +85,6 @@ impl JobManager {
             job.end_time = Some(chrono::Utc::now().timestamp_millis());
         }
     }
-
 }
 
 impl Default for JobManager {
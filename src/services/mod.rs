@@ -11,8 +11,28 @@
 //!
 //! Supporting services for the Silex server.
 
+mod assets;
+mod bundle;
+mod diff;
+mod images;
 mod jobs;
+pub mod schema;
 mod static_files;
+mod templates;
+mod thumbnails;
+mod transfer;
+mod uploads;
 
+pub use assets::{collect_all_unused_assets, collect_unused_assets};
+pub use bundle::{export_website, import_website, BUNDLE_ASSETS_FOLDER, MAX_BUNDLE_SIZE};
+pub use diff::diff_websites;
+pub use images::{is_processed_image, process_asset, ImageOptions};
 pub use jobs::JobManager;
+pub use schema::{
+    migrate_all_websites, migrate_website_data, validate_page, validate_website_data,
+};
 pub use static_files::{configure_static_files, StaticConfig};
+pub use templates::{create_from_template, list_templates, TemplateLibrary};
+pub use thumbnails::{ThumbnailCache, MAX_THUMBNAIL_WIDTH, PREVIEW_THUMBNAIL_WIDTH};
+pub use transfer::{start_transfer, transfer_website};
+pub use uploads::{sanitize_file_name, UploadLimits, SNIFF_LENGTH};
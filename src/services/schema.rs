@@ -0,0 +1,402 @@
+/*
+ * Silex website builder, free/libre no-code tool for makers.
+ * Copyright (c) 2023 lexoyo and Silex Labs foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or any later version.
+ */
+
+//! Website data schema: migrations and validation
+//!
+//! Stored website data carries a `schemaVersion`. Data read from storage is
+//! migrated to the current version by a chain of migrations on the raw JSON,
+//! stored data without version being version 0. Data written to storage is
+//! validated first, so malformed pages or styles are rejected with a precise
+//! message instead of being saved.
+
+use crate::connectors::StorageConnector;
+use crate::error::{ConnectorError, ConnectorResult};
+use crate::models::{
+    constants, MigrationFailure, MigrationReport, WebsiteData, CURRENT_SCHEMA_VERSION,
+};
+
+type JsonMap = serde_json::Map<String, serde_json::Value>;
+
+/// Migration from the previous schema version, on the raw JSON object
+type Migration = fn(&mut JsonMap);
+
+/// Migrations, the one at index `i` migrates from version `i` to `i + 1`
+const MIGRATIONS: &[Migration] = &[explicit_pages_folder, normalize_collections];
+
+const _: () = assert!(MIGRATIONS.len() == CURRENT_SCHEMA_VERSION as usize);
+
+// ==================
+// Migrations
+// ==================
+
+/// Version 1: the pages folder is always set
+///
+/// Websites without `pagesFolder` stored their pages in the legacy folder.
+fn explicit_pages_folder(data: &mut JsonMap) {
+    data.entry("pagesFolder")
+        .or_insert_with(|| constants::LEGACY_WEBSITE_PAGES_FOLDER.into());
+}
+
+/// Version 2: collections are never null, assets are objects
+///
+/// Older editors saved missing collections as `null` and assets as plain URLs.
+fn normalize_collections(data: &mut JsonMap) {
+    for key in ["pages", "assets", "styles", "fonts", "symbols"] {
+        let value = data.entry(key).or_insert(serde_json::Value::Null);
+        if value.is_null() {
+            *value = serde_json::json!([]);
+        }
+    }
+    for key in ["settings", "publication"] {
+        let value = data.entry(key).or_insert(serde_json::Value::Null);
+        if value.is_null() {
+            *value = serde_json::json!({});
+        }
+    }
+    if let Some(assets) = data.get_mut("assets").and_then(|a| a.as_array_mut()) {
+        for asset in assets {
+            if let serde_json::Value::String(src) = asset {
+                *asset = serde_json::json!({ "src": src });
+            }
+        }
+    }
+}
+
+/// Migrate stored website data to the current schema version
+pub fn migrate_website_data(mut value: serde_json::Value) -> ConnectorResult<serde_json::Value> {
+    let data = value.as_object_mut().ok_or_else(|| {
+        ConnectorError::InvalidInput("Website data must be a JSON object".to_string())
+    })?;
+
+    let version = match data.get("schemaVersion") {
+        None => 0,
+        Some(version) => version.as_u64().ok_or_else(|| {
+            ConnectorError::InvalidInput("schemaVersion must be a positive integer".to_string())
+        })?,
+    };
+    if version > CURRENT_SCHEMA_VERSION as u64 {
+        return Err(ConnectorError::NotSupported(format!(
+            "Website data schema version {} is newer than the supported version {}",
+            version, CURRENT_SCHEMA_VERSION
+        )));
+    }
+
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(data);
+    }
+    data.insert("schemaVersion".to_string(), CURRENT_SCHEMA_VERSION.into());
+
+    Ok(value)
+}
+
+/// Migrate all the websites of a storage connector to the current schema version
+///
+/// A website which fails does not stop the others.
+pub async fn migrate_all_websites(
+    connector: &dyn StorageConnector,
+    session: &serde_json::Value,
+) -> ConnectorResult<MigrationReport> {
+    let mut report = MigrationReport::default();
+
+    for website in connector.list_websites(session).await? {
+        match connector
+            .migrate_website(session, &website.website_id)
+            .await
+        {
+            Ok(true) => report.migrated.push(website.website_id),
+            Ok(false) => report.up_to_date.push(website.website_id),
+            Err(e) => {
+                tracing::warn!("Could not migrate website {}: {}", website.website_id, e);
+                report.failed.push(MigrationFailure {
+                    website_id: website.website_id,
+                    error: e.to_string(),
+                });
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+// ==================
+// Validation
+// ==================
+
+/// Validate website data before it is written
+pub fn validate_website_data(data: &WebsiteData) -> ConnectorResult<()> {
+    if data.schema_version > CURRENT_SCHEMA_VERSION {
+        return Err(invalid(format!(
+            "schemaVersion {} is newer than the supported version {}",
+            data.schema_version, CURRENT_SCHEMA_VERSION
+        )));
+    }
+
+    if !is_file_name(&data.pages_folder) {
+        return Err(invalid(format!(
+            "pagesFolder '{}' must be a folder name",
+            data.pages_folder
+        )));
+    }
+
+    let mut page_ids = Vec::new();
+    for (index, page) in data.pages.iter().enumerate() {
+        if let Some(id) = check_page(page, &format!("pages[{}]", index))? {
+            if page_ids.contains(&id) {
+                return Err(invalid(format!(
+                    "pages[{}].id '{}' is used by another page",
+                    index, id
+                )));
+            }
+            page_ids.push(id);
+        }
+    }
+
+    for (index, style) in data.styles.iter().enumerate() {
+        let path = format!("styles[{}]", index);
+        let style = object(style, &path)?;
+        if let Some(selectors) = style.get("selectors") {
+            if !selectors.is_array() && !selectors.is_string() {
+                return Err(invalid(format!(
+                    "{}.selectors must be an array or a string",
+                    path
+                )));
+            }
+        }
+        if let Some(declarations) = style.get("style") {
+            object(declarations, &format!("{}.style", path))?;
+        }
+        optional_string(style, "mediaText", &path)?;
+        optional_string(style, "state", &path)?;
+    }
+
+    for (index, asset) in data.assets.iter().enumerate() {
+        // An asset is its URL, or an object with its URL in `src`
+        if asset.is_string() {
+            continue;
+        }
+        let path = format!("assets[{}]", index);
+        let asset = object(asset, &path)?;
+        if optional_string(asset, "src", &path)?.is_none() {
+            return Err(invalid(format!("{}.src is missing", path)));
+        }
+    }
+
+    for (index, font) in data.fonts.iter().enumerate() {
+        object(font, &format!("fonts[{}]", index))?;
+    }
+
+    for (index, symbol) in data.symbols.iter().enumerate() {
+        object(symbol, &format!("symbols[{}]", index))?;
+    }
+
+    for (value, path) in [
+        (&data.settings, "settings"),
+        (&data.publication, "publication"),
+    ] {
+        if !value.is_null() {
+            object(value, path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Validate a page saved on its own
+///
+/// The page must have an ID, which is how it is found in the website data.
+pub fn validate_page(page: &serde_json::Value) -> ConnectorResult<()> {
+    match check_page(page, "page")? {
+        Some(_) => Ok(()),
+        None => Err(invalid("page.id is missing".to_string())),
+    }
+}
+
+/// Check the fields of a page, and return its ID
+fn check_page<'a>(page: &'a serde_json::Value, path: &str) -> ConnectorResult<Option<&'a str>> {
+    let page = object(page, path)?;
+    optional_string(page, "name", path)?;
+    let id = optional_string(page, "id", path)?;
+    if let Some(id) = id {
+        // Page IDs are part of the page file names
+        if !is_file_name(id) {
+            return Err(invalid(format!("{}.id '{}' is not a valid ID", path, id)));
+        }
+    }
+    Ok(id)
+}
+
+/// Build an `InvalidInput` error
+fn invalid(message: String) -> ConnectorError {
+    ConnectorError::InvalidInput(message)
+}
+
+/// Check that a value is a JSON object
+fn object<'a>(value: &'a serde_json::Value, path: &str) -> ConnectorResult<&'a JsonMap> {
+    value
+        .as_object()
+        .ok_or_else(|| invalid(format!("{} must be an object", path)))
+}
+
+/// Get an optional string field, failing if it is not a string
+fn optional_string<'a>(
+    object: &'a JsonMap,
+    field: &str,
+    path: &str,
+) -> ConnectorResult<Option<&'a str>> {
+    match object.get(field) {
+        None | Some(serde_json::Value::Null) => Ok(None),
+        Some(serde_json::Value::String(value)) => Ok(Some(value)),
+        Some(_) => Err(invalid(format!("{}.{} must be a string", path, field))),
+    }
+}
+
+/// Whether a string can be used as a single file or folder name
+fn is_file_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && !name.contains(['/', '\\'])
+        && !name.chars().any(|c| c.is_control())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Website data with some fields
+    fn data(value: serde_json::Value) -> WebsiteData {
+        serde_json::from_value(value).unwrap()
+    }
+
+    /// The message of a validation error
+    fn error(data: &WebsiteData) -> String {
+        match validate_website_data(data) {
+            Err(ConnectorError::InvalidInput(message)) => message,
+            other => panic!("expected invalid input, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn migrate_unversioned_data() {
+        let migrated = migrate_website_data(json!({
+            "pages": null,
+            "assets": ["/assets/a.png", { "src": "/assets/b.png" }],
+            "settings": null,
+        }))
+        .unwrap();
+
+        assert_eq!(
+            migrated,
+            json!({
+                "schemaVersion": CURRENT_SCHEMA_VERSION,
+                "pagesFolder": constants::LEGACY_WEBSITE_PAGES_FOLDER,
+                "pages": [],
+                "assets": [{ "src": "/assets/a.png" }, { "src": "/assets/b.png" }],
+                "styles": [],
+                "fonts": [],
+                "symbols": [],
+                "settings": {},
+                "publication": {},
+            })
+        );
+    }
+
+    #[test]
+    fn migrate_from_an_intermediate_version() {
+        // Only the migrations after version 1 run
+        let migrated = migrate_website_data(json!({
+            "schemaVersion": 1,
+            "pagesFolder": "custom",
+            "styles": null,
+        }))
+        .unwrap();
+
+        assert_eq!(migrated["pagesFolder"], "custom");
+        assert_eq!(migrated["styles"], json!([]));
+        assert_eq!(migrated["schemaVersion"], CURRENT_SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn migrate_current_data_unchanged() {
+        let current = json!({
+            "schemaVersion": CURRENT_SCHEMA_VERSION,
+            "pagesFolder": "pages",
+            "assets": ["/kept/as/string.png"],
+        });
+
+        assert_eq!(migrate_website_data(current.clone()).unwrap(), current);
+    }
+
+    #[test]
+    fn migrate_refuses_unknown_versions() {
+        assert!(matches!(
+            migrate_website_data(json!({ "schemaVersion": CURRENT_SCHEMA_VERSION + 1 })),
+            Err(ConnectorError::NotSupported(_))
+        ));
+        assert!(matches!(
+            migrate_website_data(json!({ "schemaVersion": "2" })),
+            Err(ConnectorError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            migrate_website_data(json!([])),
+            Err(ConnectorError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn validate_accepts_editor_data() {
+        let website = data(json!({
+            "pages": [{ "id": "home", "name": "Home", "frames": [] }, {}],
+            "styles": [
+                { "selectors": ["#a"], "style": { "color": "red" }, "mediaText": "(max-width: 480px)" },
+                { "selectors": "#b", "state": "hover" },
+            ],
+            "assets": ["/assets/a.png", { "src": "/assets/b.png", "type": "image" }],
+            "settings": { "title": "Site" },
+            "publication": null,
+        }));
+
+        assert!(validate_website_data(&website).is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_malformed_data() {
+        assert!(error(&data(json!({ "pagesFolder": "../pages" }))).contains("pagesFolder"));
+        assert!(error(&data(json!({ "pages": ["home"] }))).contains("pages[0] must be an object"));
+        assert!(error(&data(json!({ "pages": [{ "id": 1 }] }))).contains("pages[0].id"));
+        assert!(error(&data(json!({ "pages": [{ "id": "a/b" }] }))).contains("not a valid ID"));
+        assert!(
+            error(&data(json!({ "pages": [{ "id": "a" }, { "id": "a" }] })))
+                .contains("pages[1].id 'a' is used by another page")
+        );
+        assert!(
+            error(&data(json!({ "styles": [{ "selectors": 1 }] }))).contains("styles[0].selectors")
+        );
+        assert!(
+            error(&data(json!({ "styles": [{ "style": "color: red" }] })))
+                .contains("styles[0].style")
+        );
+        assert!(error(&data(json!({ "assets": [{ "type": "image" }] })))
+            .contains("assets[0].src is missing"));
+        assert!(error(&data(json!({ "fonts": ["Arial"] }))).contains("fonts[0]"));
+        assert!(error(&data(json!({ "settings": [] }))).contains("settings"));
+        assert!(error(&data(
+            json!({ "schemaVersion": CURRENT_SCHEMA_VERSION + 1 })
+        ))
+        .contains("newer"));
+    }
+
+    #[test]
+    fn validate_page_requires_an_id() {
+        assert!(validate_page(&json!({ "id": "home" })).is_ok());
+        assert!(validate_page(&json!({ "name": "Home" })).is_err());
+        assert!(validate_page(&json!({ "id": ".." })).is_err());
+    }
+}
@@ -86,7 +86,11 @@ pub fn configure_static_files<S: Clone + Send + Sync + 'static>(
             tracing::info!("Static route: {} -> {}", route, path.display());
             app = app.nest_service(route, ServeDir::new(path));
         } else if route != "/" {
-            tracing::warn!("Static route: {} -> {} (path does not exist)", route, path.display());
+            tracing::warn!(
+                "Static route: {} -> {} (path does not exist)",
+                route,
+                path.display()
+            );
         }
     }
 
@@ -121,17 +125,13 @@ pub fn configure_static_files<S: Clone + Send + Sync + 'static>(
     }
 
     // When dashboard is configured, serve `/` based on `?id=` query param
-    if dashboard_index.is_some() && editor_index.is_some() {
-        let dash_bytes: Vec<u8> =
-            std::fs::read(dashboard_index.as_ref().unwrap()).unwrap_or_default();
-        let edit_bytes: Vec<u8> =
-            std::fs::read(editor_index.as_ref().unwrap()).unwrap_or_default();
+    if let (Some(dashboard_index), Some(editor_index)) = (&dashboard_index, &editor_index) {
+        let dash_bytes: Vec<u8> = std::fs::read(dashboard_index).unwrap_or_default();
+        let edit_bytes: Vec<u8> = std::fs::read(editor_index).unwrap_or_default();
         let dash = Arc::new(dash_bytes);
         let edit = Arc::new(edit_bytes);
 
-        tracing::info!(
-            "  / -> dashboard (default) or editor (?id=)",
-        );
+        tracing::info!("  / -> dashboard (default) or editor (?id=)",);
 
         app = app.route(
             "/",
@@ -144,10 +144,7 @@ pub fn configure_static_files<S: Clone + Send + Sync + 'static>(
                     } else {
                         dash.as_ref().clone()
                     };
-                    (
-                        [(header::CONTENT_TYPE, "text/html; charset=utf-8")],
-                        body,
-                    )
+                    ([(header::CONTENT_TYPE, "text/html; charset=utf-8")], body)
                 }
             }),
         );
@@ -208,7 +205,11 @@ async fn try_embedded_asset<E: rust_embed::Embed>(path: &str) -> Option<axum::re
         } else {
             mime.to_string()
         };
-        ([(header::CONTENT_TYPE, content_type)], content.data.to_vec()).into_response()
+        (
+            [(header::CONTENT_TYPE, content_type)],
+            content.data.to_vec(),
+        )
+            .into_response()
     })
 }
 
@@ -0,0 +1,633 @@
+/*
+ * Silex website builder, free/libre no-code tool for makers.
+ * Copyright (c) 2023 lexoyo and Silex Labs foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or any later version.
+ */
+
+//! Website templates
+//!
+//! New websites can start from a template instead of an empty page.
+//! Templates come from two places:
+//! - the template library, a directory of the server with one website per
+//!   folder, in the layout of the filesystem storage (a website folder of the
+//!   data path can be copied there as is)
+//! - the websites of a storage connector whose metadata has `isTemplate` set
+//!
+//! Creating from a template clones its pages, styles, symbols, fonts, settings
+//! and asset files, with the asset URLs pointed to the new website. The
+//! publication settings are not cloned, they belong to the template's own hosting.
+
+use std::future::Future;
+use std::path::PathBuf;
+
+use tokio::fs;
+
+use crate::connectors::website_files;
+use crate::connectors::StorageConnector;
+use crate::error::{ConnectorError, ConnectorResult};
+use crate::models::{
+    constants, ConnectorFile, Template, TemplateSource, WebsiteData, WebsiteId,
+    WebsiteMetaFileContent,
+};
+use crate::services::assets::website_asset_paths;
+use crate::services::transfer::rewrite_asset_urls;
+
+/// The template directory of the server
+///
+/// ```text
+/// templates_path/
+///   {template_id}/
+///     website.json
+///     meta.json
+///     pages/
+///     assets/
+/// ```
+///
+/// The directory is only read, it can be mounted read-only.
+#[derive(Debug, Clone)]
+pub struct TemplateLibrary {
+    /// Root path of the templates
+    path: PathBuf,
+
+    /// Folder name for assets within each template
+    assets_folder: String,
+}
+
+impl TemplateLibrary {
+    /// Create a template library
+    ///
+    /// # Arguments
+    /// * `path` - Directory holding one folder per template
+    /// * `assets_folder` - Folder name for assets within each template
+    pub fn new(path: PathBuf, assets_folder: String) -> Self {
+        TemplateLibrary {
+            path,
+            assets_folder,
+        }
+    }
+
+    /// Get the path to a template's directory
+    fn template_path(&self, template_id: &str) -> ConnectorResult<PathBuf> {
+        check_template_id(template_id)?;
+        Ok(self.path.join(template_id))
+    }
+
+    /// List the templates of the library, sorted by name
+    ///
+    /// A missing directory is an empty library.
+    pub async fn list_templates(&self) -> ConnectorResult<Vec<Template>> {
+        let mut templates = Vec::new();
+
+        let mut entries = match fs::read_dir(&self.path).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(templates),
+            Err(e) => return Err(e.into()),
+        };
+
+        while let Some(entry) = entries.next_entry().await? {
+            let template_id = entry.file_name().to_string_lossy().to_string();
+            if template_id.starts_with('.') || !entry.file_type().await?.is_dir() {
+                continue;
+            }
+
+            // Folders without website data are not templates
+            if fs::metadata(entry.path().join(constants::WEBSITE_DATA_FILE))
+                .await
+                .is_err()
+            {
+                continue;
+            }
+
+            let meta = self.read_meta(&template_id).await;
+            templates.push(Template {
+                name: meta
+                    .as_ref()
+                    .map(|m| m.name.clone())
+                    .unwrap_or_else(|_| template_id.clone()),
+                image_url: meta.ok().and_then(|m| m.image_url),
+                template_id,
+                source: TemplateSource::Library,
+            });
+        }
+
+        templates.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(templates)
+    }
+
+    /// Read a template's metadata
+    pub async fn read_meta(&self, template_id: &str) -> ConnectorResult<WebsiteMetaFileContent> {
+        let path = self
+            .template_path(template_id)?
+            .join(constants::WEBSITE_META_DATA_FILE);
+        let content = read_file(path, template_id).await?;
+        Ok(serde_json::from_slice(&content)?)
+    }
+
+    /// Read a template's website data, with its pages
+    pub async fn read_website(&self, template_id: &str) -> ConnectorResult<WebsiteData> {
+        let template_path = self.template_path(template_id)?;
+        let content = read_file(
+            template_path.join(constants::WEBSITE_DATA_FILE),
+            template_id,
+        )
+        .await?;
+        let content = String::from_utf8(content).map_err(|_| {
+            ConnectorError::InvalidInput(format!("Template '{}' is not valid UTF-8", template_id))
+        })?;
+
+        website_files::merge_website_data(&content, |path| {
+            let path = template_path.join(path);
+            async move {
+                fs::read_to_string(&path).await.map_err(|e| {
+                    if e.kind() == std::io::ErrorKind::NotFound {
+                        ConnectorError::NotFound(format!(
+                            "Page file '{}' not found",
+                            path.display()
+                        ))
+                    } else {
+                        ConnectorError::Io(e)
+                    }
+                })
+            }
+        })
+        .await
+    }
+
+    /// List the asset files of a template, with their path in the assets folder
+    pub async fn list_assets(&self, template_id: &str) -> ConnectorResult<Vec<String>> {
+        let assets_path = self.template_path(template_id)?.join(&self.assets_folder);
+        let mut paths = Vec::new();
+        let mut folders = vec![String::new()];
+
+        while let Some(folder) = folders.pop() {
+            let mut entries = match fs::read_dir(assets_path.join(&folder)).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            while let Some(entry) = entries.next_entry().await? {
+                let name = entry.file_name().to_string_lossy().to_string();
+                let path = if folder.is_empty() {
+                    name
+                } else {
+                    format!("{}/{}", folder, name)
+                };
+                if entry.file_type().await?.is_dir() {
+                    folders.push(path);
+                } else {
+                    paths.push(path);
+                }
+            }
+        }
+
+        paths.sort();
+        Ok(paths)
+    }
+
+    /// Read an asset of a template
+    pub async fn read_asset(&self, template_id: &str, path: &str) -> ConnectorResult<Vec<u8>> {
+        let path = path.trim_start_matches('/');
+        if path.split('/').any(|part| part.is_empty() || part == "..") {
+            return Err(ConnectorError::InvalidInput(format!(
+                "Invalid asset path '{}'",
+                path
+            )));
+        }
+        let asset_path = self
+            .template_path(template_id)?
+            .join(&self.assets_folder)
+            .join(path);
+        read_file(asset_path, template_id).await
+    }
+}
+
+/// Reject template IDs which would leave the library or the connector's websites
+fn check_template_id(template_id: &str) -> ConnectorResult<()> {
+    let valid = !template_id.is_empty()
+        && !template_id.starts_with('.')
+        && !template_id.contains(['/', '\\']);
+    if !valid {
+        return Err(ConnectorError::InvalidInput(format!(
+            "Invalid template ID '{}'",
+            template_id
+        )));
+    }
+    Ok(())
+}
+
+/// Read a file of a template, a missing file being `NotFound`
+async fn read_file(path: PathBuf, template_id: &str) -> ConnectorResult<Vec<u8>> {
+    fs::read(&path).await.map_err(|e| {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            ConnectorError::NotFound(format!(
+                "File '{}' of template '{}' not found",
+                path.display(),
+                template_id
+            ))
+        } else {
+            ConnectorError::Io(e)
+        }
+    })
+}
+
+/// List the templates available to a user: the connector's websites marked
+/// as templates, then the library's templates
+pub async fn list_templates(
+    connector: &dyn StorageConnector,
+    session: &serde_json::Value,
+    library: Option<&TemplateLibrary>,
+) -> ConnectorResult<Vec<Template>> {
+    let mut templates: Vec<Template> = connector
+        .list_websites(session)
+        .await?
+        .into_iter()
+        .filter(|website| website.is_template)
+        .map(|website| Template {
+            template_id: website.website_id,
+            name: website.name,
+            image_url: website.image_url,
+            source: TemplateSource::Website,
+        })
+        .collect();
+
+    if let Some(library) = library {
+        templates.extend(library.list_templates().await?);
+    }
+
+    Ok(templates)
+}
+
+/// Create a website from a template
+///
+/// The template is looked up in the connector's websites marked as templates,
+/// then in the library. The new website gets `meta`, with the template's
+/// preview image when it has none. It is purged if the copy fails, without
+/// going through the trash.
+pub async fn create_from_template(
+    connector: &dyn StorageConnector,
+    session: &serde_json::Value,
+    library: Option<&TemplateLibrary>,
+    meta: &WebsiteMetaFileContent,
+    template_id: &str,
+) -> ConnectorResult<WebsiteId> {
+    check_template_id(template_id)?;
+    let not_found = || ConnectorError::NotFound(format!("Template '{}' not found", template_id));
+
+    // Read the template and its assets before creating anything
+    let template_website_id = template_id.to_string();
+    let (template_meta, data, assets) = match connector
+        .get_website_meta(session, &template_website_id)
+        .await
+    {
+        Ok(website) if website.is_template => {
+            let data = connector
+                .read_website(session, &template_website_id)
+                .await?;
+            let paths =
+                website_asset_paths(connector, session, &template_website_id, Some(&data)).await?;
+            let template_website_id = &template_website_id;
+            let assets = read_assets(paths, |path| async move {
+                connector
+                    .read_asset(session, template_website_id, &path)
+                    .await
+            })
+            .await?;
+            (website.to_file_content(), data, assets)
+        }
+        Ok(_) | Err(ConnectorError::NotFound(_)) => {
+            let library = library.ok_or_else(not_found)?;
+            let data = match library.read_website(template_id).await {
+                Err(ConnectorError::NotFound(_)) => return Err(not_found()),
+                result => result?,
+            };
+            let template_meta = library.read_meta(template_id).await.ok();
+            let paths = library.list_assets(template_id).await?;
+            let assets = read_assets(paths, |path| async move {
+                library.read_asset(template_id, &path).await
+            })
+            .await?;
+            let template_meta = template_meta.unwrap_or_else(|| WebsiteMetaFileContent {
+                name: template_id.to_string(),
+                image_url: None,
+                connector_user_settings: Default::default(),
+                is_template: true,
+                ..Default::default()
+            });
+            (template_meta, data, assets)
+        }
+        Err(e) => return Err(e),
+    };
+
+    let mut data = WebsiteData {
+        publication: serde_json::json!({}),
+        ..data
+    };
+    let mut meta = WebsiteMetaFileContent {
+        image_url: meta.image_url.clone().or(template_meta.image_url),
+        ..meta.clone()
+    };
+
+    let website_id = connector.create_website(session, &meta).await?;
+    let written = async {
+        // Asset URLs of the template point to the template
+        rewrite_asset_urls(
+            &mut data,
+            &mut meta,
+            template_id,
+            &website_id,
+            connector.connector_id(),
+        )?;
+        if meta.image_url.is_some() {
+            connector
+                .set_website_meta(session, &website_id, &meta)
+                .await?;
+        }
+        connector
+            .update_website(session, &website_id, &data)
+            .await?;
+        if !assets.is_empty() {
+            connector.write_assets(session, &website_id, assets).await?;
+        }
+        Ok::<_, ConnectorError>(())
+    };
+    if let Err(e) = written.await {
+        if let Err(cleanup) = connector.purge_website(session, &website_id).await {
+            tracing::warn!(
+                "Could not remove partially created website {}: {}",
+                website_id,
+                cleanup
+            );
+        }
+        return Err(e);
+    }
+
+    Ok(website_id)
+}
+
+/// Read the assets of a template, skipping the missing ones
+async fn read_assets<F, Fut>(paths: Vec<String>, mut read: F) -> ConnectorResult<Vec<ConnectorFile>>
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = ConnectorResult<Vec<u8>>>,
+{
+    let mut assets = Vec::new();
+    for path in paths {
+        match read(path.clone()).await {
+            Ok(content) => assets.push(ConnectorFile {
+                path: format!("/{}", path),
+                content,
+            }),
+            Err(ConnectorError::NotFound(_)) => {
+                tracing::warn!("Template asset {} not found, skipped", path);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(assets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectors::FsStorage;
+    use serde_json::json;
+
+    /// A storage and a template library in a temporary folder
+    fn setup() -> (tempfile::TempDir, FsStorage, TemplateLibrary) {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = FsStorage::new(dir.path().join("data"), "assets".to_string());
+        let library = TemplateLibrary::new(dir.path().join("templates"), "assets".to_string());
+        (dir, storage, library)
+    }
+
+    /// Write a file of the template library
+    fn write(library: &TemplateLibrary, path: &str, content: &str) {
+        let path = library.path.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    /// A page showing an asset of a website
+    fn page(website_id: &str) -> serde_json::Value {
+        json!({
+            "id": "home",
+            "name": "Home",
+            "html": format!(
+                "<img src=\"/api/website/assets/img/a.png?websiteId={}&connectorId=fs-storage\">",
+                website_id
+            ),
+        })
+    }
+
+    /// A library template with a page and an asset
+    fn library_template(library: &TemplateLibrary) {
+        let website = json!({ "pages": [page("blog")] });
+        write(library, "blog/website.json", &website.to_string());
+        write(
+            library,
+            "blog/meta.json",
+            r#"{ "name": "Blog", "imageUrl": "/blog.png" }"#,
+        );
+        write(library, "blog/assets/img/a.png", "png");
+    }
+
+    #[tokio::test]
+    async fn library_lists_the_folders_with_website_data() {
+        let (_dir, _storage, library) = setup();
+        assert!(library.list_templates().await.unwrap().is_empty());
+
+        library_template(&library);
+        write(&library, "shop/website.json", "{}");
+        write(&library, "notes/readme.txt", "");
+        write(&library, ".hidden/website.json", "{}");
+
+        let templates = library.list_templates().await.unwrap();
+        let names: Vec<_> = templates.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["Blog", "shop"]);
+        assert_eq!(templates[0].image_url.as_deref(), Some("/blog.png"));
+        assert_eq!(templates[0].source, TemplateSource::Library);
+    }
+
+    #[tokio::test]
+    async fn library_reads_pages_and_assets() {
+        let (_dir, _storage, library) = setup();
+        library_template(&library);
+
+        let data = library.read_website("blog").await.unwrap();
+        assert_eq!(data.pages, [page("blog")]);
+        assert_eq!(library.list_assets("blog").await.unwrap(), ["img/a.png"]);
+        assert_eq!(
+            library.read_asset("blog", "/img/a.png").await.unwrap(),
+            b"png"
+        );
+    }
+
+    #[tokio::test]
+    async fn library_rejects_paths_out_of_the_template() {
+        let (_dir, _storage, library) = setup();
+        library_template(&library);
+
+        for template_id in ["", "..", "../blog", ".hidden", "a/b"] {
+            assert!(matches!(
+                library.read_website(template_id).await,
+                Err(ConnectorError::InvalidInput(_))
+            ));
+        }
+        assert!(matches!(
+            library.read_asset("blog", "../website.json").await,
+            Err(ConnectorError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            library.read_asset("blog", "img/missing.png").await,
+            Err(ConnectorError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn create_from_a_library_template() {
+        let (_dir, storage, library) = setup();
+        library_template(&library);
+        let session = json!({});
+        let meta = WebsiteMetaFileContent {
+            name: "My blog".to_string(),
+            ..Default::default()
+        };
+
+        let website_id = create_from_template(&storage, &session, Some(&library), &meta, "blog")
+            .await
+            .unwrap();
+
+        let data = storage.read_website(&session, &website_id).await.unwrap();
+        assert_eq!(data.pages, [page(&website_id)]);
+        assert_eq!(data.publication, json!({}));
+        let created = storage
+            .get_website_meta(&session, &website_id)
+            .await
+            .unwrap();
+        assert_eq!(created.name, "My blog");
+        assert_eq!(created.image_url.as_deref(), Some("/blog.png"));
+        assert!(!created.is_template);
+        assert_eq!(
+            storage
+                .read_asset(&session, &website_id, "/img/a.png")
+                .await
+                .unwrap(),
+            b"png"
+        );
+    }
+
+    #[tokio::test]
+    async fn create_from_a_website_marked_as_template() {
+        let (_dir, storage, library) = setup();
+        let session = json!({});
+        let template_meta = WebsiteMetaFileContent {
+            name: "Landing".to_string(),
+            is_template: true,
+            ..Default::default()
+        };
+        let template_id = storage
+            .create_website(&session, &template_meta)
+            .await
+            .unwrap();
+        let data = WebsiteData {
+            pages: vec![page(&template_id)],
+            publication: json!({ "url": "https://example.com" }),
+            ..Default::default()
+        };
+        storage
+            .update_website(&session, &template_id, &data)
+            .await
+            .unwrap();
+        storage
+            .write_assets(
+                &session,
+                &template_id,
+                vec![ConnectorFile {
+                    path: "/img/a.png".to_string(),
+                    content: b"png".to_vec(),
+                }],
+            )
+            .await
+            .unwrap();
+
+        let templates = list_templates(&storage, &session, Some(&library))
+            .await
+            .unwrap();
+        assert_eq!(templates.len(), 1);
+        assert_eq!(templates[0].source, TemplateSource::Website);
+
+        let website_id = create_from_template(
+            &storage,
+            &session,
+            None,
+            &WebsiteMetaFileContent::default(),
+            &template_id,
+        )
+        .await
+        .unwrap();
+
+        let copy = storage.read_website(&session, &website_id).await.unwrap();
+        assert_eq!(copy.pages, [page(&website_id)]);
+        assert_eq!(copy.publication, json!({}));
+        assert_eq!(
+            storage
+                .read_asset(&session, &website_id, "/img/a.png")
+                .await
+                .unwrap(),
+            b"png"
+        );
+        // The template is unchanged
+        let template = storage.read_website(&session, &template_id).await.unwrap();
+        assert_eq!(template.pages, [page(&template_id)]);
+    }
+
+    #[tokio::test]
+    async fn create_from_an_unknown_template() {
+        let (_dir, storage, library) = setup();
+        let session = json!({});
+        let meta = WebsiteMetaFileContent::default();
+        // A website which is not a template
+        let website_id = storage.create_website(&session, &meta).await.unwrap();
+
+        for template_id in ["missing", website_id.as_str()] {
+            assert!(matches!(
+                create_from_template(&storage, &session, Some(&library), &meta, template_id).await,
+                Err(ConnectorError::NotFound(_))
+            ));
+        }
+        assert!(matches!(
+            create_from_template(&storage, &session, None, &meta, "../data").await,
+            Err(ConnectorError::InvalidInput(_))
+        ));
+        assert_eq!(storage.list_websites(&session).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn failed_creation_is_purged() {
+        let (dir, storage, library) = setup();
+        // Refused by the storage when written
+        write(
+            &library,
+            "broken/website.json",
+            r#"{ "pages": [{ "id": "../a" }] }"#,
+        );
+        let session = json!({});
+
+        let result = create_from_template(
+            &storage,
+            &session,
+            Some(&library),
+            &WebsiteMetaFileContent::default(),
+            "broken",
+        )
+        .await;
+
+        assert!(matches!(result, Err(ConnectorError::InvalidInput(_))));
+        assert!(storage.list_websites(&session).await.unwrap().is_empty());
+        assert!(storage.list_trash(&session).await.unwrap().is_empty());
+        assert!(!dir.path().join("data/.trash").exists());
+    }
+}
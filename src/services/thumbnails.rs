@@ -0,0 +1,276 @@
+/*
+ * Silex website builder, free/libre no-code tool for makers.
+ * Copyright (c) 2023 lexoyo and Silex Labs foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or any later version.
+ */
+
+//! Thumbnails of image assets and preview images of websites
+//!
+//! Thumbnails are made on demand, when an asset is read with a width
+//! (`/api/website/assets/photo.jpg?websiteId=X&w=200`), and kept in a cache
+//! directory named after the content of the asset. A modified asset gets a
+//! new thumbnail, and the cache can be emptied at any time.
+//!
+//! Websites without preview image get the thumbnail of the largest image of
+//! their first page in the website list. It is not saved in their metadata,
+//! so that it follows the changes of the page. Only the beginning of the
+//! images is read to get their dimensions, and a website list request picks
+//! a bounded number of previews: the other websites get theirs in the next
+//! requests.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use tokio::fs;
+use tokio::io::AsyncReadExt;
+
+use crate::connectors::website_files;
+use crate::connectors::StorageConnector;
+use crate::error::{ConnectorError, ConnectorResult};
+use crate::models::{WebsiteId, WebsiteMeta};
+use crate::services::images::{image_dimensions, image_format, resize_image};
+
+/// Widest thumbnail which can be asked for
+pub const MAX_THUMBNAIL_WIDTH: u32 = 2048;
+
+/// Largest image thumbnails are made of, in bytes, larger files are served as they are
+pub const MAX_THUMBNAIL_SOURCE_SIZE: u64 = 32 * 1024 * 1024;
+
+/// Width of the thumbnails used as website preview images
+pub const PREVIEW_THUMBNAIL_WIDTH: u32 = 400;
+
+/// Most preview images picked by a website list request, the others are picked by the next ones
+const MAX_PREVIEW_PICKS: usize = 5;
+
+/// Most images of a page whose dimensions are read to pick its preview
+const MAX_PREVIEW_CANDIDATES: usize = 10;
+
+/// Bytes read from the beginning of an image to get its dimensions, enough for its headers
+const IMAGE_HEADER_LENGTH: u64 = 128 * 1024;
+
+/// Preview image picked for a website, and the modification time it was picked at
+type PickedPreview = (Option<DateTime<Utc>>, Option<String>);
+
+/// The thumbnail cache
+///
+/// ```text
+/// path/
+///   {hash[..2]}/
+///     {sha256 of the asset}-{width}.{ext}
+/// ```
+#[derive(Debug)]
+pub struct ThumbnailCache {
+    /// Root path of the cached thumbnails
+    path: PathBuf,
+
+    /// Preview images picked for the websites, by connector and website ID
+    previews: Mutex<HashMap<(String, WebsiteId), PickedPreview>>,
+}
+
+impl ThumbnailCache {
+    /// Create a thumbnail cache
+    ///
+    /// # Arguments
+    /// * `path` - Directory of the cached thumbnails, created when needed
+    pub fn new(path: PathBuf) -> Self {
+        ThumbnailCache {
+            path,
+            previews: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether a thumbnail can be made of an asset, from its path and size
+    ///
+    /// Only images no larger than `MAX_THUMBNAIL_SOURCE_SIZE` are read whole
+    /// to make a thumbnail, other assets are streamed as they are.
+    pub fn is_thumbnail_source(asset_path: &str, size: u64) -> bool {
+        image_format(asset_path).is_some() && size <= MAX_THUMBNAIL_SOURCE_SIZE
+    }
+
+    /// Get the thumbnail of an asset, from the cache or made now
+    ///
+    /// Returns None when the asset is not an image, or is not wider than `width`.
+    pub async fn thumbnail(
+        &self,
+        asset_path: &str,
+        content: &[u8],
+        width: u32,
+    ) -> ConnectorResult<Option<Vec<u8>>> {
+        if width == 0 || width > MAX_THUMBNAIL_WIDTH {
+            return Err(ConnectorError::InvalidInput(format!(
+                "Thumbnail width must be between 1 and {}",
+                MAX_THUMBNAIL_WIDTH
+            )));
+        }
+        let Some(format) = image_format(asset_path) else {
+            return Ok(None);
+        };
+
+        let hash: String = Sha256::digest(content)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        let extension = format.extensions_str().first().copied().unwrap_or("img");
+        let cache_path = self
+            .path
+            .join(&hash[..2])
+            .join(format!("{}-{}.{}", hash, width, extension));
+
+        if let Ok(thumbnail) = fs::read(&cache_path).await {
+            return Ok(Some(thumbnail));
+        }
+
+        let content = content.to_vec();
+        let resized = tokio::task::spawn_blocking(move || resize_image(&content, format, width))
+            .await
+            .map_err(std::io::Error::other)?;
+        let thumbnail = match resized {
+            Ok(Some(thumbnail)) => thumbnail,
+            Ok(None) => return Ok(None),
+            Err(e) => {
+                tracing::warn!("Could not make a thumbnail of {}: {}", asset_path, e);
+                return Ok(None);
+            }
+        };
+
+        // The thumbnail is served even if it can not be cached
+        if let Err(e) = write_cache_file(&cache_path, &thumbnail).await {
+            tracing::warn!("Could not cache the thumbnail of {}: {}", asset_path, e);
+        }
+        Ok(Some(thumbnail))
+    }
+
+    /// Set the preview image of the websites which have none
+    ///
+    /// The preview is the thumbnail of the largest image of the website's first
+    /// page. It is picked again when the website's modification time changes.
+    /// At most `MAX_PREVIEW_PICKS` previews are picked, the websites left get
+    /// theirs in the next calls. Websites whose data can not be read keep no preview.
+    pub async fn fill_preview_images(
+        &self,
+        connector: &dyn StorageConnector,
+        session: &serde_json::Value,
+        websites: &mut [WebsiteMeta],
+    ) {
+        let connector_id = connector.connector_id().to_string();
+        let mut picks = 0;
+
+        for website in websites
+            .iter_mut()
+            .filter(|website| website.image_url.is_none())
+        {
+            let key = (connector_id.clone(), website.website_id.clone());
+            let picked = self.previews.lock().unwrap().get(&key).cloned();
+            let image_url = match picked {
+                Some((updated_at, image_url)) if updated_at == website.updated_at => image_url,
+                _ if picks >= MAX_PREVIEW_PICKS => continue,
+                _ => {
+                    picks += 1;
+                    let website_id = &website.website_id;
+                    let image_url = match pick_preview_image(connector, session, website_id).await {
+                        Ok(path) => path.map(|path| {
+                            format!(
+                                "/api/website/assets/{}?websiteId={}&connectorId={}&w={}",
+                                website_files::percent_encode_path(&path),
+                                website_id,
+                                connector_id,
+                                PREVIEW_THUMBNAIL_WIDTH
+                            )
+                        }),
+                        Err(e) => {
+                            tracing::warn!(
+                                "Could not pick a preview image for website {}: {}",
+                                website_id,
+                                e
+                            );
+                            continue;
+                        }
+                    };
+                    self.previews
+                        .lock()
+                        .unwrap()
+                        .insert(key, (website.updated_at, image_url.clone()));
+                    image_url
+                }
+            };
+            website.image_url = image_url;
+        }
+    }
+}
+
+/// Find the largest image of a website's first page
+///
+/// Returns its path in the assets folder, or None when the page has no image.
+/// Only the first `MAX_PREVIEW_CANDIDATES` images are compared.
+async fn pick_preview_image(
+    connector: &dyn StorageConnector,
+    session: &serde_json::Value,
+    website_id: &WebsiteId,
+) -> ConnectorResult<Option<String>> {
+    let data = connector.read_website(session, website_id).await?;
+    let Some(page) = data.pages.first() else {
+        return Ok(None);
+    };
+
+    let mut largest = None;
+    let mut largest_area = 0;
+    let images = website_files::value_asset_paths(page)
+        .into_iter()
+        .filter_map(|path| image_format(&path).map(|format| (path, format)))
+        .take(MAX_PREVIEW_CANDIDATES);
+    for (path, format) in images {
+        // Missing and broken images are no candidates
+        let Ok(header) = read_header(connector, session, website_id, &path).await else {
+            continue;
+        };
+        let Ok((width, height)) = image_dimensions(&header, format) else {
+            continue;
+        };
+        let area = u64::from(width) * u64::from(height);
+        if area > largest_area {
+            largest_area = area;
+            largest = Some(path);
+        }
+    }
+
+    Ok(largest)
+}
+
+/// Read the beginning of an asset, which holds the headers of an image
+async fn read_header(
+    connector: &dyn StorageConnector,
+    session: &serde_json::Value,
+    website_id: &WebsiteId,
+    path: &str,
+) -> ConnectorResult<Vec<u8>> {
+    let stream = connector
+        .read_asset_stream(session, website_id, path)
+        .await?;
+    let mut header = Vec::new();
+    stream
+        .reader
+        .take(IMAGE_HEADER_LENGTH)
+        .read_to_end(&mut header)
+        .await?;
+    Ok(header)
+}
+
+/// Write a file of the cache, through a temporary file so that it is never read half written
+async fn write_cache_file(path: &std::path::Path, content: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    let temp_path = path.with_extension(format!("tmp-{}", uuid::Uuid::new_v4()));
+    fs::write(&temp_path, content).await?;
+    if let Err(e) = fs::rename(&temp_path, path).await {
+        let _ = fs::remove_file(&temp_path).await;
+        return Err(e);
+    }
+    Ok(())
+}
@@ -0,0 +1,298 @@
+/*
+ * Silex website builder, free/libre no-code tool for makers.
+ * Copyright (c) 2023 lexoyo and Silex Labs foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or any later version.
+ */
+
+//! Copy or move websites between storage connectors
+//!
+//! The website data, metadata and assets are copied with the `StorageConnector`
+//! trait methods only, so any pair of connectors works. The copy is verified
+//! before it is reported as successful, and before the source is deleted
+//! when moving: the data must have the same revision and every asset the
+//! same bytes. A copy which fails is removed from the target.
+//!
+//! Every asset file is copied, used or not. A website can only be moved from
+//! a connector which lists its assets, copies from the others get the assets
+//! the data references. Assets are streamed one at a time, so that a transfer
+//! does not hold the website in memory. Asset URLs in the data and the
+//! metadata are pointed to the copy.
+
+use std::sync::Arc;
+
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::connectors::website_files;
+use crate::connectors::StorageConnector;
+use crate::error::{ConnectorError, ConnectorResult};
+use crate::models::{PublicationJobData, WebsiteData, WebsiteId, WebsiteMetaFileContent};
+use crate::services::assets::website_asset_paths;
+use crate::services::JobManager;
+
+/// Copy a website to another storage connector
+///
+/// Logs the progress in the job and returns the ID of the copy.
+/// With `delete_source`, the website is removed from the source connector
+/// once the copy is verified.
+pub async fn transfer_website(
+    from: &dyn StorageConnector,
+    to: &dyn StorageConnector,
+    session: &serde_json::Value,
+    website_id: &WebsiteId,
+    delete_source: bool,
+    job: &mut PublicationJobData,
+    job_manager: &JobManager,
+) -> ConnectorResult<WebsiteId> {
+    let progress = |job: &mut PublicationJobData, message: String| {
+        job.log(message.clone());
+        job.base.message = message;
+        job_manager.update_job(job);
+    };
+
+    // Read the data and the list of assets before writing anything
+    progress(
+        job,
+        format!(
+            "Reading website {} from {}",
+            website_id,
+            from.display_name()
+        ),
+    );
+    let mut data = from.read_website(session, website_id).await?;
+    let mut meta = from
+        .get_website_meta(session, website_id)
+        .await?
+        .to_file_content();
+
+    // Moving would lose the assets which are not referenced
+    let paths = website_asset_paths(from, session, website_id, (!delete_source).then_some(&data))
+        .await
+        .map_err(|e| match e {
+            ConnectorError::NotSupported(_) => ConnectorError::NotSupported(format!(
+                "{} can not list the assets, the website can only be copied",
+                from.display_name()
+            )),
+            e => e,
+        })?;
+
+    progress(
+        job,
+        format!("Read the website and {} asset paths", paths.len()),
+    );
+
+    // Write and verify the copy, with asset URLs pointing to it
+    let new_website_id = to.create_website(session, &meta).await?;
+    let copied = async {
+        rewrite_asset_urls(
+            &mut data,
+            &mut meta,
+            website_id,
+            &new_website_id,
+            to.connector_id(),
+        )?;
+        if meta.image_url.is_some() {
+            to.set_website_meta(session, &new_website_id, &meta).await?;
+        }
+
+        progress(
+            job,
+            format!(
+                "Writing website {} to {}",
+                new_website_id,
+                to.display_name()
+            ),
+        );
+        to.update_website(session, &new_website_id, &data).await?;
+
+        let mut checksums = Vec::new();
+        if !paths.is_empty() {
+            progress(job, format!("Writing {} assets", paths.len()));
+        }
+        for path in &paths {
+            let path = format!("/{}", path);
+            match copy_asset(from, to, session, website_id, &new_website_id, &path).await? {
+                Some(checksum) => checksums.push((path, checksum)),
+                None => job.log(format!("Asset {} not found, skipped", path)),
+            }
+        }
+
+        progress(job, "Verifying the copy".to_string());
+        let copy = to.read_website(session, &new_website_id).await?;
+        if copy.revision() != data.revision() {
+            return Err(ConnectorError::Remote(
+                "The copied website data differs from the original".to_string(),
+            ));
+        }
+        for (path, checksum) in &checksums {
+            let mut copy = to.read_asset_stream(session, &new_website_id, path).await?;
+            if hash_stream(&mut copy.reader).await? != *checksum {
+                return Err(ConnectorError::Remote(format!(
+                    "The copied asset {} differs from the original",
+                    path
+                )));
+            }
+        }
+
+        Ok(())
+    };
+    if let Err(e) = copied.await {
+        if let Err(cleanup) = to.purge_website(session, &new_website_id).await {
+            tracing::warn!(
+                "Could not remove the failed copy {}: {}",
+                new_website_id,
+                cleanup
+            );
+        }
+        return Err(e);
+    }
+
+    if delete_source {
+        progress(
+            job,
+            format!(
+                "Deleting website {} from {}",
+                website_id,
+                from.display_name()
+            ),
+        );
+        from.delete_website(session, website_id).await?;
+    }
+
+    Ok(new_website_id)
+}
+
+/// Size of the buffer between the asset read from the source and written to the target
+const COPY_PIPE_SIZE: usize = 64 * 1024;
+
+/// Stream an asset from a website to its copy
+///
+/// Returns the SHA-256 of its content, or None when the source has no such asset.
+async fn copy_asset(
+    from: &dyn StorageConnector,
+    to: &dyn StorageConnector,
+    session: &serde_json::Value,
+    website_id: &WebsiteId,
+    new_website_id: &WebsiteId,
+    path: &str,
+) -> ConnectorResult<Option<Vec<u8>>> {
+    let mut source = match from.read_asset_stream(session, website_id, path).await {
+        Ok(source) => source,
+        Err(ConnectorError::NotFound(_)) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    let (mut sender, receiver) = tokio::io::duplex(COPY_PIPE_SIZE);
+    let mut hasher = Sha256::new();
+    let send = async {
+        let mut buffer = vec![0; COPY_PIPE_SIZE];
+        loop {
+            let read = source.reader.read(&mut buffer).await?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+            sender.write_all(&buffer[..read]).await?;
+        }
+        sender.shutdown().await?;
+        Ok::<_, ConnectorError>(())
+    };
+    // A failed read cancels the write
+    let write = to.write_asset_stream(session, new_website_id, path, Box::new(receiver));
+    tokio::try_join!(send, write)?;
+
+    Ok(Some(hasher.finalize().to_vec()))
+}
+
+/// Get the SHA-256 of a stream, reading it by chunks
+async fn hash_stream(
+    reader: &mut (impl tokio::io::AsyncRead + Unpin + ?Sized),
+) -> ConnectorResult<Vec<u8>> {
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; COPY_PIPE_SIZE];
+    loop {
+        let read = reader.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finalize().to_vec())
+}
+
+/// Point the asset URLs of website data and metadata to a copy of the website
+pub(crate) fn rewrite_asset_urls(
+    data: &mut WebsiteData,
+    meta: &mut WebsiteMetaFileContent,
+    from_website_id: &str,
+    to_website_id: &str,
+    to_connector_id: &str,
+) -> ConnectorResult<()> {
+    let mut value = serde_json::to_value(&*data)?;
+    website_files::rewrite_asset_urls(&mut value, from_website_id, to_website_id, to_connector_id);
+    *data = serde_json::from_value(value)?;
+
+    if let Some(image_url) = &mut meta.image_url {
+        let mut value = serde_json::Value::String(image_url.clone());
+        website_files::rewrite_asset_urls(
+            &mut value,
+            from_website_id,
+            to_website_id,
+            to_connector_id,
+        );
+        if let serde_json::Value::String(rewritten) = value {
+            *image_url = rewritten;
+        }
+    }
+
+    Ok(())
+}
+
+/// Start copying or moving a website in the background
+///
+/// Returns the job, which ends with the ID of the copy in its message.
+pub fn start_transfer(
+    from: Arc<dyn StorageConnector>,
+    to: Arc<dyn StorageConnector>,
+    session: serde_json::Value,
+    website_id: WebsiteId,
+    delete_source: bool,
+    job_manager: JobManager,
+) -> PublicationJobData {
+    let action = if delete_source { "Moving" } else { "Copying" };
+    let job = job_manager.start_job(format!(
+        "{} website {} from {} to {}",
+        action,
+        website_id,
+        from.display_name(),
+        to.display_name()
+    ));
+
+    let mut running = job.clone();
+    tokio::spawn(async move {
+        let result = transfer_website(
+            from.as_ref(),
+            to.as_ref(),
+            &session,
+            &website_id,
+            delete_source,
+            &mut running,
+            &job_manager,
+        )
+        .await;
+        match result {
+            Ok(new_website_id) => running.success(format!(
+                "Website copied to {} with ID: {}",
+                to.display_name(),
+                new_website_id
+            )),
+            Err(e) => running.fail(format!("Transfer failed: {}", e)),
+        }
+        job_manager.update_job(&running);
+    });
+
+    job
+}
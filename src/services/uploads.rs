@@ -0,0 +1,448 @@
+/*
+ * Silex website builder, free/libre no-code tool for makers.
+ * Copyright (c) 2023 lexoyo and Silex Labs foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or any later version.
+ */
+
+//! Checks of the uploaded assets
+//!
+//! So that a public server can not be used to host any file, uploads are:
+//! - limited in size, per file and per request (413 Payload Too Large)
+//! - limited to an allow-list of types, detected from the content of the
+//!   files and matching their extension (415 Unsupported Media Type)
+//! - renamed to safe file names, without hidden files or folders
+//!
+//! Binary files are recognized by their signature. Text files have no
+//! signature, their type comes from their extension, except SVG images which
+//! are recognized by their root element.
+
+use sha2::{Digest, Sha256};
+
+use crate::config::Config;
+use crate::error::{ConnectorError, ConnectorResult};
+
+/// Number of bytes at the beginning of a file which tell its type
+pub const SNIFF_LENGTH: usize = 8192;
+
+/// Type of files which are neither recognized nor text
+const BINARY_CONTENT_TYPE: &str = "application/octet-stream";
+
+/// Longest file or folder name, in bytes
+const MAX_NAME_LENGTH: usize = 200;
+
+/// Limits of the asset uploads
+#[derive(Debug, Clone)]
+pub struct UploadLimits {
+    /// Largest file, in bytes (None for no limit)
+    pub max_file_size: Option<usize>,
+
+    /// Largest total of the files of a request, in bytes (None for no limit)
+    pub max_request_size: Option<usize>,
+
+    /// Allowed MIME types, `type/*` allowing a whole type and `*/*` everything
+    pub allowed_types: Vec<String>,
+}
+
+impl UploadLimits {
+    /// Get the upload limits from config
+    pub fn from_config(config: &Config) -> Self {
+        let megabytes = |size: u64| (size > 0).then(|| (size * 1024 * 1024) as usize);
+        UploadLimits {
+            max_file_size: megabytes(config.assets_max_file_size_mb),
+            max_request_size: megabytes(config.assets_max_upload_size_mb),
+            allowed_types: config.assets_allowed_types.clone(),
+        }
+    }
+
+    /// Check the size of a file being received and the total of the request
+    pub fn check_size(
+        &self,
+        file_name: &str,
+        file_size: usize,
+        request_size: usize,
+    ) -> ConnectorResult<()> {
+        if let Some(max) = self.max_file_size.filter(|&max| file_size > max) {
+            return Err(ConnectorError::PayloadTooLarge(format!(
+                "File '{}' is larger than {} bytes",
+                file_name, max
+            )));
+        }
+        if let Some(max) = self.max_request_size.filter(|&max| request_size > max) {
+            return Err(ConnectorError::PayloadTooLarge(format!(
+                "Uploaded files are larger than {} bytes in total",
+                max
+            )));
+        }
+        Ok(())
+    }
+
+    /// Check the type of an uploaded file, and return it
+    ///
+    /// The type detected from the content must be allowed, and be one of the
+    /// types of the file's extension. `head` is the beginning of the file,
+    /// at least `SNIFF_LENGTH` bytes long unless the file is shorter.
+    pub fn check_type(&self, path: &str, head: &[u8]) -> ConnectorResult<String> {
+        let content_type = detect_content_type(path, head);
+
+        if !self.is_allowed(&content_type) {
+            return Err(ConnectorError::UnsupportedMediaType(format!(
+                "Files of type {} are not allowed ('{}')",
+                content_type, path
+            )));
+        }
+
+        let matches_extension = mime_guess::from_path(path)
+            .iter()
+            .any(|mime| mime.essence_str() == content_type);
+        if !matches_extension {
+            return Err(ConnectorError::UnsupportedMediaType(format!(
+                "The content of '{}' is of type {}, which does not match its extension",
+                path, content_type
+            )));
+        }
+
+        Ok(content_type)
+    }
+
+    /// Whether a MIME type is in the allow-list
+    fn is_allowed(&self, content_type: &str) -> bool {
+        let main_type = content_type.split('/').next().unwrap_or_default();
+        self.allowed_types
+            .iter()
+            .any(|allowed| match allowed.strip_suffix("/*") {
+                Some("*") => true,
+                Some(allowed_main_type) => allowed_main_type == main_type,
+                None => allowed == content_type,
+            })
+    }
+}
+
+/// Detect the MIME type of a file from its beginning
+fn detect_content_type(path: &str, head: &[u8]) -> String {
+    if is_svg(head) {
+        return "image/svg+xml".to_string();
+    }
+
+    // Text files of some kind are recognized by infer (HTML, XML, scripts),
+    // others only by their extension
+    let is_text = is_text(head);
+    if let Some(kind) = infer::get(head).filter(|_| !is_text) {
+        // The types of infer are not always the registered ones (e.g. fonts)
+        return mime_guess::from_ext(kind.extension())
+            .first()
+            .map(|mime| mime.essence_str().to_string())
+            .unwrap_or_else(|| kind.mime_type().to_string());
+    }
+    if !is_text {
+        return BINARY_CONTENT_TYPE.to_string();
+    }
+
+    mime_guess::from_path(path)
+        .iter()
+        .map(|mime| mime.essence_str().to_string())
+        .find(|mime| is_text_type(mime))
+        .unwrap_or_else(|| "text/plain".to_string())
+}
+
+/// Whether the beginning of a file is text: UTF-8 without null character
+///
+/// The last character may be cut.
+fn is_text(head: &[u8]) -> bool {
+    let valid = match std::str::from_utf8(head) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    };
+    valid && !head.contains(&0)
+}
+
+/// Whether a MIME type is a text format
+///
+/// SVG is excluded, it is only given to files with an `<svg>` root element.
+fn is_text_type(content_type: &str) -> bool {
+    content_type.starts_with("text/")
+        || matches!(
+            content_type,
+            "application/json" | "application/javascript" | "application/xml"
+        )
+}
+
+/// Whether a file is an SVG image: XML whose first element is `<svg`
+fn is_svg(content: &[u8]) -> bool {
+    let text = String::from_utf8_lossy(&content[..content.len().min(4096)]);
+    let mut rest = text.trim_start_matches('\u{feff}').trim_start();
+    // Skip the XML declaration, comments and doctype
+    while rest.starts_with("<?") || rest.starts_with("<!") {
+        let end = if rest.starts_with("<!--") {
+            rest.find("-->").map(|i| i + 3)
+        } else {
+            rest.find('>').map(|i| i + 1)
+        };
+        match end {
+            Some(end) => rest = rest[end..].trim_start(),
+            None => return false,
+        }
+    }
+    rest.starts_with("<svg")
+}
+
+/// Make a safe asset path from the name of an uploaded file
+///
+/// Folders are kept, but `.`, `..` and empty parts are removed, and names
+/// keep ASCII letters, digits, `-`, `_` and `.` only. Accented latin letters
+/// lose their accent (`é` becomes `e`), other characters become `-`.
+/// Names can not start with a dot, so that no hidden file is created.
+/// The result has a leading slash.
+///
+/// Names with other non ASCII characters (e.g. `写真.png`) get a hash of
+/// the original name, so that they do not all end up as the same file.
+pub fn sanitize_file_name(file_name: &str) -> String {
+    let parts: Vec<String> = file_name
+        .split(['/', '\\'])
+        .map(|part| {
+            let mut name = String::with_capacity(part.len());
+            let mut lost = false;
+            for c in part.chars() {
+                if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                    name.push(c);
+                } else if let Some(ascii) = transliterate(c) {
+                    name.push_str(ascii);
+                } else {
+                    lost |= !c.is_ascii();
+                    name.push('-');
+                }
+            }
+            let name = name.trim_start_matches('.');
+            let (stem, extension) = match name.rsplit_once('.') {
+                Some((stem, extension)) if extension.len() < 16 => (stem, Some(extension)),
+                _ => (name, None),
+            };
+            let hash = if lost {
+                let digest = Sha256::digest(part.as_bytes());
+                let hash: String = digest[..4]
+                    .iter()
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect();
+                format!("-{}", hash)
+            } else {
+                String::new()
+            };
+            // Cut long names, keeping their hash and extension
+            let suffix_length = hash.len() + extension.map_or(0, |extension| extension.len() + 1);
+            let stem = &stem[..stem
+                .len()
+                .min(MAX_NAME_LENGTH.saturating_sub(suffix_length))];
+            match extension {
+                Some(extension) => format!("{}{}.{}", stem, hash, extension),
+                None => format!("{}{}", stem, hash),
+            }
+        })
+        .filter(|name| !name.is_empty())
+        .collect();
+
+    if parts.is_empty() {
+        return "/file".to_string();
+    }
+    format!("/{}", parts.join("/"))
+}
+
+/// ASCII form of the accented latin letters
+fn transliterate(c: char) -> Option<&'static str> {
+    let ascii = match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+        'À' | 'Á' | 'Â' | 'Ã' | 'Ä' | 'Å' | 'Ā' | 'Ă' | 'Ą' => "A",
+        'æ' => "ae",
+        'Æ' => "AE",
+        'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => "c",
+        'Ç' | 'Ć' | 'Ĉ' | 'Ċ' | 'Č' => "C",
+        'ď' | 'đ' | 'ð' => "d",
+        'Ď' | 'Đ' | 'Ð' => "D",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => "e",
+        'È' | 'É' | 'Ê' | 'Ë' | 'Ē' | 'Ĕ' | 'Ė' | 'Ę' | 'Ě' => "E",
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' => "g",
+        'Ĝ' | 'Ğ' | 'Ġ' | 'Ģ' => "G",
+        'ĥ' | 'ħ' => "h",
+        'Ĥ' | 'Ħ' => "H",
+        'ì' | 'í' | 'î' | 'ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => "i",
+        'Ì' | 'Í' | 'Î' | 'Ï' | 'Ĩ' | 'Ī' | 'Ĭ' | 'Į' | 'İ' => "I",
+        'ĵ' => "j",
+        'Ĵ' => "J",
+        'ķ' => "k",
+        'Ķ' => "K",
+        'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => "l",
+        'Ĺ' | 'Ļ' | 'Ľ' | 'Ŀ' | 'Ł' => "L",
+        'ñ' | 'ń' | 'ņ' | 'ň' => "n",
+        'Ñ' | 'Ń' | 'Ņ' | 'Ň' => "N",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => "o",
+        'Ò' | 'Ó' | 'Ô' | 'Õ' | 'Ö' | 'Ø' | 'Ō' | 'Ŏ' | 'Ő' => "O",
+        'œ' => "oe",
+        'Œ' => "OE",
+        'ŕ' | 'ŗ' | 'ř' => "r",
+        'Ŕ' | 'Ŗ' | 'Ř' => "R",
+        'ś' | 'ŝ' | 'ş' | 'š' => "s",
+        'Ś' | 'Ŝ' | 'Ş' | 'Š' => "S",
+        'ß' => "ss",
+        'ţ' | 'ť' | 'ŧ' => "t",
+        'Ţ' | 'Ť' | 'Ŧ' => "T",
+        'þ' => "th",
+        'Þ' => "TH",
+        'ù' | 'ú' | 'û' | 'ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => "u",
+        'Ù' | 'Ú' | 'Û' | 'Ü' | 'Ũ' | 'Ū' | 'Ŭ' | 'Ů' | 'Ű' | 'Ų' => "U",
+        'ŵ' => "w",
+        'Ŵ' => "W",
+        'ý' | 'ÿ' | 'ŷ' => "y",
+        'Ý' | 'Ÿ' | 'Ŷ' => "Y",
+        'ź' | 'ż' | 'ž' => "z",
+        'Ź' | 'Ż' | 'Ž' => "Z",
+        _ => return None,
+    };
+    Some(ascii)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\0\x01\0\0\0\x01\x08\x06\0\0\0";
+    const JPEG: &[u8] = b"\xff\xd8\xff\xe0\0\x10JFIF\0\x01\x01\0\0\x01\0\x01\0\0";
+
+    /// Limits allowing some types
+    fn limits(allowed_types: &[&str]) -> UploadLimits {
+        UploadLimits {
+            max_file_size: Some(10),
+            max_request_size: Some(15),
+            allowed_types: allowed_types.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn sanitize_keeps_safe_names() {
+        assert_eq!(sanitize_file_name("photo.jpg"), "/photo.jpg");
+        assert_eq!(
+            sanitize_file_name("img/My_photo-1.JPG"),
+            "/img/My_photo-1.JPG"
+        );
+    }
+
+    #[test]
+    fn sanitize_removes_traversal_and_hidden_files() {
+        assert_eq!(sanitize_file_name("../../etc/passwd"), "/etc/passwd");
+        assert_eq!(sanitize_file_name("a\\..\\b.png"), "/a/b.png");
+        assert_eq!(sanitize_file_name("/.htaccess"), "/htaccess");
+        assert_eq!(sanitize_file_name("a//b.png"), "/a/b.png");
+        assert_eq!(sanitize_file_name(".."), "/file");
+        assert_eq!(sanitize_file_name(""), "/file");
+    }
+
+    #[test]
+    fn sanitize_replaces_special_characters() {
+        assert_eq!(sanitize_file_name("my photo (1).png"), "/my-photo--1-.png");
+        assert_eq!(sanitize_file_name("Été à Noël.png"), "/Ete-a-Noel.png");
+    }
+
+    #[test]
+    fn sanitize_hashes_other_scripts() {
+        let first = sanitize_file_name("写真.png");
+        let second = sanitize_file_name("画像.png");
+
+        assert!(first.starts_with("/---"), "{}", first);
+        assert!(first.ends_with(".png"));
+        assert_ne!(first, second);
+        assert_eq!(first, sanitize_file_name("写真.png"));
+    }
+
+    #[test]
+    fn sanitize_cuts_long_names() {
+        let name = format!("{}.png", "a".repeat(300));
+        let sanitized = sanitize_file_name(&name);
+
+        assert_eq!(sanitized.len(), MAX_NAME_LENGTH + 1);
+        assert!(sanitized.ends_with("a.png"));
+    }
+
+    #[test]
+    fn detect_binary_types_from_content() {
+        assert_eq!(detect_content_type("a.png", PNG), "image/png");
+        assert_eq!(detect_content_type("a.jpg", JPEG), "image/jpeg");
+        // The extension does not matter for recognized content
+        assert_eq!(detect_content_type("a.txt", PNG), "image/png");
+        assert_eq!(
+            detect_content_type("a.pdf", b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n"),
+            "application/pdf"
+        );
+        assert_eq!(
+            detect_content_type("a.bin", b"\0\x01\x02\x03"),
+            BINARY_CONTENT_TYPE
+        );
+    }
+
+    #[test]
+    fn detect_text_types_from_extension() {
+        assert_eq!(
+            detect_content_type("a.css", b"body { color: red }"),
+            "text/css"
+        );
+        assert_eq!(
+            detect_content_type("a.json", b"{\"a\": 1}"),
+            "application/json"
+        );
+        assert_eq!(detect_content_type("a.png", b"just text"), "text/plain");
+        // A cut multi-byte character at the end is still text
+        assert_eq!(
+            detect_content_type("a.txt", "é".as_bytes()[..1].as_ref()),
+            "text/plain"
+        );
+    }
+
+    #[test]
+    fn detect_svg_by_root_element() {
+        let svg = b"\xef\xbb\xbf<?xml version=\"1.0\"?>\n<!-- a > b -->\n<!DOCTYPE svg>\n<svg xmlns=\"http://www.w3.org/2000/svg\"/>";
+        assert_eq!(detect_content_type("a.svg", svg), "image/svg+xml");
+        assert_eq!(detect_content_type("a.txt", b"<svg/>"), "image/svg+xml");
+        assert_ne!(
+            detect_content_type("a.svg", b"<html><svg/></html>"),
+            "image/svg+xml"
+        );
+    }
+
+    #[test]
+    fn check_type_requires_an_allowed_type_matching_the_extension() {
+        let limits = limits(&["image/png", "text/*"]);
+
+        assert_eq!(limits.check_type("/a.png", PNG).unwrap(), "image/png");
+        assert_eq!(limits.check_type("/a.css", b"a {}").unwrap(), "text/css");
+        assert!(matches!(
+            limits.check_type("/a.jpg", JPEG),
+            Err(ConnectorError::UnsupportedMediaType(_))
+        ));
+        // A PNG named as a text file
+        assert!(matches!(
+            limits.check_type("/a.txt", PNG),
+            Err(ConnectorError::UnsupportedMediaType(_))
+        ));
+        assert!(self::limits(&["*/*"]).check_type("/a.jpg", JPEG).is_ok());
+    }
+
+    #[test]
+    fn check_size_of_files_and_requests() {
+        let limits = limits(&[]);
+
+        assert!(limits.check_size("a", 10, 15).is_ok());
+        assert!(matches!(
+            limits.check_size("a", 11, 11),
+            Err(ConnectorError::PayloadTooLarge(_))
+        ));
+        assert!(matches!(
+            limits.check_size("a", 5, 16),
+            Err(ConnectorError::PayloadTooLarge(_))
+        ));
+        let unlimited = UploadLimits {
+            max_file_size: None,
+            max_request_size: None,
+            allowed_types: Vec::new(),
+        };
+        assert!(unlimited.check_size("a", usize::MAX, usize::MAX).is_ok());
+    }
+}